@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "build-info")]
+    {
+        let mut config = tonic_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::configure()
+            .build_client(false)
+            .build_transport(false)
+            .compile_protos_with_config(config, &["proto/build_info.proto"], &["proto"])?;
+    }
+    Ok(())
+}
@@ -1,16 +1,69 @@
+/// Blocking channels for synchronous applications
+#[cfg(feature = "blocking")]
+pub mod blocking;
+/// A gRPC service answering the build of a server
+#[cfg(feature = "build-info")]
+pub mod build_info;
+/// Typed clients sharing one channel
+pub mod client;
+/// Connection details and errors of channels
+pub mod connection;
+/// Insecure TLS options for local development
+#[cfg(feature = "dangerous-dev-tls")]
+pub mod dangerous;
+/// Rich error details of a status, as `google.rpc.Status`
+#[cfg(feature = "error-details")]
+pub mod error_details;
 /// Interceptors for the gRPC channel
 pub mod interceptor;
+/// Tower layers for gRPC channels and servers
+pub mod layer;
+/// Redacted dumps of metadata for bug reports
+pub mod metadata_dump;
+/// Client profiles loaded from TOML files
+#[cfg(feature = "toml")]
+pub mod profile;
+/// Connections through HTTP proxies
+pub mod proxy;
+/// The rustls connector of the channels with custom TLS
+#[cfg(any(feature = "dangerous-dev-tls", feature = "server-certificate"))]
+mod rustls_channel;
+/// TLS channels handing the server certificates to a callback
+#[cfg(feature = "server-certificate")]
+pub mod server_certificate;
+/// Background tasks of the crate, shut down together
+pub mod tasks;
+/// TLS configuration from PEM in memory
+pub mod tls;
+/// Eager connection setup for channels
+pub mod warm_up;
 
-/// Creates a [tonic::transport::Channel] for the endpoint using the given 
+/// Creates a [tonic::transport::Channel] for the endpoint using the given
 /// TLS configuration
 pub async fn channel(
     tls: tonic::transport::ClientTlsConfig,
     endpoint: tonic::transport::Endpoint,
 ) -> Result<tonic::transport::Channel, Box<dyn std::error::Error>> {
-    Ok(endpoint
+    Ok(configure(tls, endpoint)?.connect().await?)
+}
+
+/// The TCP keepalive interval of the channels of the crate
+pub(crate) const TCP_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Applies the keep-alive settings and the TLS configuration of [channel]
+pub(crate) fn configure(
+    tls: tonic::transport::ClientTlsConfig,
+    endpoint: tonic::transport::Endpoint,
+) -> Result<tonic::transport::Endpoint, tonic::transport::Error> {
+    endpoint
         .keep_alive_while_idle(true)
-        .tcp_keepalive(Some(std::time::Duration::from_secs(60)))
-        .tls_config(tls)?
-        .connect()
-        .await?)
+        .tcp_keepalive(Some(TCP_KEEPALIVE))
+        .tls_config(tls)
+}
+
+/// Sets the TCP keepalive of [configure] on a stream of a custom connector,
+/// which tonic does not configure
+pub(crate) fn set_tcp_keepalive(stream: &tokio::net::TcpStream) -> std::io::Result<()> {
+    let keepalive = socket2::TcpKeepalive::new().with_time(TCP_KEEPALIVE);
+    socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)
 }
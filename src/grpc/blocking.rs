@@ -0,0 +1,193 @@
+use std::future::Future;
+
+use tokio::runtime::{Handle, Runtime};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+use crate::grpc::connection::ChannelError;
+
+fn ensure_outside_runtime() -> Result<(), ChannelError> {
+    match Handle::try_current() {
+        Ok(_) => Err(ChannelError::InsideRuntime),
+        Err(_) => Ok(()),
+    }
+}
+
+/// A channel with its own runtime, for applications without async code
+///
+/// The generated clients are async, so calls are driven with
+/// [BlockingChannel::call] or [BlockingChannel::block_on]:
+/// ```ignore
+/// let channel = grpc_utils_rs::grpc::blocking::channel(tls, endpoint)?;
+/// let response = channel.call(|channel| async move {
+///     GreeterClient::new(channel).say_hello(request).await
+/// })??;
+/// ```
+/// Dropping the channel shuts the runtime down.
+pub struct BlockingChannel {
+    runtime: Option<Runtime>,
+    channel: Channel,
+}
+
+/// Creates a [BlockingChannel] like [crate::grpc::channel]
+///
+/// Fails with [ChannelError::InsideRuntime], when it is called inside of a
+/// tokio runtime; use the async channel there.
+pub fn channel(tls: ClientTlsConfig, endpoint: Endpoint) -> Result<BlockingChannel, ChannelError> {
+    ensure_outside_runtime()?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(ChannelError::Runtime)?;
+    let channel = runtime
+        .block_on(async { crate::grpc::configure(tls, endpoint)?.connect().await })
+        .map_err(ChannelError::Connect)?;
+    Ok(BlockingChannel {
+        runtime: Some(runtime),
+        channel,
+    })
+}
+
+impl BlockingChannel {
+    /// Returns the async channel, e.g. to create a client
+    pub fn channel(&self) -> Channel {
+        self.channel.clone()
+    }
+
+    /// Runs the future on the runtime of the channel, until it is complete
+    pub fn block_on<F: Future>(&self, future: F) -> Result<F::Output, ChannelError> {
+        ensure_outside_runtime()?;
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("the runtime is only taken when dropped");
+        Ok(runtime.block_on(future))
+    }
+
+    /// Calls the function with the channel and waits for the returned future
+    /// # Arguments
+    /// * `call`: A function making the calls, e.g. with a generated client
+    pub fn call<F, Fut>(&self, call: F) -> Result<Fut::Output, ChannelError>
+    where
+        F: FnOnce(Channel) -> Fut,
+        Fut: Future,
+    {
+        self.block_on(call(self.channel()))
+    }
+}
+
+impl Drop for BlockingChannel {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            // A runtime can not be dropped within another one
+            if Handle::try_current().is_ok() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use http::HeaderMap;
+    use http_body::Frame;
+    use http_body_util::StreamBody;
+    use tonic::body::BoxBody;
+    use tonic::server::NamedService;
+    use tonic::transport::{ClientTlsConfig, Endpoint};
+    use tower_service::Service;
+
+    use crate::grpc::blocking;
+    use crate::grpc::connection::ChannelError;
+
+    /// A server answering every call with an empty message
+    #[derive(Clone)]
+    struct Empty;
+
+    impl NamedService for Empty {
+        const NAME: &'static str = "test.Empty";
+    }
+
+    impl Service<http::Request<BoxBody>> for Empty {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<BoxBody>) -> Self::Future {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            let frames = [
+                Ok::<_, Infallible>(Frame::data(Bytes::from_static(&[0, 0, 0, 0, 0]))),
+                Ok(Frame::trailers(trailers)),
+            ];
+            let response = http::Response::builder()
+                .header("content-type", "application/grpc")
+                .body(tonic::body::boxed(StreamBody::new(
+                    futures_util::stream::iter(frames),
+                )))
+                .unwrap();
+            Box::pin(async { Ok(response) })
+        }
+    }
+
+    /// Starts the server on its own thread and returns its endpoint
+    fn start_server() -> Endpoint {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let incoming =
+                    tonic::transport::server::TcpIncoming::from_listener(listener, true, None)
+                        .unwrap();
+                tonic::transport::Server::builder()
+                    .add_service(Empty)
+                    .serve_with_incoming(incoming)
+                    .await
+                    .unwrap();
+            });
+        });
+        Endpoint::from_shared(format!("http://{addr}")).unwrap()
+    }
+
+    #[test]
+    fn test_unary_call() {
+        let channel = blocking::channel(ClientTlsConfig::new(), start_server()).unwrap();
+
+        let response = channel
+            .call(|channel| async move {
+                let mut client = tonic::client::Grpc::new(channel);
+                client.ready().await.unwrap();
+                client
+                    .unary(
+                        tonic::Request::new(()),
+                        http::uri::PathAndQuery::from_static("/test.Empty/Call"),
+                        tonic::codec::ProstCodec::<(), ()>::default(),
+                    )
+                    .await
+            })
+            .unwrap();
+
+        assert!(response.is_ok());
+        drop(channel);
+    }
+
+    #[tokio::test]
+    async fn test_inside_runtime() {
+        let endpoint = Endpoint::from_static("http://127.0.0.1:1");
+        let result = blocking::channel(ClientTlsConfig::new(), endpoint);
+
+        assert!(matches!(result, Err(ChannelError::InsideRuntime)));
+    }
+}
@@ -0,0 +1,169 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http_body::Body;
+use tonic::body::BoxBody;
+use tonic::server::NamedService;
+use tonic::Status;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+/// The messages and the server generated from `proto/build_info.proto`
+pub mod proto {
+    tonic::include_proto!("grpc_utils");
+}
+
+use proto::build_info_server::{self, BuildInfoServer};
+pub use proto::GetBuildInfoRequest;
+/// The build of a server, as answered by the [BuildInfoService]
+///
+/// Fill it with [build_info!](crate::build_info) in the application, so the
+/// values are those of the application and not of this crate.
+pub use proto::GetBuildInfoResponse as BuildInfo;
+
+/// The path of the `Get` method
+pub const GET_PATH: &str = "/grpc_utils.BuildInfo/Get";
+
+/// Creates a [BuildInfo] for the crate, that calls the macro
+///
+/// The name and version are taken from `CARGO_PKG_NAME` and
+/// `CARGO_PKG_VERSION`, the git commit and build time from the
+/// `VERGEN_GIT_SHA` and `VERGEN_BUILD_TIMESTAMP` variables at compile time,
+/// if they are set (e.g. by `vergen` in a build script). The enabled
+/// features can be given as a list:
+/// ```
+/// let info = grpc_utils_rs::build_info!(features: ["blocking"]);
+/// assert_eq!(vec!["blocking"], info.features);
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::build_info!(features: [])
+    };
+    ( features: [ $($feature:expr),* $(,)? ] ) => {
+        $crate::grpc::build_info::BuildInfo {
+            name: ::std::string::String::from(::std::env!("CARGO_PKG_NAME")),
+            version: ::std::string::String::from(::std::env!("CARGO_PKG_VERSION")),
+            git_sha: ::std::string::String::from(
+                ::std::option_env!("VERGEN_GIT_SHA").unwrap_or_default(),
+            ),
+            build_timestamp: ::std::string::String::from(
+                ::std::option_env!("VERGEN_BUILD_TIMESTAMP").unwrap_or_default(),
+            ),
+            features: ::std::vec![$(::std::string::String::from($feature)),*],
+        }
+    };
+}
+
+/// A gRPC service answering, which build of the server is running
+///
+/// It implements the `grpc_utils.BuildInfo` service of
+/// `proto/build_info.proto`, so clients in any language can call it. Add it
+/// with `tonic::transport::Server::builder().add_service(...)`.
+#[derive(Clone, Debug)]
+pub struct BuildInfoService {
+    inner: BuildInfoServer<Get>,
+}
+
+impl BuildInfoService {
+    /// Creates a new service
+    /// # Arguments
+    /// * `info`: The build to answer with, see [build_info!](crate::build_info)
+    pub fn new(info: BuildInfo) -> Self {
+        Self {
+            inner: BuildInfoServer::new(Get(Arc::new(info))),
+        }
+    }
+}
+
+impl NamedService for BuildInfoService {
+    const NAME: &'static str = <BuildInfoServer<Get> as NamedService>::NAME;
+}
+
+#[derive(Debug)]
+struct Get(Arc<BuildInfo>);
+
+#[tonic::async_trait]
+impl build_info_server::BuildInfo for Get {
+    async fn get(
+        &self,
+        _request: tonic::Request<GetBuildInfoRequest>,
+    ) -> Result<tonic::Response<BuildInfo>, Status> {
+        Ok(tonic::Response::new(self.0.as_ref().clone()))
+    }
+}
+
+impl<B> Service<http::Request<B>> for BuildInfoService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<BoxError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = tonic::codegen::BoxFuture<Self::Response, Infallible>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Service::<http::Request<B>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tonic::codec::ProstCodec;
+    use tonic::transport::Endpoint;
+
+    use crate::grpc::build_info::{BuildInfo, BuildInfoService, GetBuildInfoRequest, GET_PATH};
+
+    #[tokio::test]
+    async fn test_get() {
+        let info = crate::build_info!(features: ["build-info", "blocking"]);
+        assert_eq!("grpc-utils-rs", info.name);
+        assert_eq!(env!("CARGO_PKG_VERSION"), info.version);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(BuildInfoService::new(info.clone()))
+                .serve_with_incoming(incoming),
+        );
+
+        let channel = Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        let response = client
+            .unary(
+                tonic::Request::new(GetBuildInfoRequest {}),
+                http::uri::PathAndQuery::from_static(GET_PATH),
+                ProstCodec::<GetBuildInfoRequest, BuildInfo>::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(info, response.into_inner());
+
+        client.ready().await.unwrap();
+        let status = client
+            .unary(
+                tonic::Request::new(GetBuildInfoRequest {}),
+                http::uri::PathAndQuery::from_static("/grpc_utils.BuildInfo/Unknown"),
+                ProstCodec::<GetBuildInfoRequest, BuildInfo>::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unimplemented, status.code());
+    }
+}
@@ -0,0 +1,573 @@
+// tonic::Status, the error of interceptors and services, is larger than
+// clippy::result_large_err allows
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+
+use tokio::sync::OnceCell;
+use tonic::body::BoxBody;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tower_service::Service;
+
+#[cfg(feature = "toml")]
+use crate::grpc::connection::ChannelError;
+use crate::grpc::interceptor::{CompositeInterceptor, Interceptors};
+use crate::grpc::layer::BoxError;
+#[cfg(feature = "toml")]
+use crate::grpc::profile::ClientProfile;
+#[cfg(feature = "toml")]
+use crate::grpc::warm_up::{warm_up, EndpointState};
+
+/// The service of the clients created by a [ClientFactory]
+pub type FactoryService = InterceptedService<Channel, CompositeInterceptor>;
+
+/// A client, that can be created from a channel and interceptors
+///
+/// Implement it for generated clients with
+/// [client_factory!](crate::client_factory).
+pub trait FromInterceptedChannel {
+    /// Creates the client, that calls the interceptor on every request
+    fn from_intercepted_channel(channel: Channel, interceptor: CompositeInterceptor) -> Self;
+}
+
+impl FromInterceptedChannel for FactoryService {
+    fn from_intercepted_channel(channel: Channel, interceptor: CompositeInterceptor) -> Self {
+        InterceptedService::new(channel, interceptor)
+    }
+}
+
+/// Implements [FromInterceptedChannel] for a generated client
+///
+/// The client must be in scope by its name:
+/// ```ignore
+/// use greeter::greeter_client::GreeterClient;
+///
+/// grpc_utils_rs::client_factory!(GreeterClient);
+/// ```
+#[macro_export]
+macro_rules! client_factory {
+    ( $client:ident ) => {
+        impl $crate::grpc::client::FromInterceptedChannel
+            for $client<$crate::grpc::client::FactoryService>
+        {
+            fn from_intercepted_channel(
+                channel: ::tonic::transport::Channel,
+                interceptor: $crate::grpc::interceptor::CompositeInterceptor,
+            ) -> Self {
+                $client::with_interceptor(channel, interceptor)
+            }
+        }
+    };
+}
+
+/// Creates typed clients sharing one channel and one interceptor chain
+///
+/// Connections are not duplicated, as all clients use the same channel:
+/// ```ignore
+/// let factory = ClientFactory::connect(tls, endpoint, interceptors).await?;
+/// let greeter: GreeterClient<_> = factory.client();
+/// let orders: OrderClient<_> = factory.client();
+/// ```
+#[derive(Clone)]
+pub struct ClientFactory {
+    channel: Channel,
+    interceptors: Interceptors,
+}
+
+impl ClientFactory {
+    /// Creates a factory for an existing channel
+    /// # Arguments
+    /// * `channel`: The channel of all clients
+    /// * `interceptors`: The chain called on every request of all clients
+    pub fn new(channel: Channel, interceptors: Interceptors) -> Self {
+        Self {
+            channel,
+            interceptors,
+        }
+    }
+
+    /// Creates a factory with a channel like [crate::grpc::channel]
+    pub async fn connect(
+        tls: ClientTlsConfig,
+        endpoint: Endpoint,
+        interceptors: Interceptors,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let channel = crate::grpc::channel(tls, endpoint).await?;
+        Ok(Self::new(channel, interceptors))
+    }
+
+    /// Creates a factory with the endpoint, TLS settings and interceptors of
+    /// the profile
+    ///
+    /// The channel is configured like the one of [crate::grpc::channel],
+    /// without TLS for `http` endpoints, and warmed up with the options of
+    /// [ClientProfile::with_warm_up].
+    #[cfg(feature = "toml")]
+    pub async fn from_profile(profile: &ClientProfile) -> Result<Self, ChannelError> {
+        let endpoint = match profile.tls() {
+            Some(tls) => crate::grpc::configure(tls.clone(), profile.endpoint().clone())
+                .map_err(ChannelError::Connect)?,
+            None => profile
+                .endpoint()
+                .clone()
+                .keep_alive_while_idle(true)
+                .tcp_keepalive(Some(crate::grpc::TCP_KEEPALIVE)),
+        };
+        let channel = match profile.warm_up() {
+            Some(options) => {
+                let report = warm_up([endpoint], options.clone()).await;
+                match report.endpoints.into_iter().next().map(|(_, state)| state) {
+                    Some(EndpointState::Ready(channel)) => channel,
+                    Some(EndpointState::Failed(e)) => return Err(ChannelError::NotReady(e)),
+                    _ => {
+                        return Err(ChannelError::DeadlineExceeded {
+                            elapsed: options.timeout(),
+                        })
+                    }
+                }
+            }
+            None => endpoint.connect().await.map_err(ChannelError::Connect)?,
+        };
+        Ok(Self::new(channel, profile.interceptors()))
+    }
+
+    /// Returns a new client on the shared channel
+    pub fn client<T: FromInterceptedChannel>(&self) -> T {
+        T::from_intercepted_channel(
+            self.channel.clone(),
+            CompositeInterceptor::new(self.interceptors.clone()),
+        )
+    }
+}
+
+/// Connects a channel like [crate::grpc::channel] and returns a client,
+/// that calls the interceptors on every request
+///
+/// Use a [ClientFactory] for several clients of the same endpoint.
+pub async fn connect_client<T: FromInterceptedChannel>(
+    tls: ClientTlsConfig,
+    endpoint: Endpoint,
+    interceptors: Interceptors,
+) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(ClientFactory::connect(tls, endpoint, interceptors)
+        .await?
+        .client())
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<http::Response<BoxBody>, BoxError>> + Send>>;
+
+/// A service, that can be cloned behind a box
+trait CloneService: Send {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>>;
+    fn call(&mut self, req: http::Request<BoxBody>) -> BoxFuture;
+    fn clone_box(&self) -> Box<dyn CloneService>;
+}
+
+impl<S> CloneService for S
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        Service::poll_ready(self, cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> BoxFuture {
+        let future = Service::call(self, req);
+        Box::pin(async move { future.await.map_err(Into::into) })
+    }
+
+    fn clone_box(&self) -> Box<dyn CloneService> {
+        Box::new(self.clone())
+    }
+}
+
+/// A channel with any interceptor, whose type does not show in the clients
+///
+/// Clients on it have the same type regardless of the interceptors, so
+/// they can be stored in structs and collections:
+/// ```ignore
+/// struct State {
+///     greeter: GreeterClient<InterceptedChannel>,
+/// }
+///
+/// let greeter = GreeterClient::new(InterceptedChannel::new(channel, interceptors));
+/// ```
+pub struct InterceptedChannel {
+    inner: Box<dyn CloneService>,
+}
+
+impl InterceptedChannel {
+    /// Creates a channel, that calls the interceptors on every request
+    /// # Arguments
+    /// * `channel`: The underlying channel
+    /// * `interceptors`: The chain called on every request
+    pub fn new(channel: Channel, interceptors: Interceptors) -> Self {
+        Self::from(InterceptedService::new(
+            channel,
+            CompositeInterceptor::new(interceptors),
+        ))
+    }
+}
+
+impl Clone for InterceptedChannel {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone_box(),
+        }
+    }
+}
+
+impl From<Channel> for InterceptedChannel {
+    fn from(channel: Channel) -> Self {
+        Self {
+            inner: Box::new(channel),
+        }
+    }
+}
+
+impl<I> From<InterceptedService<Channel, I>> for InterceptedChannel
+where
+    I: Interceptor + Clone + Send + 'static,
+{
+    fn from(service: InterceptedService<Channel, I>) -> Self {
+        Self {
+            inner: Box::new(service),
+        }
+    }
+}
+
+impl Service<http::Request<BoxBody>> for InterceptedChannel {
+    type Response = http::Response<BoxBody>;
+    type Error = BoxError;
+    type Future = BoxFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+/// The channel of a [ClientRegistry] entry, connected at most once
+///
+/// The mutex makes the cell shareable, as [InterceptedChannel] is not Sync.
+type RegistryEntry = Arc<OnceCell<Mutex<InterceptedChannel>>>;
+
+/// Shares one [InterceptedChannel] per name, connected on first use
+///
+/// Concurrent first callers of a name wait for the same connect, so every
+/// name is connected once. If the connect fails, the next caller tries
+/// again. Use [ClientRegistry::global] for a registry of the whole process,
+/// or own one in the application state:
+/// ```ignore
+/// let channel = registry
+///     .get_or_connect("payments", || async {
+///         let channel = grpc_utils_rs::grpc::channel(tls, endpoint).await?;
+///         Ok::<_, Box<dyn std::error::Error>>(InterceptedChannel::new(channel, interceptors))
+///     })
+///     .await?;
+/// let payments = PaymentsClient::new(channel);
+/// ```
+///
+/// The registry only hands out clones, so evicting an entry or dropping the
+/// registry closes no channel, that is still in use.
+#[derive(Clone, Default)]
+pub struct ClientRegistry {
+    entries: Arc<Mutex<HashMap<String, RegistryEntry>>>,
+}
+
+impl ClientRegistry {
+    /// Creates a new, empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the registry of the process
+    pub fn global() -> &'static ClientRegistry {
+        static GLOBAL: OnceLock<ClientRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(ClientRegistry::new)
+    }
+
+    fn entry(&self, name: &str) -> RegistryEntry {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the channel of the name, connecting it first, if it is not
+    /// connected yet
+    /// # Arguments
+    /// * `name`: The name of the channel, e.g. `payments`
+    /// * `connect`: Connects the channel, only called for the first use
+    pub async fn get_or_connect<F, Fut, E>(
+        &self,
+        name: &str,
+        connect: F,
+    ) -> Result<InterceptedChannel, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<InterceptedChannel, E>>,
+    {
+        let entry = self.entry(name);
+        let channel = entry
+            .get_or_try_init(|| async { connect().await.map(Mutex::new) })
+            .await?;
+        Ok(channel.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    /// Removes the channel of the name, so the next use connects a new one,
+    /// e.g. with the interceptors of rotated credentials
+    ///
+    /// Returns true, if the name was registered. Clients on the removed
+    /// channel keep working.
+    pub fn evict(&self, name: &str) -> bool {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name)
+            .is_some()
+    }
+
+    /// Returns true, if the channel of the name is connected
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .is_some_and(|entry| entry.initialized())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::net::TcpListener;
+    use tonic::service::Interceptor;
+    use tonic::transport::{ClientTlsConfig, Endpoint};
+
+    use crate::grpc::client::{connect_client, ClientFactory, ClientRegistry, InterceptedChannel};
+    use crate::interceptors;
+
+    /// Defines a client like a generated one
+    macro_rules! test_client {
+        ( $client:ident, $path:literal ) => {
+            struct $client<T> {
+                inner: tonic::client::Grpc<T>,
+            }
+
+            impl<T> $client<T> {
+                fn with_interceptor<F: Interceptor>(
+                    inner: T,
+                    interceptor: F,
+                ) -> $client<tonic::service::interceptor::InterceptedService<T, F>> {
+                    $client {
+                        inner: tonic::client::Grpc::new(
+                            tonic::service::interceptor::InterceptedService::new(
+                                inner,
+                                interceptor,
+                            ),
+                        ),
+                    }
+                }
+            }
+
+            impl $client<crate::grpc::client::FactoryService> {
+                async fn call(&mut self) -> tonic::Code {
+                    self.inner.ready().await.unwrap();
+                    self.inner
+                        .unary(
+                            tonic::Request::new(()),
+                            http::uri::PathAndQuery::from_static($path),
+                            tonic::codec::ProstCodec::<(), ()>::default(),
+                        )
+                        .await
+                        .unwrap_err()
+                        .code()
+                }
+            }
+
+            crate::client_factory!($client);
+        };
+    }
+
+    test_client!(GreeterClient, "/test.Greeter/SayHello");
+    test_client!(OrderClient, "/test.Orders/Get");
+
+    /// Starts a server without services and counts its connections
+    async fn serve(connections: Arc<AtomicUsize>) -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = futures_util::stream::unfold(listener, move |listener| {
+            let connections = connections.clone();
+            async move {
+                let accepted = listener.accept().await.map(|(stream, _)| stream);
+                connections.fetch_add(1, Ordering::SeqCst);
+                Some((accepted, listener))
+            }
+        });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+        Endpoint::from_shared(format!("http://{addr}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_channel() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let endpoint = serve(connections.clone()).await;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let interceptors = interceptors!(move |req: tonic::Request<()>| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(req)
+        });
+
+        let factory =
+            ClientFactory::connect(ClientTlsConfig::new(), endpoint.clone(), interceptors)
+                .await
+                .unwrap();
+        let mut greeter: GreeterClient<_> = factory.client();
+        let mut orders: OrderClient<_> = factory.client();
+
+        assert_eq!(tonic::Code::Unimplemented, greeter.call().await);
+        assert_eq!(tonic::Code::Unimplemented, orders.call().await);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+        assert_eq!(1, connections.load(Ordering::SeqCst));
+
+        let mut greeter: GreeterClient<_> =
+            connect_client(ClientTlsConfig::new(), endpoint, interceptors!())
+                .await
+                .unwrap();
+        assert_eq!(tonic::Code::Unimplemented, greeter.call().await);
+        assert_eq!(2, connections.load(Ordering::SeqCst));
+    }
+
+    async fn call(channel: InterceptedChannel) -> tonic::Code {
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        client
+            .unary(
+                tonic::Request::new(()),
+                http::uri::PathAndQuery::from_static("/test.Greeter/SayHello"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await
+            .unwrap_err()
+            .code()
+    }
+
+    #[tokio::test]
+    async fn test_intercepted_channel() {
+        let endpoint = serve(Arc::new(AtomicUsize::new(0))).await;
+        let channel = endpoint.connect().await.unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+
+        let channels: Vec<InterceptedChannel> = vec![
+            InterceptedChannel::from(channel.clone()),
+            InterceptedChannel::new(
+                channel.clone(),
+                interceptors!(move |req: tonic::Request<()>| {
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    Ok(req)
+                }),
+            ),
+            InterceptedChannel::from(tonic::service::interceptor::InterceptedService::new(
+                channel,
+                |req: tonic::Request<()>| Ok(req),
+            )),
+        ];
+        fn assert_send<T: Send + Clone>(_: &T) {}
+        assert_send(&channels);
+
+        for channel in channels.iter().cloned() {
+            assert_eq!(tonic::Code::Unimplemented, call(channel).await);
+        }
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_registry_single_connect() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let endpoint = serve(connections.clone()).await;
+        let connects = Arc::new(AtomicUsize::new(0));
+        let registry = ClientRegistry::new();
+
+        let get = || {
+            let (registry, endpoint, connects) =
+                (registry.clone(), endpoint.clone(), connects.clone());
+            async move {
+                registry
+                    .get_or_connect("greeter", || async move {
+                        connects.fetch_add(1, Ordering::SeqCst);
+                        // Gives the other callers time to arrive
+                        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                        let channel = endpoint.connect().await?;
+                        Ok::<_, tonic::transport::Error>(InterceptedChannel::from(channel))
+                    })
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let channels = futures_util::future::join_all((0..8).map(|_| get())).await;
+        assert_eq!(1, connects.load(Ordering::SeqCst));
+        assert!(registry.contains("greeter"));
+        for channel in channels {
+            assert_eq!(tonic::Code::Unimplemented, call(channel).await);
+        }
+        assert_eq!(1, connections.load(Ordering::SeqCst));
+
+        // The evicted channel keeps working, the next use connects again
+        let evicted = get().await;
+        assert!(registry.evict("greeter"));
+        assert!(!registry.evict("greeter"));
+        assert!(!registry.contains("greeter"));
+        let refreshed = get().await;
+        assert_eq!(2, connects.load(Ordering::SeqCst));
+        assert_eq!(tonic::Code::Unimplemented, call(evicted).await);
+        assert_eq!(tonic::Code::Unimplemented, call(refreshed).await);
+        assert_eq!(2, connections.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_registry_failed_connect() {
+        let registry = ClientRegistry::new();
+        let failed = registry
+            .get_or_connect("greeter", || async { Err("refused") })
+            .await;
+        assert_eq!(Some("refused"), failed.err());
+        assert!(!registry.contains("greeter"));
+
+        // The next use tries again
+        let endpoint = serve(Arc::new(AtomicUsize::new(0))).await;
+        let channel = registry
+            .get_or_connect("greeter", || async {
+                Ok::<_, &str>(InterceptedChannel::from(endpoint.connect_lazy()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(tonic::Code::Unimplemented, call(channel).await);
+        assert!(std::ptr::eq(
+            ClientRegistry::global(),
+            ClientRegistry::global()
+        ));
+    }
+}
@@ -0,0 +1,1096 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use http::Uri;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tower_service::Service;
+
+use crate::grpc::layer::deadline::DeadlineContext;
+use crate::grpc::layer::BoxError;
+
+/// The errors of creating and using a channel
+#[derive(Debug)]
+pub enum ChannelError {
+    /// The configuration is invalid or connecting failed
+    Connect(tonic::transport::Error),
+    /// The runtime of a blocking channel could not be started
+    Runtime(std::io::Error),
+    /// A blocking channel was used inside of a tokio runtime, where it
+    /// would block the runtime
+    InsideRuntime,
+    /// Connecting did not finish before the deadline
+    DeadlineExceeded {
+        /// The time connecting took until it was given up
+        elapsed: Duration,
+    },
+    /// The warm up of the channel failed, e.g. its readiness probe
+    NotReady(BoxError),
+}
+
+impl std::fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelError::Connect(e) => write!(f, "Connecting the channel failed: {e}"),
+            ChannelError::Runtime(e) => write!(f, "Starting the runtime failed: {e}"),
+            ChannelError::InsideRuntime => write!(
+                f,
+                "A blocking channel can not be used inside of a tokio runtime, use the async channel instead"
+            ),
+            ChannelError::DeadlineExceeded { elapsed } => write!(
+                f,
+                "Connecting the channel was given up at the deadline after {elapsed:?}"
+            ),
+            ChannelError::NotReady(e) => write!(f, "The channel is not ready: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChannelError::Connect(e) => Some(e),
+            ChannelError::Runtime(e) => Some(e),
+            ChannelError::NotReady(e) => Some(e.as_ref()),
+            ChannelError::InsideRuntime | ChannelError::DeadlineExceeded { .. } => None,
+        }
+    }
+}
+
+/// The details of an established connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// The address, that was actually dialed
+    pub remote_addr: SocketAddr,
+    /// The local address of the connection
+    pub local_addr: SocketAddr,
+    /// True, if TLS is used on the connection
+    pub tls: bool,
+}
+
+/// A connector, that records the details of every connection it establishes
+#[derive(Clone)]
+struct InfoConnector {
+    http: HttpConnector,
+    first: Arc<Mutex<Option<ConnectionInfo>>>,
+}
+
+impl Service<Uri> for InfoConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let tls = uri.scheme_str() == Some("https");
+        let future = self.http.call(uri.clone());
+        let first = self.first.clone();
+        Box::pin(async move {
+            let io = future.await?;
+            let info = ConnectionInfo {
+                remote_addr: io.inner().peer_addr()?,
+                local_addr: io.inner().local_addr()?,
+                tls,
+            };
+            log::info!(
+                "Connected to {uri} at {} from {} ({})",
+                info.remote_addr,
+                info.local_addr,
+                if info.tls { "TLS" } else { "plaintext" }
+            );
+            if let Ok(mut first) = first.lock() {
+                first.get_or_insert(info);
+            }
+            Ok(io)
+        })
+    }
+}
+
+/// Creates a [tonic::transport::Channel] like [crate::grpc::channel] and
+/// returns the details of the established connection
+///
+/// Every connection of the channel, including reconnects, is logged with
+/// `log::info!`. The negotiated TLS version and ALPN protocol are not
+/// available, because tonic performs the TLS handshake on top of the
+/// connector.
+pub async fn channel_with_info(
+    tls: ClientTlsConfig,
+    endpoint: Endpoint,
+) -> Result<(Channel, ConnectionInfo), Box<dyn std::error::Error>> {
+    let keep_alive = Some(Duration::from_secs(60));
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_nodelay(true);
+    http.set_keepalive(keep_alive);
+
+    let connector = InfoConnector {
+        http,
+        first: Arc::new(Mutex::new(None)),
+    };
+    let first = connector.first.clone();
+    let channel = crate::grpc::configure(tls, endpoint)?
+        .connect_with_connector(connector)
+        .await?;
+
+    let info = first
+        .lock()
+        .ok()
+        .and_then(|mut first| first.take())
+        .ok_or("The connection was established without the connector")?;
+    Ok((channel, info))
+}
+
+/// The changes of the connection of a channel, see [channel_with_events]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A connection was established
+    Connected {
+        /// The address, that was dialed
+        addr: SocketAddr,
+        /// The time it took to connect
+        took: Duration,
+    },
+    /// An established connection was closed or failed
+    Disconnected {
+        /// Why the connection ended
+        reason: String,
+    },
+    /// The channel dials again, after it lost its connection
+    ReconnectAttempt {
+        /// The number of the attempt since the connection was lost,
+        /// starting with 1
+        attempt: u32,
+    },
+}
+
+/// The callback of [channel_with_events]
+pub type ConnectionEventCallback = Arc<dyn Fn(ConnectionEvent) + Send + Sync>;
+
+/// Calls the callback, so that a panic does not reach the connection
+fn emit(on_event: &ConnectionEventCallback, event: ConnectionEvent) {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| on_event(event)));
+    if result.is_err() {
+        log::error!("The connection event callback panicked");
+    }
+}
+
+/// A TcpStream, that reports the end of the connection once
+struct EventStream {
+    inner: TcpStream,
+    on_event: ConnectionEventCallback,
+    closed: bool,
+}
+
+impl EventStream {
+    fn close(&mut self, reason: String) {
+        if !std::mem::replace(&mut self.closed, true) {
+            emit(&self.on_event, ConnectionEvent::Disconnected { reason });
+        }
+    }
+
+    fn observe<T>(&mut self, result: &Poll<std::io::Result<T>>) {
+        if let Poll::Ready(Err(e)) = result {
+            self.close(e.to_string());
+        }
+    }
+}
+
+impl AsyncRead for EventStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let remaining = buf.remaining();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.observe(&result);
+        if matches!(result, Poll::Ready(Ok(()))) && remaining > 0 && buf.remaining() == remaining {
+            self.close(String::from("Closed by the server"));
+        }
+        result
+    }
+}
+
+impl AsyncWrite for EventStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.observe(&result);
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_flush(cx);
+        self.observe(&result);
+        result
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.observe(&result);
+        result
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.close(String::from("Closed by the client"));
+    }
+}
+
+/// A connector, that reports the connection events of a channel
+#[derive(Clone)]
+struct EventConnector {
+    http: HttpConnector,
+    on_event: ConnectionEventCallback,
+    connected: Arc<AtomicBool>,
+    attempts: Arc<AtomicU32>,
+}
+
+impl Service<Uri> for EventConnector {
+    type Response = TokioIo<EventStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        if self.connected.load(Ordering::SeqCst) {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            emit(
+                &self.on_event,
+                ConnectionEvent::ReconnectAttempt { attempt },
+            );
+        }
+
+        let start = Instant::now();
+        let future = self.http.call(uri);
+        let connector = self.clone();
+        Box::pin(async move {
+            let io = future.await?;
+            let addr = io.inner().peer_addr()?;
+            connector.attempts.store(0, Ordering::SeqCst);
+            connector.connected.store(true, Ordering::SeqCst);
+            emit(
+                &connector.on_event,
+                ConnectionEvent::Connected {
+                    addr,
+                    took: start.elapsed(),
+                },
+            );
+            Ok(TokioIo::new(EventStream {
+                inner: io.into_inner(),
+                on_event: connector.on_event,
+                closed: false,
+            }))
+        })
+    }
+}
+
+/// Creates a [tonic::transport::Channel] like [crate::grpc::channel], that
+/// reports the events of its connection to the callback
+///
+/// The callback is called on the connection path, so it should be cheap,
+/// e.g. increment a counter or log. A panic of the callback is caught and
+/// logged. The first connection is reported with
+/// [ConnectionEvent::Connected] only; after the connection is lost, every
+/// dial is preceded by a [ConnectionEvent::ReconnectAttempt].
+/// # Arguments
+/// * `tls`: The TLS configuration
+/// * `endpoint`: The endpoint
+/// * `on_event`: The callback for the events
+pub async fn channel_with_events(
+    tls: ClientTlsConfig,
+    endpoint: Endpoint,
+    on_event: ConnectionEventCallback,
+) -> Result<Channel, Box<dyn std::error::Error>> {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_nodelay(true);
+    http.set_keepalive(Some(Duration::from_secs(60)));
+
+    let connector = EventConnector {
+        http,
+        on_event,
+        connected: Arc::new(AtomicBool::new(false)),
+        attempts: Arc::new(AtomicU32::new(0)),
+    };
+    Ok(crate::grpc::configure(tls, endpoint)?
+        .connect_with_connector(connector)
+        .await?)
+}
+
+/// Adapts a connector yielding tokio IO to the IO traits of hyper
+#[derive(Clone)]
+struct TokioIoConnector<C>(C);
+
+impl<C> Service<Uri> for TokioIoConnector<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = TokioIo<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let future = self.0.call(uri);
+        Box::pin(async move { future.await.map(TokioIo::new) })
+    }
+}
+
+fn configure_for_connector(
+    tls: Option<ClientTlsConfig>,
+    endpoint: Endpoint,
+) -> Result<Endpoint, ChannelError> {
+    match tls {
+        Some(tls) => crate::grpc::configure(tls, endpoint).map_err(ChannelError::Connect),
+        None => Ok(endpoint.keep_alive_while_idle(true)),
+    }
+}
+
+/// Creates a [tonic::transport::Channel] like [crate::grpc::channel], that
+/// gives up connecting at the deadline
+///
+/// Fails with [ChannelError::DeadlineExceeded], so a connect can not use up
+/// more than the remaining time of a call.
+/// # Arguments
+/// * `deadline`: The point in time, when connecting is given up
+/// * `tls`: The TLS configuration
+/// * `endpoint`: The endpoint
+pub async fn connect_within(
+    deadline: Instant,
+    tls: ClientTlsConfig,
+    endpoint: Endpoint,
+) -> Result<Channel, ChannelError> {
+    let endpoint = crate::grpc::configure(tls, endpoint).map_err(ChannelError::Connect)?;
+    let started = Instant::now();
+    match tokio::time::timeout_at(deadline.into(), endpoint.connect()).await {
+        Ok(channel) => channel.map_err(ChannelError::Connect),
+        Err(_) => Err(ChannelError::DeadlineExceeded {
+            elapsed: started.elapsed(),
+        }),
+    }
+}
+
+/// Creates a [tonic::transport::Channel] like [crate::grpc::channel], that
+/// gives up connecting at the deadline of the current [DeadlineContext]
+///
+/// Without a current context, e.g. outside of a handler behind the
+/// [DeadlineLayer](crate::grpc::layer::deadline::DeadlineLayer), it waits
+/// for the connection like [crate::grpc::channel]. See [connect_within].
+pub async fn connect(tls: ClientTlsConfig, endpoint: Endpoint) -> Result<Channel, ChannelError> {
+    match DeadlineContext::current() {
+        Some(context) => connect_within(context.deadline, tls, endpoint).await,
+        None => crate::grpc::configure(tls, endpoint)
+            .map_err(ChannelError::Connect)?
+            .connect()
+            .await
+            .map_err(ChannelError::Connect),
+    }
+}
+
+/// Creates a [tonic::transport::Channel], that connects through the
+/// connector
+///
+/// The connector yields the IO of every connection, e.g. an instrumented
+/// TcpStream or a tunnel. With a TLS configuration, tonic runs TLS on top
+/// of it for `https` endpoints, like [crate::grpc::channel] does. Without
+/// one, the connector is responsible for TLS.
+/// # Arguments
+/// * `tls`: The TLS configuration, or None if the connector handles TLS
+/// * `endpoint`: The endpoint
+/// * `connector`: A service returning the IO for the URI of the endpoint
+pub async fn channel_with_connector<C>(
+    tls: Option<ClientTlsConfig>,
+    endpoint: Endpoint,
+    connector: C,
+) -> Result<Channel, ChannelError>
+where
+    C: Service<Uri> + Send + 'static,
+    C::Response: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    C::Future: Send + 'static,
+    Box<dyn std::error::Error + Send + Sync>: From<C::Error> + Send,
+{
+    configure_for_connector(tls, endpoint)?
+        .connect_with_connector(TokioIoConnector(connector))
+        .await
+        .map_err(ChannelError::Connect)
+}
+
+/// Creates a [tonic::transport::Channel] like [channel_with_connector], that
+/// connects on the first call
+pub fn channel_with_connector_lazy<C>(
+    tls: Option<ClientTlsConfig>,
+    endpoint: Endpoint,
+    connector: C,
+) -> Result<Channel, ChannelError>
+where
+    C: Service<Uri> + Send + 'static,
+    C::Response: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    C::Future: Send + 'static,
+    Box<dyn std::error::Error + Send + Sync>: From<C::Error> + Send,
+{
+    Ok(configure_for_connector(tls, endpoint)?
+        .connect_with_connector_lazy(TokioIoConnector(connector)))
+}
+
+/// A connector, that dials fixed addresses instead of resolving some hosts,
+/// like the `--resolve` option of curl
+///
+/// The URI of the endpoint is not changed, so the `:authority`, the SNI and
+/// the certificate verification still use the logical hostname. Hosts
+/// without an override are resolved as usual. Use it with
+/// [Endpoint::connect_with_connector].
+#[derive(Clone)]
+pub struct ResolveOverrideConnector {
+    http: HttpConnector,
+    overrides: Arc<HashMap<String, SocketAddr>>,
+}
+
+impl Default for ResolveOverrideConnector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResolveOverrideConnector {
+    /// Creates a new connector without overrides
+    pub fn new() -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_nodelay(true);
+        Self {
+            http,
+            overrides: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Dials the address for all connections to the host
+    /// # Arguments
+    /// * `host`: The hostname of the endpoint URI
+    /// * `addr`: The address to connect to instead
+    pub fn resolve_override(mut self, host: &str, addr: SocketAddr) -> Self {
+        Arc::make_mut(&mut self.overrides).insert(host.to_ascii_lowercase(), addr);
+        self
+    }
+}
+
+impl Service<Uri> for ResolveOverrideConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let Some(addr) = self.overrides.get(&host).copied() else {
+            let future = self.http.call(uri);
+            return Box::pin(async move { Ok(future.await?) });
+        };
+
+        Box::pin(async move {
+            log::debug!("Connecting to {addr} for {host}");
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            Ok(TokioIo::new(stream))
+        })
+    }
+}
+
+/// A connector, that races connections to all addresses of the host, like
+/// the Happy Eyeballs of RFC 8305
+///
+/// The host is resolved to all its IPv6 and IPv4 addresses up front. The
+/// attempts start one after another with the stagger delay in between,
+/// alternating the address families, and the next one starts right away
+/// when an attempt fails. The first established connection is used and the
+/// other attempts are cancelled, so a dead address only delays connecting
+/// by the stagger delay. Use it with [Endpoint::connect_with_connector].
+#[derive(Clone)]
+pub struct RacingConnector {
+    stagger: Duration,
+    overrides: Arc<HashMap<String, Vec<SocketAddr>>>,
+    last: Arc<Mutex<Option<ConnectionInfo>>>,
+}
+
+impl Default for RacingConnector {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(250))
+    }
+}
+
+impl RacingConnector {
+    /// Creates a new connector
+    /// # Arguments
+    /// * `stagger`: The delay between the starts of two attempts, 250ms by default
+    pub fn new(stagger: Duration) -> Self {
+        Self {
+            stagger,
+            overrides: Arc::new(HashMap::new()),
+            last: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Races the addresses for all connections to the host, instead of
+    /// resolving it
+    /// # Arguments
+    /// * `host`: The hostname of the endpoint URI
+    /// * `addrs`: The addresses to race in their order
+    pub fn resolve_override(mut self, host: &str, addrs: Vec<SocketAddr>) -> Self {
+        Arc::make_mut(&mut self.overrides).insert(host.to_ascii_lowercase(), addrs);
+        self
+    }
+
+    /// Returns the details of the last established connection, with the
+    /// address, that won the race
+    pub fn connection_info(&self) -> Option<ConnectionInfo> {
+        self.last.lock().ok().and_then(|last| last.clone())
+    }
+}
+
+/// Orders the addresses alternating between the address families, starting
+/// with the family of the first one
+fn interleave(addrs: Vec<SocketAddr>) -> VecDeque<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut ordered = VecDeque::new();
+    while !first.is_empty() || !second.is_empty() {
+        ordered.extend(first.pop_front());
+        ordered.extend(second.pop_front());
+    }
+    ordered
+}
+
+/// Connects to the first address, that accepts the connection
+async fn race(mut pending: VecDeque<SocketAddr>, stagger: Duration) -> Result<TcpStream, BoxError> {
+    let attempt = |addr: SocketAddr| async move { (addr, TcpStream::connect(addr).await) };
+    let mut attempts = FuturesUnordered::new();
+    let mut error: Option<BoxError> = None;
+    loop {
+        if attempts.is_empty() {
+            let Some(addr) = pending.pop_front() else {
+                return Err(error.unwrap_or_else(|| "The host has no addresses".into()));
+            };
+            attempts.push(attempt(addr));
+        }
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    log::debug!("Connecting to {addr} failed: {e}");
+                    error = Some(format!("Connecting to {addr} failed: {e}").into());
+                    attempts.extend(pending.pop_front().map(attempt));
+                }
+            },
+            _ = tokio::time::sleep(stagger), if !pending.is_empty() => {
+                attempts.extend(pending.pop_front().map(attempt));
+            }
+        }
+    }
+}
+
+impl Service<Uri> for RacingConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.clone();
+        Box::pin(async move {
+            let tls = uri.scheme_str() == Some("https");
+            let host = uri.host().ok_or("The endpoint has no host")?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+            let addrs = match connector.overrides.get(&host.to_ascii_lowercase()) {
+                Some(addrs) => addrs.clone(),
+                None => tokio::net::lookup_host((host, port)).await?.collect(),
+            };
+
+            let stream = race(interleave(addrs), connector.stagger).await?;
+            stream.set_nodelay(true)?;
+            crate::grpc::set_tcp_keepalive(&stream)?;
+            let info = ConnectionInfo {
+                remote_addr: stream.peer_addr()?,
+                local_addr: stream.local_addr()?,
+                tls,
+            };
+            log::info!(
+                "Connected to {uri} at {} from {} ({})",
+                info.remote_addr,
+                info.local_addr,
+                if info.tls { "TLS" } else { "plaintext" }
+            );
+            if let Ok(mut last) = connector.last.lock() {
+                *last = Some(info);
+            }
+            Ok(TokioIo::new(stream))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::TcpListener;
+    use tonic::transport::{ClientTlsConfig, Endpoint};
+    use tower_service::Service;
+
+    use crate::grpc::connection::interleave;
+    use crate::grpc::connection::{
+        channel_with_connector, channel_with_connector_lazy, channel_with_events,
+        channel_with_info, connect, connect_within, ChannelError, ConnectionEvent,
+        ConnectionEventCallback, RacingConnector, ResolveOverrideConnector,
+    };
+    use crate::grpc::layer::deadline::DeadlineContext;
+
+    /// An IO counting the bytes written to it
+    struct Counting {
+        inner: tokio::io::DuplexStream,
+        written: Arc<AtomicUsize>,
+    }
+
+    impl AsyncRead for Counting {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Counting {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+            if let Poll::Ready(Ok(written)) = result {
+                self.written.fetch_add(written, Ordering::SeqCst);
+            }
+            result
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// A connector serving every connection with a server without services
+    fn connector(
+        written: Arc<AtomicUsize>,
+    ) -> impl Service<
+        http::Uri,
+        Response = Counting,
+        Error = std::io::Error,
+        Future = impl Future<Output = std::io::Result<Counting>> + Send + 'static,
+    > + Send
+           + 'static {
+        tower::service_fn(move |_uri: http::Uri| {
+            let (client, server) = tokio::io::duplex(4096);
+            tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_routes(tonic::service::Routes::default())
+                    .serve_with_incoming(
+                        futures_util::stream::iter([Ok::<_, std::io::Error>(server)])
+                            .chain(futures_util::stream::pending()),
+                    ),
+            );
+            let written = written.clone();
+            async move {
+                Ok(Counting {
+                    inner: client,
+                    written,
+                })
+            }
+        })
+    }
+
+    async fn unimplemented_call(channel: tonic::transport::Channel) -> tonic::Code {
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        client
+            .unary(
+                tonic::Request::new(()),
+                http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await
+            .unwrap_err()
+            .code()
+    }
+
+    #[tokio::test]
+    async fn test_channel_with_connector() {
+        let endpoint = Endpoint::from_static("http://tunnel.example.invalid");
+
+        let written = Arc::new(AtomicUsize::new(0));
+        let channel = channel_with_connector(None, endpoint.clone(), connector(written.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            tonic::Code::Unimplemented,
+            unimplemented_call(channel).await
+        );
+        assert!(written.load(Ordering::SeqCst) > 0);
+
+        let written = Arc::new(AtomicUsize::new(0));
+        let channel = channel_with_connector_lazy(
+            Some(ClientTlsConfig::new()),
+            endpoint,
+            connector(written.clone()),
+        )
+        .unwrap();
+        assert_eq!(0, written.load(Ordering::SeqCst));
+        assert_eq!(
+            tonic::Code::Unimplemented,
+            unimplemented_call(channel).await
+        );
+        assert!(written.load(Ordering::SeqCst) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+
+        let (_channel, info) = channel_with_info(ClientTlsConfig::new(), endpoint)
+            .await
+            .unwrap();
+
+        assert_eq!(addr, info.remote_addr);
+        assert_eq!(addr.ip(), info.local_addr.ip());
+        assert_ne!(addr.port(), info.local_addr.port());
+        assert!(!info.tls);
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+
+        assert!(channel_with_info(ClientTlsConfig::new(), endpoint)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_override() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let other = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let connector = ResolveOverrideConnector::new()
+            .resolve_override("backend.example.invalid", addr)
+            .resolve_override("other.example.invalid", other.local_addr().unwrap());
+
+        let endpoint =
+            Endpoint::from_shared(format!("http://backend.example.invalid:{}", addr.port()))
+                .unwrap();
+        assert!(endpoint
+            .connect_with_connector(connector.clone())
+            .await
+            .is_ok());
+
+        // Hosts without an override are resolved
+        let endpoint =
+            Endpoint::from_shared(format!("http://unknown.example.invalid:{}", addr.port()))
+                .unwrap();
+        assert!(endpoint.connect_with_connector(connector).await.is_err());
+    }
+
+    #[test]
+    fn test_interleave() {
+        let v4 = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        let v6 = |port| SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], port));
+
+        assert_eq!(
+            vec![v6(1), v4(3), v6(2), v4(4), v4(5)],
+            Vec::from(interleave(vec![v6(1), v6(2), v4(3), v4(4), v4(5)]))
+        );
+        assert_eq!(
+            vec![v4(1), v6(2), v4(3)],
+            Vec::from(interleave(vec![v4(1), v4(3), v6(2)]))
+        );
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_racing_connector() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // An address of the discard prefix, that never answers
+        let blackhole: SocketAddr = "[100::1]:50051".parse().unwrap();
+        let connector = RacingConnector::new(Duration::from_millis(200))
+            .resolve_override("backend.example.invalid", vec![blackhole, addr]);
+
+        let endpoint =
+            Endpoint::from_shared(format!("http://backend.example.invalid:{}", addr.port()))
+                .unwrap()
+                .connect_timeout(Duration::from_secs(10));
+        let start = std::time::Instant::now();
+        assert!(endpoint
+            .connect_with_connector(connector.clone())
+            .await
+            .is_ok());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let info = connector.connection_info().unwrap();
+        assert_eq!(addr, info.remote_addr);
+        assert!(!info.tls);
+    }
+
+    #[tokio::test]
+    async fn test_racing_connector_fails() {
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let connector = RacingConnector::default()
+            .resolve_override("backend.example.invalid", vec![closed, closed]);
+
+        let endpoint =
+            Endpoint::from_shared(format!("http://backend.example.invalid:{}", closed.port()))
+                .unwrap();
+        let start = std::time::Instant::now();
+        assert!(endpoint
+            .connect_with_connector(connector.clone())
+            .await
+            .is_err());
+        // A failed attempt starts the next one right away
+        assert!(start.elapsed() < Duration::from_millis(250));
+        assert!(connector.connection_info().is_none());
+    }
+
+    /// Serves a server without services on the address, until the sender
+    /// is dropped
+    async fn serve(addr: SocketAddr) -> tokio::sync::oneshot::Sender<()> {
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        let (shutdown, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = shutdown_rx.await;
+                }),
+        );
+        shutdown
+    }
+
+    async fn wait_for(
+        events: &Mutex<Vec<ConnectionEvent>>,
+        predicate: fn(&ConnectionEvent) -> bool,
+    ) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !events.lock().unwrap().iter().any(predicate) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the event was not observed");
+    }
+
+    #[tokio::test]
+    async fn test_connection_events() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let shutdown = serve(addr).await;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let captured = events.clone();
+        let on_event: ConnectionEventCallback =
+            Arc::new(move |event| captured.lock().unwrap().push(event));
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        let channel = channel_with_events(ClientTlsConfig::new(), endpoint, on_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            tonic::Code::Unimplemented,
+            unimplemented_call(channel.clone()).await
+        );
+
+        drop(shutdown);
+        wait_for(&events, |event| {
+            matches!(event, ConnectionEvent::Disconnected { .. })
+        })
+        .await;
+
+        let _shutdown = serve(addr).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let mut client = tonic::client::Grpc::new(channel.clone());
+                if client.ready().await.is_ok() {
+                    let result = client
+                        .unary(
+                            tonic::Request::new(()),
+                            http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                            tonic::codec::ProstCodec::<(), ()>::default(),
+                        )
+                        .await;
+                    if let Err(status) = result
+                        && status.code() == tonic::Code::Unimplemented
+                    {
+                        break;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the channel did not reconnect");
+
+        let events = events.lock().unwrap();
+        assert!(
+            matches!(events[0], ConnectionEvent::Connected { addr: connected, .. } if connected == addr)
+        );
+        assert!(matches!(events[1], ConnectionEvent::Disconnected { .. }));
+        assert_eq!(ConnectionEvent::ReconnectAttempt { attempt: 1 }, events[2]);
+        assert!(matches!(
+            events.last().unwrap(),
+            ConnectionEvent::Connected { .. }
+        ));
+        assert_eq!(
+            1,
+            events
+                .iter()
+                .filter(|event| matches!(event, ConnectionEvent::Disconnected { .. }))
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panicking_callback() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let _shutdown = serve(addr).await;
+
+        let on_event: ConnectionEventCallback = Arc::new(|_event| panic!("broken callback"));
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        let channel = channel_with_events(ClientTlsConfig::new(), endpoint, on_event)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            tonic::Code::Unimplemented,
+            unimplemented_call(channel).await
+        );
+    }
+
+    /// Returns an endpoint, whose listener never accepts, so the TLS
+    /// handshake never finishes
+    async fn slow_endpoint() -> (TcpListener, Endpoint) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let endpoint = Endpoint::from_shared(format!("https://{addr}")).unwrap();
+        (listener, endpoint)
+    }
+
+    #[tokio::test]
+    async fn test_connect_within() {
+        let (_listener, endpoint) = slow_endpoint().await;
+        let started = std::time::Instant::now();
+        let result = connect_within(
+            started + Duration::from_millis(100),
+            ClientTlsConfig::new(),
+            endpoint,
+        )
+        .await;
+
+        let Err(ChannelError::DeadlineExceeded { elapsed }) = result else {
+            panic!("Expected the deadline to be exceeded, got {result:?}");
+        };
+        assert!(elapsed >= Duration::from_millis(90), "{elapsed:?}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(ChannelError::DeadlineExceeded { elapsed }
+            .to_string()
+            .contains("deadline"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_context() {
+        let (_listener, endpoint) = slow_endpoint().await;
+        let started = std::time::Instant::now();
+        let result = DeadlineContext::from_timeout(Duration::from_millis(100))
+            .scope(connect(ClientTlsConfig::new(), endpoint))
+            .await;
+
+        assert!(matches!(result, Err(ChannelError::DeadlineExceeded { .. })));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_connect_without_context() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        connect(ClientTlsConfig::new(), endpoint).await.unwrap();
+    }
+}
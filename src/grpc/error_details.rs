@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use prost::{Message, Name};
+use prost_types::Any;
+
+/// The domain of the type URLs of the detail messages
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+
+/// Implements [Name] for a message of the `google.rpc` package
+macro_rules! google_rpc_name {
+    ( $message:ident ) => {
+        impl Name for $message {
+            const NAME: &'static str = stringify!($message);
+            const PACKAGE: &'static str = "google.rpc";
+
+            fn type_url() -> String {
+                format!("{TYPE_URL_PREFIX}{}", Self::full_name())
+            }
+        }
+    };
+}
+
+/// The `google.rpc.Status` carried in the `grpc-status-details-bin` trailer
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RpcStatus {
+    /// The gRPC code
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    /// The error message
+    #[prost(string, tag = "2")]
+    pub message: String,
+    /// The detail messages
+    #[prost(message, repeated, tag = "3")]
+    pub details: Vec<Any>,
+}
+
+google_rpc_name!(RpcStatus);
+
+/// The cause of an error, `google.rpc.ErrorInfo`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ErrorInfo {
+    /// The reason of the error, e.g. `API_DISABLED`
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    /// The domain of the reason, e.g. the service name
+    #[prost(string, tag = "2")]
+    pub domain: String,
+    /// Additional structured details
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: HashMap<String, String>,
+}
+
+google_rpc_name!(ErrorInfo);
+
+/// When the client may retry, `google.rpc.RetryInfo`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetryInfo {
+    /// The time to wait before retrying
+    #[prost(message, optional, tag = "1")]
+    pub retry_delay: Option<prost_types::Duration>,
+}
+
+google_rpc_name!(RetryInfo);
+
+/// A violated field of a request
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FieldViolation {
+    /// The path of the field, e.g. `user.email`
+    #[prost(string, tag = "1")]
+    pub field: String,
+    /// Why the field is invalid
+    #[prost(string, tag = "2")]
+    pub description: String,
+}
+
+/// The invalid fields of a request, `google.rpc.BadRequest`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BadRequest {
+    /// The violated fields
+    #[prost(message, repeated, tag = "1")]
+    pub field_violations: Vec<FieldViolation>,
+}
+
+google_rpc_name!(BadRequest);
+
+/// A quota, that was exceeded
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QuotaViolation {
+    /// The subject of the quota, e.g. `project:123`
+    #[prost(string, tag = "1")]
+    pub subject: String,
+    /// How the quota was exceeded
+    #[prost(string, tag = "2")]
+    pub description: String,
+}
+
+/// The exceeded quotas, `google.rpc.QuotaFailure`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QuotaFailure {
+    /// The exceeded quotas
+    #[prost(message, repeated, tag = "1")]
+    pub violations: Vec<QuotaViolation>,
+}
+
+google_rpc_name!(QuotaFailure);
+
+/// The details of a [tonic::Status], decoded from `google.rpc.Status`
+///
+/// Detail messages of other types, or ones that can not be decoded, are
+/// kept in [ErrorDetails::unknown] with their type URL and bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorDetails {
+    /// The cause of the error
+    pub error_info: Option<ErrorInfo>,
+    /// When the client may retry
+    pub retry_info: Option<RetryInfo>,
+    /// The invalid fields of the request
+    pub bad_request: Option<BadRequest>,
+    /// The exceeded quotas
+    pub quota_failure: Option<QuotaFailure>,
+    /// The detail messages of other types
+    pub unknown: Vec<Any>,
+}
+
+/// Returns, whether the type URL names the message, with any domain
+fn is_type<M: Name>(type_url: &str) -> bool {
+    type_url.rsplit('/').next() == Some(M::full_name().as_str())
+}
+
+/// Decodes the detail, if it is of the type
+fn decode<M: Name + Default>(detail: &Any) -> Option<M> {
+    if !is_type::<M>(&detail.type_url) {
+        return None;
+    }
+    M::decode(detail.value.as_slice()).ok()
+}
+
+impl ErrorDetails {
+    /// Decodes the details from the bytes of `grpc-status-details-bin`
+    pub fn decode(bytes: &[u8]) -> Result<Self, prost::DecodeError> {
+        let status = RpcStatus::decode(bytes)?;
+        let mut details = Self::default();
+        for detail in status.details {
+            if let Some(info) = decode(&detail) {
+                details.error_info = Some(info);
+            } else if let Some(info) = decode(&detail) {
+                details.retry_info = Some(info);
+            } else if let Some(request) = decode(&detail) {
+                details.bad_request = Some(request);
+            } else if let Some(failure) = decode(&detail) {
+                details.quota_failure = Some(failure);
+            } else {
+                details.unknown.push(detail);
+            }
+        }
+        Ok(details)
+    }
+
+    /// Returns the delay the server suggests before retrying
+    ///
+    /// Retry policies should wait at least this long, instead of their own
+    /// backoff. Negative delays are ignored.
+    pub fn retry_delay(&self) -> Option<std::time::Duration> {
+        let delay = self.retry_info.as_ref()?.retry_delay?;
+        std::time::Duration::try_from(delay).ok()
+    }
+}
+
+/// Decodes the rich error details of a [tonic::Status]
+///
+/// ```ignore
+/// use grpc_utils_rs::grpc::error_details::ErrorDetailsExt;
+///
+/// if let Some(delay) = status.error_details().and_then(|d| d.retry_delay()) {
+///     tokio::time::sleep(delay).await;
+/// }
+/// ```
+pub trait ErrorDetailsExt {
+    /// Returns the details, or `None`, if the status has none or they can
+    /// not be decoded
+    fn error_details(&self) -> Option<ErrorDetails>;
+}
+
+impl ErrorDetailsExt for tonic::Status {
+    fn error_details(&self) -> Option<ErrorDetails> {
+        if self.details().is_empty() {
+            return None;
+        }
+        match ErrorDetails::decode(self.details()) {
+            Ok(details) => Some(details),
+            Err(e) => {
+                log::debug!("Invalid error details: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Attaches rich error details to a [tonic::Status] on the server
+///
+/// The details are appended to the `google.rpc.Status` in
+/// `grpc-status-details-bin`, so several of them can be attached. The code,
+/// message and metadata of the status are kept:
+/// ```ignore
+/// use grpc_utils_rs::grpc::error_details::StatusExt;
+///
+/// Status::unavailable("overloaded").with_retry_info(Duration::from_secs(2))
+/// ```
+pub trait StatusExt: Sized {
+    /// Appends a detail message of any type
+    fn with_detail<M: Name>(self, detail: &M) -> Self;
+
+    /// Appends the invalid fields of the request
+    fn with_bad_request(self, field_violations: Vec<FieldViolation>) -> Self {
+        self.with_detail(&BadRequest { field_violations })
+    }
+
+    /// Appends the delay, after which the client may retry
+    fn with_retry_info(self, retry_delay: std::time::Duration) -> Self {
+        self.with_detail(&RetryInfo {
+            retry_delay: prost_types::Duration::try_from(retry_delay).ok(),
+        })
+    }
+
+    /// Appends the cause of the error
+    fn with_error_info(
+        self,
+        reason: impl Into<String>,
+        domain: impl Into<String>,
+        metadata: HashMap<String, String>,
+    ) -> Self {
+        self.with_detail(&ErrorInfo {
+            reason: reason.into(),
+            domain: domain.into(),
+            metadata,
+        })
+    }
+}
+
+impl StatusExt for tonic::Status {
+    fn with_detail<M: Name>(self, detail: &M) -> Self {
+        let mut status = RpcStatus::decode(self.details()).unwrap_or_default();
+        status.code = self.code() as i32;
+        status.message = self.message().to_string();
+        status.details.push(Any {
+            type_url: M::type_url(),
+            value: detail.encode_to_vec(),
+        });
+        tonic::Status::with_details_and_metadata(
+            self.code(),
+            self.message(),
+            status.encode_to_vec().into(),
+            self.metadata().clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use prost::{Message, Name};
+    use prost_types::Any;
+    use tonic::{Code, Status};
+
+    use crate::grpc::error_details::{
+        BadRequest, ErrorDetailsExt, ErrorInfo, FieldViolation, QuotaFailure, QuotaViolation,
+        RetryInfo, RpcStatus, StatusExt,
+    };
+
+    /// Sends the status through the trailers, like a server does
+    fn transmitted(status: Status) -> Status {
+        let mut headers = http::HeaderMap::new();
+        status.add_header(&mut headers).unwrap();
+        Status::from_header_map(&headers).unwrap()
+    }
+
+    fn status(details: Vec<Any>) -> Status {
+        let encoded = RpcStatus {
+            code: Code::InvalidArgument as i32,
+            message: "invalid".to_string(),
+            details,
+        }
+        .encode_to_vec();
+        transmitted(Status::with_details(
+            Code::InvalidArgument,
+            "invalid",
+            encoded.into(),
+        ))
+    }
+
+    #[test]
+    fn test_error_details() {
+        let violation = FieldViolation {
+            field: "user.email".to_string(),
+            description: "missing".to_string(),
+        };
+        let status = status(vec![
+            Any::from_msg(&BadRequest {
+                field_violations: vec![violation.clone()],
+            })
+            .unwrap(),
+            Any::from_msg(&RetryInfo {
+                retry_delay: Some(prost_types::Duration {
+                    seconds: 2,
+                    nanos: 500_000_000,
+                }),
+            })
+            .unwrap(),
+            Any {
+                type_url: "type.googleapis.com/example.Custom".to_string(),
+                value: vec![1, 2, 3],
+            },
+        ]);
+        assert_eq!(
+            "type.googleapis.com/google.rpc.BadRequest",
+            BadRequest::type_url()
+        );
+
+        let details = status.error_details().unwrap();
+        assert_eq!(Some(Duration::from_millis(2500)), details.retry_delay());
+        assert_eq!(
+            vec![violation],
+            details.bad_request.unwrap().field_violations
+        );
+        assert_eq!(None::<ErrorInfo>, details.error_info);
+        assert_eq!(1, details.unknown.len());
+        assert_eq!(
+            "type.googleapis.com/example.Custom",
+            details.unknown[0].type_url
+        );
+        assert_eq!(vec![1, 2, 3], details.unknown[0].value);
+    }
+
+    #[test]
+    fn test_no_error_details() {
+        assert_eq!(None, Status::internal("error").error_details());
+        assert_eq!(
+            None,
+            Status::with_details(Code::Internal, "error", vec![0xff].into()).error_details()
+        );
+    }
+
+    /// A detail type, that the decoder does not know
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Custom {
+        #[prost(string, tag = "1")]
+        value: String,
+    }
+
+    impl Name for Custom {
+        const NAME: &'static str = "Custom";
+        const PACKAGE: &'static str = "example";
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        metadata.insert("x-request-id", "42".parse().unwrap());
+        let violations = vec![FieldViolation {
+            field: "name".to_string(),
+            description: "empty".to_string(),
+        }];
+        let quota = QuotaFailure {
+            violations: vec![QuotaViolation {
+                subject: "project:1".to_string(),
+                description: "too many calls".to_string(),
+            }],
+        };
+        let status = Status::with_metadata(Code::FailedPrecondition, "failed", metadata)
+            .with_bad_request(violations.clone())
+            .with_retry_info(Duration::from_secs(3))
+            .with_error_info(
+                "QUOTA",
+                "example.com",
+                [("limit".to_string(), "10".to_string())].into(),
+            )
+            .with_detail(&quota)
+            .with_detail(&Custom {
+                value: "custom".to_string(),
+            });
+        let status = transmitted(status);
+
+        assert_eq!(Code::FailedPrecondition, status.code());
+        assert_eq!("failed", status.message());
+        assert_eq!("42", status.metadata().get("x-request-id").unwrap());
+        let encoded = RpcStatus::decode(status.details()).unwrap();
+        assert_eq!(Code::FailedPrecondition as i32, encoded.code);
+        assert_eq!("failed", encoded.message);
+
+        let details = status.error_details().unwrap();
+        assert_eq!(Some(Duration::from_secs(3)), details.retry_delay());
+        assert_eq!(violations, details.bad_request.unwrap().field_violations);
+        let info = details.error_info.unwrap();
+        assert_eq!(("QUOTA", "example.com"), (&*info.reason, &*info.domain));
+        assert_eq!("10", info.metadata["limit"]);
+        assert_eq!(Some(quota), details.quota_failure);
+        assert_eq!(1, details.unknown.len());
+        assert_eq!("/example.Custom", details.unknown[0].type_url);
+        assert_eq!(
+            "custom",
+            details.unknown[0].to_msg::<Custom>().unwrap().value
+        );
+    }
+}
@@ -1,45 +1,213 @@
+// tonic::Status, the error of interceptors and services, is larger than
+// clippy::result_large_err allows
+#![allow(clippy::result_large_err)]
+
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tonic::metadata::Ascii;
 use tonic::metadata::MetadataKey;
+use tonic::metadata::{KeyAndValueRef, KeyRef, MetadataMap};
 use tonic::{metadata::AsciiMetadataValue, service::Interceptor, Status};
 
+use crate::grpc::interceptor::stats::StatsCollector;
+
+/// Interceptor adding nonce and timestamp headers against replays
+pub mod anti_replay;
+/// Interceptor enforcing a size budget for the metadata
+pub mod budget;
+/// Interceptor identifying the host and process of the client
+pub mod client_info;
+/// Interceptor adding the metadata of a task-local scope
+pub mod context;
+/// Interceptors propagating the deadline of the current or a queued call
+pub mod deadline;
+/// Interceptor signing requests with an Ed25519 key
+#[cfg(feature = "ed25519")]
+pub mod ed25519;
+/// Interceptor copying per-request metadata from the request extensions
+pub mod extension;
+/// Interceptor binding credentials to the host of the endpoint
+pub mod host_scope;
+/// Interceptor for Kubernetes service account tokens
+pub mod kubernetes;
+/// Server interceptor rejecting replayed nonces
+pub mod replay_guard;
+/// Credentials from a directory of secret files
+pub mod secrets_dir;
+/// Interceptors measuring the latency from client to server
+pub mod send_time;
+/// Counters of interceptor chains, flushed as JSON
+pub mod stats;
+/// Interceptor removing metadata keys from outgoing requests
+pub mod strip;
+/// Server interceptor resolving API keys to tenants
+pub mod tenant;
+/// Helpers for testing interceptors
+pub mod testing;
+
+/// Defines, how an interceptor treats a header that is already present
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverridePolicy {
+    /// Replace all existing values of the header
+    #[default]
+    Overwrite,
+    /// Keep the existing values and do not set the header
+    SkipIfPresent,
+    /// Add the value to the existing values, for repeatable headers
+    Append,
+}
+
+impl OverridePolicy {
+    /// Sets the header in the metadata according to the policy
+    /// # Arguments
+    /// * `metadata`: The metadata of the request
+    /// * `key`: The key of the header
+    /// * `value`: The value of the header
+    pub fn apply(
+        &self,
+        metadata: &mut MetadataMap,
+        key: MetadataKey<Ascii>,
+        value: AsciiMetadataValue,
+    ) {
+        match self {
+            OverridePolicy::Overwrite => {
+                metadata.insert(key, value);
+            }
+            OverridePolicy::SkipIfPresent => {
+                if metadata.contains_key(&key) {
+                    log::debug!("Header {key} is already present, skipping");
+                } else {
+                    metadata.insert(key, value);
+                }
+            }
+            OverridePolicy::Append => {
+                metadata.append(key, value);
+            }
+        }
+    }
+
+    /// Merges all entries of `extra` into the metadata according to the
+    /// policy
+    ///
+    /// All values of a key in `extra` are kept, so with [OverridePolicy::Overwrite]
+    /// a repeated key replaces the existing values with all of its values.
+    /// Binary (`-bin`) keys are supported.
+    /// # Arguments
+    /// * `metadata`: The metadata of the request
+    /// * `extra`: The entries to add
+    pub fn merge(&self, metadata: &mut MetadataMap, extra: &MetadataMap) {
+        for key in extra.keys() {
+            let name = match key {
+                KeyRef::Ascii(key) => key.as_str(),
+                KeyRef::Binary(key) => key.as_str(),
+            };
+            if *self == OverridePolicy::SkipIfPresent && metadata.contains_key(name) {
+                log::debug!("Header {name} is already present, skipping");
+                continue;
+            }
+
+            match key {
+                KeyRef::Ascii(key) => {
+                    if *self == OverridePolicy::Overwrite {
+                        metadata.remove(key);
+                    }
+                    for value in extra.get_all(key) {
+                        metadata.append(key.clone(), value.clone());
+                    }
+                }
+                KeyRef::Binary(key) => {
+                    if *self == OverridePolicy::Overwrite {
+                        metadata.remove_bin(key);
+                    }
+                    for value in extra.get_all_bin(key) {
+                        metadata.append_bin(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct APIKeyClientInterceptor {
-    header_name: Option<String>,
-    api_key: String,
+    header_key: MetadataKey<Ascii>,
+    /// The parsed API key, or the error every call fails with
+    value: Result<AsciiMetadataValue, Status>,
+    override_policy: OverridePolicy,
 }
 
 const X_API_KEY: &str = "x-api-key";
 
+fn invalid_metadata() -> Status {
+    Status::invalid_argument("Error while setting additional metadata")
+}
+
+/// Parses the name of a header, so interceptors reject it up front
+pub(crate) fn metadata_key(header_name: &str) -> Result<MetadataKey<Ascii>, Status> {
+    MetadataKey::from_bytes(header_name.as_bytes())
+        .map_err(|e| Status::invalid_argument(format!("Invalid meta data key: {e}")))
+}
+
 impl APIKeyClientInterceptor {
     /// Cretes a new interceptor for API-Key authentication
+    ///
+    /// An API key, that is no valid header value, fails every call.
     /// # Arguments
     /// * `api_key`: The API key that should be used for authentication
     ///
+    #[deprecated(note = "Use try_new, which rejects an invalid API key up front")]
     pub fn new(api_key: String) -> Self {
         Self {
-            api_key,
-            header_name: None,
+            header_key: MetadataKey::from_static(X_API_KEY),
+            value: AsciiMetadataValue::from_str(&api_key).map_err(|_| invalid_metadata()),
+            override_policy: OverridePolicy::default(),
         }
     }
 
-    fn header_name(&self) -> String {
-        self.header_name
-            .clone()
-            .unwrap_or_else(|| String::from(X_API_KEY))
+    /// Creates a new interceptor for API-Key authentication, failing if the
+    /// API key is no valid header value
+    /// # Arguments
+    /// * `api_key`: The API key that should be used for authentication
+    pub fn try_new(api_key: &str) -> Result<Self, Status> {
+        Ok(Self {
+            header_key: MetadataKey::from_static(X_API_KEY),
+            value: Ok(AsciiMetadataValue::from_str(api_key).map_err(|_| invalid_metadata())?),
+            override_policy: OverridePolicy::default(),
+        })
+    }
+
+    /// Overrides the name of the API key header (default `x-api-key`)
+    pub fn with_header_name(mut self, header_name: &str) -> Result<Self, Status> {
+        self.header_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Sets the policy for an already present API key header
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
     }
 
     pub fn header_key(&self) -> MetadataKey<Ascii> {
-        let header_name = self.header_name();
-        match MetadataKey::<Ascii>::from_bytes(header_name.as_bytes()) {
-            Ok(key) => key,
-            Err(e) => {
-                log::error!("Invalid meta data key: {e}");
-                MetadataKey::from_static(X_API_KEY)
-            }
-        }
+        self.header_key.clone()
+    }
+}
+
+/// A request extension, that replaces the API key of the
+/// [APIKeyClientInterceptor] for this call, e.g. for calls on behalf of a
+/// customer
+///
+/// The interceptor removes it from the extensions, so it does not reach the
+/// layers below. The [Debug] output does not show the key.
+#[derive(Clone)]
+pub struct ApiKeyOverride(pub String);
+
+impl std::fmt::Debug for ApiKeyOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKeyOverride(<redacted>)")
     }
 }
 
@@ -48,51 +216,362 @@ impl Interceptor for APIKeyClientInterceptor {
         &mut self,
         mut request: tonic::Request<()>,
     ) -> Result<tonic::Request<()>, tonic::Status> {
-        if let Ok(value) = AsciiMetadataValue::from_str(&self.api_key) {
-            request.metadata_mut().insert(self.header_key(), value);
-            return Ok(request);
-        }
+        let value = match request.extensions_mut().remove::<ApiKeyOverride>() {
+            // Only this call fails, the message does not contain the key
+            Some(ApiKeyOverride(api_key)) => AsciiMetadataValue::from_str(&api_key)
+                .map_err(|_| Status::invalid_argument("Invalid API key override"))?,
+            None => self.value.clone()?,
+        };
+        self.override_policy
+            .apply(request.metadata_mut(), self.header_key.clone(), value);
+        Ok(request)
+    }
+}
 
-        Err(Status::invalid_argument(
-            "Error while setting additional metadata",
+impl DescribableInterceptor for APIKeyClientInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "header {}, override policy {:?}",
+            self.header_key, self.override_policy
         ))
     }
 }
 
 pub struct BearerTokenInterceptor {
-    token: String,
+    /// The parsed `Bearer` header value, or the error every call fails with
+    value: Result<AsciiMetadataValue, Status>,
+    override_policy: OverridePolicy,
+}
+
+fn bearer_value(token: &str) -> Result<AsciiMetadataValue, Status> {
+    format!("Bearer {token}")
+        .parse()
+        .map_err(|_| tonic::Status::invalid_argument("Invalid Token"))
 }
 
 impl BearerTokenInterceptor {
+    /// Creates a new interceptor for bearer token authentication
+    ///
+    /// A token, that is no valid header value, fails every call.
+    #[deprecated(note = "Use try_new, which rejects an invalid token up front")]
     pub fn new(token: String) -> Self {
-        BearerTokenInterceptor { token }
+        BearerTokenInterceptor {
+            value: bearer_value(&token),
+            override_policy: OverridePolicy::default(),
+        }
+    }
+
+    /// Creates a new interceptor for bearer token authentication, failing if
+    /// the token is no valid header value
+    pub fn try_new(token: &str) -> Result<Self, Status> {
+        Ok(BearerTokenInterceptor {
+            value: Ok(bearer_value(token)?),
+            override_policy: OverridePolicy::default(),
+        })
+    }
+
+    /// Sets the policy for an already present authorization header
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
     }
 }
 
 impl Interceptor for BearerTokenInterceptor {
     fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
-        req.metadata_mut().insert(
-            "authorization",
-            format!("Bearer {}", self.token)
-                .parse()
-                .map_err(|_| tonic::Status::invalid_argument("Invalid Token"))?,
+        self.override_policy.apply(
+            req.metadata_mut(),
+            MetadataKey::from_static("authorization"),
+            self.value.clone()?,
         );
         Ok(req)
     }
 }
 
+impl DescribableInterceptor for BearerTokenInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "header authorization, override policy {:?}",
+            self.override_policy
+        ))
+    }
+}
+
+/// An [Interceptor], that describes itself for [CompositeInterceptor::snapshot]
+///
+/// Describing is opt-in: the chain only shows the description of
+/// interceptors added with [ChainBuilder::with_described]. All methods have
+/// defaults, so `impl DescribableInterceptor for MyInterceptor {}` is enough.
+pub trait DescribableInterceptor: Interceptor {
+    /// Returns the name of the interceptor, its type name by default
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// Returns whether the interceptor changes requests
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Returns a summary of the configuration
+    ///
+    /// It is shown on admin endpoints, so it must never contain secret
+    /// values like keys or tokens.
+    fn describe(&self) -> Option<String> {
+        None
+    }
+}
+
 /// A type alias for an [Interceptor] implementation
 pub type BoxedInterceptor = Box<dyn Interceptor + Send + Sync>;
 /// A type alias for a list of [Interceptor] implementations
 pub type Interceptors = Arc<Mutex<Vec<BoxedInterceptor>>>;
 
+/// Boxes an interceptor closure, so it can join [Interceptors]
+///
+/// It accepts the closures used with [tonic::service::interceptor()].
+pub fn from_tonic_fn<F>(f: F) -> BoxedInterceptor
+where
+    F: FnMut(tonic::Request<()>) -> Result<tonic::Request<()>, Status> + Send + Sync + 'static,
+{
+    Box::new(f)
+}
+
+/// An entry of the [CompositeInterceptor::snapshot]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterceptorInfo {
+    /// The position in the chain, starting at 0
+    pub position: usize,
+    /// The name, see [DescribableInterceptor::name]
+    pub name: String,
+    /// Whether the interceptor changes requests
+    pub enabled: bool,
+    /// The redacted summary, see [DescribableInterceptor::describe]
+    pub summary: Option<String>,
+}
+
+/// The name of interceptors without a description in the snapshot
+pub(crate) const UNNAMED: &str = "unnamed";
+
+/// Builds a [CompositeInterceptor], that describes its interceptors in the
+/// [CompositeInterceptor::snapshot]
+///
+/// The descriptions are taken when the interceptors are added and kept next
+/// to the list, the interceptors are not asked again.
+#[derive(Default)]
+pub struct ChainBuilder {
+    interceptors: Vec<BoxedInterceptor>,
+    descriptions: Vec<InterceptorInfo>,
+}
+
+impl ChainBuilder {
+    /// Creates a new, empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(
+        mut self,
+        interceptor: BoxedInterceptor,
+        name: String,
+        enabled: bool,
+        summary: Option<String>,
+    ) -> Self {
+        self.descriptions.push(InterceptorInfo {
+            position: self.interceptors.len(),
+            name,
+            enabled,
+            summary,
+        });
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Adds an interceptor, named by its type
+    pub fn with<I: Interceptor + Send + Sync + 'static>(self, interceptor: I) -> Self {
+        let name = std::any::type_name::<I>().to_string();
+        self.push(Box::new(interceptor), name, true, None)
+    }
+
+    /// Adds an interceptor under the name, e.g. a closure, whose type name
+    /// says little
+    pub fn with_named<I: Interceptor + Send + Sync + 'static>(
+        self,
+        name: &str,
+        interceptor: I,
+    ) -> Self {
+        self.push(Box::new(interceptor), name.to_string(), true, None)
+    }
+
+    /// Adds an interceptor with its own description
+    pub fn with_described<I: DescribableInterceptor + Send + Sync + 'static>(
+        self,
+        interceptor: I,
+    ) -> Self {
+        let (name, enabled, summary) = (
+            interceptor.name(),
+            interceptor.enabled(),
+            interceptor.describe(),
+        );
+        self.push(Box::new(interceptor), name, enabled, summary)
+    }
+
+    /// Creates the chain
+    pub fn build(mut self) -> CompositeInterceptor {
+        let chain = match self.interceptors.len() {
+            1 => Chain::One(Arc::new(Mutex::new(self.interceptors.remove(0)))),
+            _ => Chain::Many(Arc::new(Mutex::new(self.interceptors))),
+        };
+        let mut composite = CompositeInterceptor::with_chain(chain);
+        composite.descriptions = self.descriptions.into();
+        composite
+    }
+}
+
+/// A function returning the current time, used to inject clocks in tests
+pub type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+/// Returns the time of the clock in milliseconds since the unix epoch
+pub(crate) fn unix_millis(clock: &Clock) -> Result<u64, Status> {
+    Ok(clock()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| Status::internal("System time is before the unix epoch"))?
+        .as_millis() as u64)
+}
+
+/// Defines, whether a [CompositeInterceptor] checks for interceptors
+/// overwriting each other's metadata
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictDetection {
+    /// Do not check, without any overhead
+    #[default]
+    Off,
+    /// Log a warning for every conflict
+    Warn,
+    /// Fail the call with [Status::internal] on the first conflict
+    Strict,
+}
+
+/// The one who last wrote a metadata key
+#[derive(Clone, Copy)]
+enum Writer {
+    Caller,
+    Interceptor(usize),
+}
+
+/// Returns the values of all keys, to find the keys an interceptor wrote
+fn snapshot(metadata: &MetadataMap) -> HashMap<String, Vec<Vec<u8>>> {
+    let mut values: HashMap<String, Vec<Vec<u8>>> = HashMap::new();
+    for entry in metadata.iter() {
+        let (key, value) = match entry {
+            KeyAndValueRef::Ascii(key, value) => (key.as_str(), value.as_bytes()),
+            KeyAndValueRef::Binary(key, value) => (key.as_str(), value.as_encoded_bytes()),
+        };
+        values
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_vec());
+    }
+    values
+}
+
+/// Tracks the writer of every key through the chain
+struct Conflicts {
+    mode: ConflictDetection,
+    writers: HashMap<String, Writer>,
+    values: HashMap<String, Vec<Vec<u8>>>,
+}
+
+impl Conflicts {
+    fn new(mode: ConflictDetection, metadata: &MetadataMap) -> Self {
+        let values = snapshot(metadata);
+        let writers = values
+            .keys()
+            .map(|key| (key.clone(), Writer::Caller))
+            .collect();
+        Self {
+            mode,
+            writers,
+            values,
+        }
+    }
+
+    /// Checks the metadata after the interceptor at the index was called
+    ///
+    /// Appending values to a key is not a conflict, replacing or removing
+    /// them is.
+    fn check(&mut self, index: usize, metadata: &MetadataMap) -> Result<(), Status> {
+        let after = snapshot(metadata);
+        let keys: HashSet<&String> = self.values.keys().chain(after.keys()).collect();
+        let empty = Vec::new();
+        for key in keys {
+            let old = self.values.get(key).unwrap_or(&empty);
+            let new = after.get(key).unwrap_or(&empty);
+            if old == new {
+                continue;
+            }
+            let appended = new.starts_with(old);
+            match self.writers.insert(key.clone(), Writer::Interceptor(index)) {
+                Some(Writer::Interceptor(earlier)) if !appended => {
+                    let message = format!(
+                        "Interceptor {index} overwrote the metadata key {key} set by interceptor {earlier}"
+                    );
+                    if self.mode == ConflictDetection::Strict {
+                        return Err(Status::internal(message));
+                    }
+                    log::warn!("{message}");
+                }
+                Some(Writer::Caller) if !appended => {
+                    log::debug!(
+                        "Interceptor {index} overwrote the metadata key {key} set by the caller"
+                    );
+                }
+                _ => {}
+            }
+        }
+        self.values = after;
+        Ok(())
+    }
+}
+
 /// A composite interceptor
 ///
 /// It contain a list of interceptors, that will be called in sequence on
 /// every call
 ///
+/// A panicking interceptor fails the call with [Status::internal] naming its
+/// index in the list, and the chain stays usable for the next calls. Use
+/// [CompositeInterceptor::with_fail_fast] to let panics unwind instead.
+/// Clones share the list of interceptors.
+///
+/// A chain of one interceptor from a [ChainBuilder] or [composite!](crate::composite)
+/// calls it without the list: no iteration, no metadata snapshots for the
+/// conflict detection, which can not find a conflict with one interceptor,
+/// and no allocation for the stats.
+#[derive(Clone)]
 pub struct CompositeInterceptor {
-    interceptors: Interceptors,
+    chain: Chain,
+    /// The descriptions by position, see [ChainBuilder]
+    descriptions: Arc<[InterceptorInfo]>,
+    fail_fast: bool,
+    conflict_detection: ConflictDetection,
+    stats: Option<ChainStats>,
+}
+
+/// The interceptors of a [CompositeInterceptor]
+#[derive(Clone)]
+enum Chain {
+    /// A list shared with the caller, that may change between calls
+    Many(Interceptors),
+    /// The only interceptor of a chain, that can not change
+    One(Arc<Mutex<BoxedInterceptor>>),
+}
+
+/// The collector of a chain with the names of its interceptors
+#[derive(Clone)]
+struct ChainStats {
+    collector: StatsCollector,
+    names: Arc<[Arc<str>]>,
 }
 
 impl CompositeInterceptor {
@@ -100,21 +579,259 @@ impl CompositeInterceptor {
     /// # Arguments
     /// * `interceptors`: A vector of [Interceptor] instances
     pub fn new(interceptors: Interceptors) -> Self {
-        Self { interceptors }
+        Self::with_chain(Chain::Many(interceptors))
     }
+
+    fn with_chain(chain: Chain) -> Self {
+        Self {
+            chain,
+            descriptions: Arc::new([]),
+            fail_fast: false,
+            conflict_detection: ConflictDetection::default(),
+            stats: None,
+        }
+    }
+
+    /// Lets panics of the interceptors unwind through the call
+    ///
+    /// The panic poisons the list, so all further calls fail.
+    pub fn with_fail_fast(mut self) -> Self {
+        self.fail_fast = true;
+        self
+    }
+
+    /// Checks for interceptors overwriting metadata keys, that an earlier
+    /// interceptor of the chain set
+    ///
+    /// Overwriting a key set by the caller before the chain ran is only
+    /// logged at debug level, as that is what [OverridePolicy::Overwrite]
+    /// is for.
+    pub fn with_conflict_detection(mut self, conflict_detection: ConflictDetection) -> Self {
+        self.conflict_detection = conflict_detection;
+        self
+    }
+
+    /// Records the calls of the chain and of every interceptor in the
+    /// collector
+    ///
+    /// The interceptors are named as in the [CompositeInterceptor::snapshot].
+    pub fn with_stats(mut self, stats: StatsCollector) -> Self {
+        let names = self
+            .descriptions
+            .iter()
+            .map(|info| Arc::from(info.name.as_str()))
+            .collect();
+        self.stats = Some(ChainStats {
+            collector: stats,
+            names,
+        });
+        self
+    }
+
+    /// Returns the chain as a closure, e.g. for `with_interceptor` of a
+    /// generated client or [tonic::service::interceptor()]
+    ///
+    /// Clones of the closure share the list of interceptors.
+    pub fn into_fn(
+        mut self,
+    ) -> impl FnMut(tonic::Request<()>) -> Result<tonic::Request<()>, Status> + Clone + Send + Sync
+    {
+        move |req| self.call(req)
+    }
+
+    /// Returns the interceptors currently in the chain
+    ///
+    /// Interceptors are described, if the chain was built with a
+    /// [ChainBuilder], others are `unnamed`. The list is only locked to read
+    /// its length, a poisoned list is read anyway.
+    pub fn snapshot(&self) -> Vec<InterceptorInfo> {
+        let len = match &self.chain {
+            Chain::Many(interceptors) => {
+                interceptors.lock().unwrap_or_else(|e| e.into_inner()).len()
+            }
+            Chain::One(_) => 1,
+        };
+        (0..len)
+            .map(|position| {
+                self.descriptions
+                    .get(position)
+                    .cloned()
+                    .unwrap_or_else(|| InterceptorInfo {
+                        position,
+                        name: UNNAMED.to_string(),
+                        enabled: true,
+                        summary: None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Calls the interceptors in sequence
+    ///
+    /// The result of every called interceptor is added to the results.
+    fn call_chain(
+        &self,
+        interceptors: &mut [BoxedInterceptor],
+        mut req: tonic::Request<()>,
+        mut results: Option<&mut Vec<Option<tonic::Code>>>,
+    ) -> Result<tonic::Request<()>, Status> {
+        let mut conflicts = (self.conflict_detection != ConflictDetection::Off)
+            .then(|| Conflicts::new(self.conflict_detection, req.metadata()));
+        for (index, interceptor) in interceptors.iter_mut().enumerate() {
+            let result = call_isolated(self.fail_fast, index, interceptor.as_mut(), req);
+            if let Some(results) = results.as_deref_mut() {
+                results.push(result.as_ref().err().map(Status::code));
+            }
+            req = result?;
+            if let Some(conflicts) = &mut conflicts {
+                conflicts.check(index, req.metadata())?;
+            }
+        }
+        Ok(req)
+    }
+
+    /// Calls the only interceptor of a chain like [Self::call_chain]
+    fn call_one(
+        &self,
+        interceptor: &Mutex<BoxedInterceptor>,
+        req: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, Status> {
+        let mut interceptor = interceptor.lock().map_err(lock_failed)?;
+        let Some(stats) = &self.stats else {
+            return call_isolated(self.fail_fast, 0, interceptor.as_mut(), req);
+        };
+        let started = Instant::now();
+        let result = call_isolated(self.fail_fast, 0, interceptor.as_mut(), req);
+        let code = result.as_ref().err().map(Status::code);
+        stats
+            .collector
+            .record(started.elapsed(), code, &stats.names, &[code]);
+        result
+    }
+}
+
+/// Calls the interceptor, turning a panic into a [Status] naming the index,
+/// unless it should fail fast
+fn call_isolated<I: Interceptor + ?Sized>(
+    fail_fast: bool,
+    index: usize,
+    interceptor: &mut I,
+    req: tonic::Request<()>,
+) -> Result<tonic::Request<()>, Status> {
+    if fail_fast {
+        return interceptor.call(req);
+    }
+
+    // The request is moved into the closure and lost on a panic, so
+    // no broken request can be observed. The interceptor may be left
+    // inconsistent, which is accepted over failing all later calls.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| interceptor.call(req)));
+    result.unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+        log::error!("Interceptor {index} panicked: {message}");
+        Err(Status::internal(format!(
+            "Interceptor {index} panicked: {message}"
+        )))
+    })
+}
+
+/// Returns the message of a panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Maps the error to a Status, indicating that the lock operation failed
+fn lock_failed<T>(e: std::sync::PoisonError<T>) -> Status {
+    Status::internal(format!("Failed to lock interceptors: {}", e))
 }
 
 impl Interceptor for CompositeInterceptor {
-    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
-        let mut interceptors = self.interceptors.lock().map_err(|e| {
-            // Map the error to a Status, indicating that the lock operation failed
-            Status::internal(format!("Failed to lock interceptors: {}", e))
-        })?;
+    fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let interceptors = match &self.chain {
+            Chain::Many(interceptors) => interceptors,
+            Chain::One(interceptor) => return self.call_one(interceptor, req),
+        };
+        let mut interceptors = interceptors.lock().map_err(lock_failed)?;
 
-        for interceptor in interceptors.iter_mut() {
-            req = interceptor.call(req)?;
+        let Some(stats) = &self.stats else {
+            return self.call_chain(&mut interceptors, req, None);
+        };
+        let started = Instant::now();
+        let mut results = Vec::with_capacity(interceptors.len());
+        let result = self.call_chain(&mut interceptors, req, Some(&mut results));
+        stats.collector.record(
+            started.elapsed(),
+            result.as_ref().err().map(Status::code),
+            &stats.names,
+            &results,
+        );
+        result
+    }
+}
+
+impl DescribableInterceptor for CompositeInterceptor {
+    fn describe(&self) -> Option<String> {
+        let names: Vec<_> = self.snapshot().into_iter().map(|info| info.name).collect();
+        Some(format!("chain of [{}]", names.join(", ")))
+    }
+}
+
+/// A chain of exactly one interceptor
+///
+/// It behaves like a [CompositeInterceptor] with one element, including the
+/// panic isolation, but calls the interceptor directly, without the lock of
+/// a shared list and dynamic dispatch. The interceptor can not be replaced
+/// after creation, and clones clone it instead of sharing it. A
+/// [composite!](crate::composite) of one interceptor keeps the sharing and
+/// skips the list only.
+#[derive(Clone)]
+pub struct SingleInterceptor<I> {
+    interceptor: I,
+    fail_fast: bool,
+}
+
+impl<I: Interceptor> SingleInterceptor<I> {
+    /// Creates a new chain of one interceptor
+    pub fn new(interceptor: I) -> Self {
+        Self {
+            interceptor,
+            fail_fast: false,
         }
-        Ok(req)
+    }
+
+    /// Lets panics of the interceptor unwind through the call
+    pub fn with_fail_fast(mut self) -> Self {
+        self.fail_fast = true;
+        self
+    }
+
+    /// Returns the interceptor, like [CompositeInterceptor::snapshot] of a
+    /// chain built with [ChainBuilder::with]
+    pub fn snapshot(&self) -> Vec<InterceptorInfo> {
+        vec![InterceptorInfo {
+            position: 0,
+            name: std::any::type_name::<I>().to_string(),
+            enabled: true,
+            summary: None,
+        }]
+    }
+}
+
+impl<I: Interceptor> Interceptor for SingleInterceptor<I> {
+    fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        call_isolated(self.fail_fast, 0, &mut self.interceptor, req)
+    }
+}
+
+impl<I: Interceptor> DescribableInterceptor for SingleInterceptor<I> {
+    fn describe(&self) -> Option<String> {
+        Some(format!("chain of [{}]", std::any::type_name::<I>()))
     }
 }
 
@@ -123,60 +840,581 @@ macro_rules! interceptors {
     // Match the case where we have at least one item
 ( $($interceptor:expr),* ) => {{
         // Create the interceptors vector
-        let interceptors: Vec<Box<dyn tonic::service::Interceptor + Send + Sync>> = vec![
-            $( Box::new($interceptor) ),*
+        let interceptors: ::std::vec::Vec<$crate::grpc::interceptor::BoxedInterceptor> = ::std::vec![
+            $( ::std::boxed::Box::new($interceptor) ),*
         ];
 
         // Wrap the vector inside an Arc<Mutex>
-        std::sync::Arc::new(std::sync::Mutex::new(interceptors))
+        ::std::sync::Arc::new(::std::sync::Mutex::new(interceptors))
     }};
 }
 
+/// Creates a [CompositeInterceptor] calling the interceptors in sequence
+///
+/// It is the same as `ChainBuilder::new().with(...).build()`, so the
+/// snapshot names the interceptors by their types.
+#[macro_export]
+macro_rules! composite {
+    ( $($interceptor:expr),* $(,)? ) => {
+        $crate::grpc::interceptor::ChainBuilder::new()
+            $( .with($interceptor) )*
+            .build()
+    };
+}
+
+/// Creates a generated client, that calls the interceptor on every request
+///
+/// It expands to `ClientType::with_interceptor(channel, interceptor)`:
+/// ```ignore
+/// let client = intercepted_client!(
+///     GreeterClient<_>,
+///     channel,
+///     composite!(BearerTokenInterceptor::try_new(&token)?)
+/// );
+/// ```
+#[macro_export]
+macro_rules! intercepted_client {
+    ( $client:ty, $channel:expr, $interceptor:expr $(,)? ) => {
+        <$client>::with_interceptor($channel, $interceptor)
+    };
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::grpc::interceptor::{APIKeyClientInterceptor, BearerTokenInterceptor, X_API_KEY};
+    use tonic::service::Interceptor;
+    use tonic::Code;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::{
+        from_tonic_fn, APIKeyClientInterceptor, ApiKeyOverride, BearerTokenInterceptor,
+        ChainBuilder, CompositeInterceptor, ConflictDetection, InterceptorInfo, OverridePolicy,
+        SingleInterceptor, StatsCollector, X_API_KEY,
+    };
 
     #[test]
     fn test_api_key_header_none() {
-        let test_object = APIKeyClientInterceptor {
-            api_key: "key".to_string(),
-            header_name: None,
-        };
+        let test_object = APIKeyClientInterceptor::try_new("key").unwrap();
 
-        assert_eq!("key", test_object.api_key);
         assert_eq!(X_API_KEY, test_object.header_key());
+        assert_sets_metadata!(test_object, { "x-api-key" => "key" });
     }
 
     #[test]
     fn test_api_key_header_some() {
-        let test_object = APIKeyClientInterceptor {
-            api_key: "key".to_string(),
-            header_name: Some(String::from("alternative-key")),
-        };
+        let test_object = APIKeyClientInterceptor::try_new("key")
+            .unwrap()
+            .with_header_name("alternative-key")
+            .unwrap();
 
-        assert_eq!("key", test_object.api_key);
         assert_eq!("alternative-key", test_object.header_key());
+        let req = assert_sets_metadata!(test_object, { "alternative-key" => "key" });
+        assert!(req.metadata().get(X_API_KEY).is_none());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_invalid_values() {
+        assert!(APIKeyClientInterceptor::try_new("key")
+            .unwrap()
+            .with_header_name("invalid key")
+            .is_err());
+        assert!(APIKeyClientInterceptor::try_new("in\nvalid").is_err());
+        assert!(BearerTokenInterceptor::try_new("in\nvalid").is_err());
+        assert!(BearerTokenInterceptor::try_new("token").is_ok());
+
+        // Invalid values fail every call, like before they were parsed once
+        let test_object = APIKeyClientInterceptor::new("in\nvalid".to_string());
+        for _ in 0..2 {
+            assert_sets_metadata!(test_object.clone(), Err(Code::InvalidArgument));
+        }
+        let status = assert_sets_metadata!(
+            BearerTokenInterceptor::new("in\nvalid".to_string()),
+            Err(Code::InvalidArgument)
+        );
+        assert_eq!("Invalid Token", status.message());
+    }
+
+    #[test]
+    fn test_api_key_override() {
+        let mut test_object = APIKeyClientInterceptor::try_new("service-key").unwrap();
+
+        let mut req = tonic::Request::new(());
+        req.extensions_mut()
+            .insert(ApiKeyOverride("customer-key".to_string()));
+        let req = test_object.call(req).unwrap();
+        assert_eq!("customer-key", req.metadata().get(X_API_KEY).unwrap());
+        assert!(req.extensions().get::<ApiKeyOverride>().is_none());
+
+        let req = test_object.call(tonic::Request::new(())).unwrap();
+        assert_eq!("service-key", req.metadata().get(X_API_KEY).unwrap());
+
+        // An invalid override only fails its own call
+        let mut req = tonic::Request::new(());
+        req.extensions_mut()
+            .insert(ApiKeyOverride("in\nvalid".to_string()));
+        let status = test_object.call(req).unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+        assert_eq!("Invalid API key override", status.message());
+        assert!(test_object.call(tonic::Request::new(())).is_ok());
+
+        assert_eq!(
+            "ApiKeyOverride(<redacted>)",
+            format!("{:?}", ApiKeyOverride("customer-key".to_string()))
+        );
+    }
+
+    /// Checks, that the calls are faster than parsing the values on every
+    /// call, as it was done before; run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn bench_precomputed_values() {
+        const CALLS: u32 = 100_000;
+        let mut test_object = APIKeyClientInterceptor::try_new("key").unwrap();
+
+        let started = std::time::Instant::now();
+        for _ in 0..CALLS {
+            let mut req = tonic::Request::new(());
+            let key = tonic::metadata::MetadataKey::from_bytes(X_API_KEY.as_bytes()).unwrap();
+            let value: tonic::metadata::AsciiMetadataValue = "key".parse().unwrap();
+            req.metadata_mut().insert(key, value);
+        }
+        let parsing = started.elapsed() / CALLS;
+
+        let started = std::time::Instant::now();
+        for _ in 0..CALLS {
+            test_object.call(tonic::Request::new(())).unwrap();
+        }
+        let precomputed = started.elapsed() / CALLS;
+        assert!(
+            precomputed < parsing,
+            "Precomputed {precomputed:?} per call, parsing every call {parsing:?}"
+        );
     }
 
     #[test]
     fn test_macro() {
         let interceptors = interceptors!(
-            APIKeyClientInterceptor {
-                api_key: "key".to_string(),
-                header_name: None,
-            },
-            BearerTokenInterceptor {
-                token: "token".to_string()
-            }
+            APIKeyClientInterceptor::try_new("key").unwrap(),
+            BearerTokenInterceptor::try_new("token").unwrap()
         );
 
         assert_eq!(2, interceptors.lock().unwrap().len());
     }
 
+    /// The part of a generated client used by intercepted_client!
+    struct TestClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+
+    impl<T> TestClient<T> {
+        fn with_interceptor<F: Interceptor>(
+            inner: T,
+            interceptor: F,
+        ) -> TestClient<tonic::service::interceptor::InterceptedService<T, F>> {
+            TestClient {
+                inner: tonic::client::Grpc::new(
+                    tonic::service::interceptor::InterceptedService::new(inner, interceptor),
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_composite_and_intercepted_client() {
+        // A local variable with the name used inside the macros
+        let interceptors = "not the interceptor list";
+
+        let headers = std::sync::Arc::new(std::sync::Mutex::new(http::HeaderMap::new()));
+        let captured = headers.clone();
+        let service = tower::service_fn(move |req: http::Request<tonic::body::BoxBody>| {
+            *captured.lock().unwrap() = req.headers().clone();
+            async {
+                Ok::<_, std::convert::Infallible>(
+                    http::Response::builder()
+                        .header("grpc-status", "12")
+                        .body(tonic::body::empty_body())
+                        .unwrap(),
+                )
+            }
+        });
+
+        let mut client = crate::intercepted_client!(
+            TestClient<_>,
+            service,
+            crate::composite!(
+                APIKeyClientInterceptor::try_new("key").unwrap(),
+                BearerTokenInterceptor::try_new("token").unwrap(),
+            )
+        );
+        client.inner.ready().await.unwrap();
+        let _ = client
+            .inner
+            .unary(
+                tonic::Request::new(()),
+                http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await;
+
+        let headers = headers.lock().unwrap();
+        assert_eq!("key", headers[X_API_KEY]);
+        assert_eq!("Bearer token", headers["authorization"]);
+        assert_eq!("not the interceptor list", interceptors);
+    }
+
     #[test]
     fn test_bearer_token() {
-        let test_object = BearerTokenInterceptor::new("test-token".to_string());
+        assert_sets_metadata!(BearerTokenInterceptor::try_new("test-token").unwrap(), {
+            "authorization" => "Bearer test-token",
+        });
+    }
+
+    fn prepopulated() -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("authorization", "Bearer app-token".parse().unwrap());
+        req.metadata_mut()
+            .insert(X_API_KEY, "app-key".parse().unwrap());
+        req
+    }
+
+    fn values(req: &tonic::Request<()>, key: &str) -> Vec<String> {
+        req.metadata()
+            .get_all(key)
+            .iter()
+            .map(|value| value.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_override_policy_overwrite() {
+        let req = BearerTokenInterceptor::try_new("token")
+            .unwrap()
+            .call(prepopulated())
+            .unwrap();
+        assert_eq!(vec!["Bearer token"], values(&req, "authorization"));
+
+        let req = APIKeyClientInterceptor::try_new("key")
+            .unwrap()
+            .with_override_policy(OverridePolicy::Overwrite)
+            .call(prepopulated())
+            .unwrap();
+        assert_eq!(vec!["key"], values(&req, X_API_KEY));
+    }
+
+    #[test]
+    fn test_override_policy_skip_if_present() {
+        let mut test_object = BearerTokenInterceptor::try_new("token")
+            .unwrap()
+            .with_override_policy(OverridePolicy::SkipIfPresent);
+        let req = test_object.call(prepopulated()).unwrap();
+        assert_eq!(vec!["Bearer app-token"], values(&req, "authorization"));
+
+        let req = test_object.call(tonic::Request::new(())).unwrap();
+        assert_eq!(vec!["Bearer token"], values(&req, "authorization"));
+
+        let req = APIKeyClientInterceptor::try_new("key")
+            .unwrap()
+            .with_override_policy(OverridePolicy::SkipIfPresent)
+            .call(prepopulated())
+            .unwrap();
+        assert_eq!(vec!["app-key"], values(&req, X_API_KEY));
+    }
+
+    #[test]
+    fn test_override_policy_append() {
+        let req = BearerTokenInterceptor::try_new("token")
+            .unwrap()
+            .with_override_policy(OverridePolicy::Append)
+            .call(prepopulated())
+            .unwrap();
+        assert_eq!(
+            vec!["Bearer app-token", "Bearer token"],
+            values(&req, "authorization")
+        );
+
+        let req = APIKeyClientInterceptor::try_new("key")
+            .unwrap()
+            .with_override_policy(OverridePolicy::Append)
+            .call(prepopulated())
+            .unwrap();
+        assert_eq!(vec!["app-key", "key"], values(&req, X_API_KEY));
+    }
+
+    #[test]
+    fn test_panic_isolation() {
+        let mut test_object = crate::composite!(
+            APIKeyClientInterceptor::try_new("key").unwrap(),
+            |req: tonic::Request<()>| {
+                if req.metadata().contains_key("x-malformed") {
+                    panic!("malformed value");
+                }
+                Ok(req)
+            }
+        );
+
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("x-malformed", "value".parse().unwrap());
+        let status = test_object.call(req).unwrap_err();
+        assert_eq!(tonic::Code::Internal, status.code());
+        assert_eq!("Interceptor 1 panicked: malformed value", status.message());
+
+        let req = test_object.call(tonic::Request::new(())).unwrap();
+        assert_eq!(vec!["key"], values(&req, X_API_KEY));
+    }
+
+    #[test]
+    fn test_fail_fast() {
+        let interceptors = crate::interceptors!(
+            |_req: tonic::Request<()>| -> Result<tonic::Request<()>, tonic::Status> {
+                panic!("fail fast")
+            }
+        );
+        let mut test_object = CompositeInterceptor::new(interceptors).with_fail_fast();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            test_object.call(tonic::Request::new(()))
+        }));
+        assert!(result.is_err());
+        let status = test_object.call(tonic::Request::new(())).unwrap_err();
+        assert!(status.message().starts_with("Failed to lock interceptors"));
+    }
+
+    fn conflicting(mode: ConflictDetection) -> CompositeInterceptor {
+        crate::composite!(
+            BearerTokenInterceptor::try_new("first").unwrap(),
+            APIKeyClientInterceptor::try_new("key").unwrap(),
+            BearerTokenInterceptor::try_new("second").unwrap(),
+        )
+        .with_conflict_detection(mode)
+    }
+
+    #[test]
+    fn test_conflict_detection_warn() {
+        let req = conflicting(ConflictDetection::Warn)
+            .call(tonic::Request::new(()))
+            .unwrap();
+        assert_eq!(vec!["Bearer second"], values(&req, "authorization"));
+    }
+
+    #[test]
+    fn test_conflict_detection_strict() {
+        let status = conflicting(ConflictDetection::Strict)
+            .call(tonic::Request::new(()))
+            .unwrap_err();
+        assert_eq!(tonic::Code::Internal, status.code());
+        assert_eq!(
+            "Interceptor 2 overwrote the metadata key authorization set by interceptor 0",
+            status.message()
+        );
+
+        // Keys of the caller and appended values are no conflicts
+        let req = crate::composite!(
+            BearerTokenInterceptor::try_new("token").unwrap(),
+            APIKeyClientInterceptor::try_new("key").unwrap(),
+            APIKeyClientInterceptor::try_new("other")
+                .unwrap()
+                .with_override_policy(OverridePolicy::Append),
+        )
+        .with_conflict_detection(ConflictDetection::Strict)
+        .call(prepopulated())
+        .unwrap();
+        assert_eq!(vec!["Bearer token"], values(&req, "authorization"));
+        assert_eq!(vec!["key", "other"], values(&req, X_API_KEY));
+    }
+
+    /// An interceptor of a user, that does not describe itself
+    struct CustomInterceptor;
+
+    impl Interceptor for CustomInterceptor {
+        fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+            Ok(req)
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let closure = |req: tonic::Request<()>| Ok(req);
+        let composite = ChainBuilder::new()
+            .with_described(APIKeyClientInterceptor::try_new("secret-key").unwrap())
+            .with_described(
+                BearerTokenInterceptor::try_new("secret-token")
+                    .unwrap()
+                    .with_override_policy(OverridePolicy::SkipIfPresent),
+            )
+            .with_named("noop", closure)
+            .with(closure)
+            .with(CustomInterceptor)
+            .build();
+
+        let snapshot = composite.snapshot();
+        assert_eq!(5, snapshot.len());
+        assert_eq!(
+            InterceptorInfo {
+                position: 0,
+                name: std::any::type_name::<APIKeyClientInterceptor>().to_string(),
+                enabled: true,
+                summary: Some("header x-api-key, override policy Overwrite".to_string()),
+            },
+            snapshot[0]
+        );
+        assert_eq!(
+            Some("header authorization, override policy SkipIfPresent"),
+            snapshot[1].summary.as_deref()
+        );
+        assert_eq!(
+            ("noop", None),
+            (snapshot[2].name.as_str(), snapshot[2].summary.as_ref())
+        );
+        assert!(snapshot[3].name.contains("test_snapshot"));
+        assert_eq!(3, snapshot[3].position);
+        assert_eq!(std::any::type_name::<CustomInterceptor>(), snapshot[4].name);
+
+        let debug = format!("{snapshot:?}");
+        assert!(!debug.contains("secret"));
+        // The list is not locked after the snapshot
+        assert_eq!(5, composite.snapshot().len());
+    }
+
+    #[test]
+    fn test_snapshot_without_descriptions() {
+        // Interceptors of a user join the list without any extra impl
+        let interceptors = crate::interceptors!(
+            CustomInterceptor,
+            APIKeyClientInterceptor::try_new("key").unwrap()
+        );
+        let composite = CompositeInterceptor::new(interceptors);
+        let snapshot = composite.snapshot();
+        assert_eq!(
+            vec![("unnamed", 0), ("unnamed", 1)],
+            snapshot
+                .iter()
+                .map(|info| (info.name.as_str(), info.position))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            std::any::type_name::<CustomInterceptor>(),
+            crate::composite!(CustomInterceptor).snapshot()[0].name
+        );
+    }
+
+    #[test]
+    fn test_tonic_fn_adapters() {
+        let tenant = |mut req: tonic::Request<()>| {
+            req.metadata_mut()
+                .insert("x-tenant", "acme".parse().unwrap());
+            Ok(req)
+        };
+        let interceptors = crate::interceptors!(APIKeyClientInterceptor::try_new("key").unwrap());
+        interceptors.lock().unwrap().push(from_tonic_fn(tenant));
+        let mut composite = CompositeInterceptor::new(interceptors.clone());
+        let mut closure = composite.clone().into_fn();
+        let mut cloned = closure.clone();
+
+        let via_chain = composite.call(tonic::Request::new(())).unwrap();
+        let via_fn = closure(tonic::Request::new(())).unwrap();
+        let via_clone = cloned(tonic::Request::new(())).unwrap();
+        for req in [&via_fn, &via_clone] {
+            assert_eq!(
+                via_chain.metadata().clone().into_headers(),
+                req.metadata().clone().into_headers()
+            );
+        }
+        assert_eq!("acme", via_fn.metadata().get("x-tenant").unwrap());
+        assert_eq!("key", via_fn.metadata().get(X_API_KEY).unwrap());
+
+        // The closure shares the list with the chain
+        interceptors.lock().unwrap().push(from_tonic_fn(|_req| {
+            Err::<tonic::Request<()>, _>(tonic::Status::aborted(""))
+        }));
+        assert_eq!(
+            tonic::Code::Aborted,
+            closure(tonic::Request::new(())).unwrap_err().code()
+        );
+        let _ = tonic::service::interceptor(cloned);
+    }
+
+    /// Fails calls with `x-fail`, panics on calls with `x-panic`
+    fn picky(req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        if req.metadata().contains_key("x-panic") {
+            panic!("picky panicked");
+        }
+        if req.metadata().contains_key("x-fail") {
+            return Err(tonic::Status::failed_precondition("picky failed"));
+        }
+        Ok(req)
+    }
+
+    /// Returns the headers set by the interceptor on a new request
+    fn headers(interceptor: &mut impl Interceptor) -> http::HeaderMap {
+        let req = interceptor.call(tonic::Request::new(())).unwrap();
+        req.into_parts().0.into_headers()
+    }
+
+    #[test]
+    fn test_single_interceptor() {
+        let key = || APIKeyClientInterceptor::try_new("key").unwrap();
+        let mut many = CompositeInterceptor::new(crate::interceptors!(key()));
+        let mut one = crate::composite!(key());
+        let mut single = SingleInterceptor::new(key());
+        assert_eq!(headers(&mut many), headers(&mut one));
+        assert_eq!(headers(&mut many), headers(&mut single));
+        assert_eq!(one.snapshot(), single.snapshot());
+        assert_eq!(1, many.snapshot().len());
+
+        let stats = StatsCollector::new(|_| {});
+        let mut many = CompositeInterceptor::new(crate::interceptors!(picky));
+        let mut one = ChainBuilder::new()
+            .with(picky)
+            .build()
+            .with_conflict_detection(ConflictDetection::Strict)
+            .with_stats(stats.clone());
+        let mut single = SingleInterceptor::new(picky);
+        for key in ["x-fail", "x-panic"] {
+            let request = || {
+                let mut req = tonic::Request::new(());
+                req.metadata_mut().insert(key, "1".parse().unwrap());
+                req
+            };
+            let expected = many.call(request()).unwrap_err();
+            for actual in [
+                one.call(request()).unwrap_err(),
+                single.call(request()).unwrap_err(),
+            ] {
+                assert_eq!(
+                    (expected.code(), expected.message()),
+                    (actual.code(), actual.message())
+                );
+            }
+        }
+        assert!(one.call(tonic::Request::new(())).is_ok());
+        assert!(single.call(tonic::Request::new(())).is_ok());
+        let snapshot = stats.snapshot();
+        assert_eq!(3, snapshot.calls);
+        assert_eq!(2, snapshot.errors.values().sum::<u64>());
+    }
+
+    /// Compares a chain of one from composite! with a list of one and a
+    /// SingleInterceptor; run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_single_interceptor() {
+        const CALLS: u32 = 100_000;
+        let key = || APIKeyClientInterceptor::try_new("key").unwrap();
+        let per_call = |interceptor: &mut dyn Interceptor| {
+            let started = std::time::Instant::now();
+            for _ in 0..CALLS {
+                interceptor.call(tonic::Request::new(())).unwrap();
+            }
+            started.elapsed() / CALLS
+        };
 
-        assert_eq!("test-token", test_object.token);
+        let many = per_call(&mut CompositeInterceptor::new(crate::interceptors!(key())));
+        let one = per_call(&mut crate::composite!(key()));
+        let single = per_call(&mut SingleInterceptor::new(key()));
+        println!("List of one {many:?}, composite! of one {one:?}, single {single:?} per call");
+        assert!(one < many, "composite! of one {one:?}, list {many:?}");
     }
 }
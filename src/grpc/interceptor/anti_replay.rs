@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tonic::metadata::{Ascii, MetadataKey};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::{metadata_key, unix_millis, Clock, OverridePolicy};
+
+/// The default header containing the nonce
+pub const X_NONCE: &str = "x-nonce";
+/// The default header containing the unix timestamp in milliseconds
+pub const X_TIMESTAMP: &str = "x-timestamp";
+
+/// A function returning 128 random bits, used to inject the nonce source
+pub type NonceSource = Arc<dyn Fn() -> [u8; 16] + Send + Sync>;
+
+/// The encoding of the nonce in the request header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonceEncoding {
+    /// Lower case hexadecimal, 32 characters
+    Hex,
+    /// Standard base64 with padding, 24 characters
+    Base64,
+}
+
+/// The values set by an [AntiReplayInterceptor] with signing enabled
+///
+/// They are inserted into the request extensions, so that a signing
+/// interceptor later in the chain includes them in its signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayHeaders {
+    /// The encoded nonce, as sent in the nonce header
+    pub nonce: String,
+    /// The timestamp in milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// An interceptor, that adds a unique nonce and a timestamp to every request
+///
+/// The server can use both values to reject replayed requests. Every call
+/// draws a fresh nonce from the nonce source, which clones of the
+/// interceptor share, so nonces are never reused across clones.
+#[derive(Clone)]
+pub struct AntiReplayInterceptor {
+    nonce_key: MetadataKey<Ascii>,
+    timestamp_key: MetadataKey<Ascii>,
+    encoding: NonceEncoding,
+    sign: bool,
+    clock: Clock,
+    nonce_source: NonceSource,
+    override_policy: OverridePolicy,
+}
+
+impl Default for AntiReplayInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AntiReplayInterceptor {
+    /// Creates a new interceptor with hex encoded nonces from a
+    /// cryptographically secure random number generator
+    pub fn new() -> Self {
+        Self {
+            nonce_key: MetadataKey::from_static(X_NONCE),
+            timestamp_key: MetadataKey::from_static(X_TIMESTAMP),
+            encoding: NonceEncoding::Hex,
+            sign: false,
+            clock: Arc::new(SystemTime::now),
+            nonce_source: Arc::new(rand::random),
+            override_policy: OverridePolicy::default(),
+        }
+    }
+
+    /// Overrides the name of the nonce header (default [X_NONCE])
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_nonce_header(mut self, header_name: &str) -> Result<Self, Status> {
+        self.nonce_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Overrides the name of the timestamp header (default [X_TIMESTAMP])
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_timestamp_header(mut self, header_name: &str) -> Result<Self, Status> {
+        self.timestamp_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Sets the encoding of the nonce
+    pub fn with_encoding(mut self, encoding: NonceEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Enables signing of the nonce and the timestamp
+    ///
+    /// When enabled, the values are also inserted as [ReplayHeaders] into the
+    /// request extensions, which the signing interceptors fold into their
+    /// canonical string. The signing interceptor must come after this one in
+    /// the chain. With signing enabled, the headers are always overwritten.
+    pub fn with_signing(mut self, sign: bool) -> Self {
+        self.sign = sign;
+        self
+    }
+
+    /// Replaces the clock used for the timestamps
+    pub fn with_clock(mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Replaces the source of the random nonce bytes
+    pub fn with_nonce_source(
+        mut self,
+        source: impl Fn() -> [u8; 16] + Send + Sync + 'static,
+    ) -> Self {
+        self.nonce_source = Arc::new(source);
+        self
+    }
+
+    /// Sets the policy for already present nonce and timestamp headers
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
+    }
+
+    fn encode(&self, nonce: [u8; 16]) -> String {
+        match self.encoding {
+            NonceEncoding::Hex => nonce.iter().map(|b| format!("{b:02x}")).collect(),
+            NonceEncoding::Base64 => STANDARD.encode(nonce),
+        }
+    }
+}
+
+impl Interceptor for AntiReplayInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let nonce = self.encode((self.nonce_source)());
+        let timestamp = unix_millis(&self.clock)?;
+
+        let nonce_value = nonce
+            .parse()
+            .map_err(|_| Status::invalid_argument("Error while setting additional metadata"))?;
+        // The signed values must be the ones that are sent
+        let override_policy = match self.sign {
+            true => OverridePolicy::Overwrite,
+            false => self.override_policy,
+        };
+        override_policy.apply(req.metadata_mut(), self.nonce_key.clone(), nonce_value);
+        override_policy.apply(
+            req.metadata_mut(),
+            self.timestamp_key.clone(),
+            timestamp.into(),
+        );
+
+        if self.sign {
+            req.extensions_mut()
+                .insert(ReplayHeaders { nonce, timestamp });
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tonic::service::Interceptor;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::anti_replay::{
+        AntiReplayInterceptor, NonceEncoding, ReplayHeaders, X_NONCE,
+    };
+
+    fn test_object() -> AntiReplayInterceptor {
+        let counter = AtomicU8::new(0);
+        AntiReplayInterceptor::new()
+            .with_clock(|| UNIX_EPOCH + Duration::from_millis(1_700_000_000_123))
+            .with_nonce_source(move || [counter.fetch_add(1, Ordering::SeqCst); 16])
+    }
+
+    fn header(req: &tonic::Request<()>, name: &str) -> String {
+        req.metadata()
+            .get(name)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_headers() {
+        let req = assert_sets_metadata!(test_object(), {
+            "x-nonce" => "00000000000000000000000000000000",
+            "x-timestamp" => "1700000000123",
+        });
+
+        assert!(req.extensions().get::<ReplayHeaders>().is_none());
+    }
+
+    #[test]
+    fn test_base64_and_header_names() {
+        let mut test_object = test_object()
+            .with_encoding(NonceEncoding::Base64)
+            .with_nonce_header("x-request-nonce")
+            .unwrap()
+            .with_timestamp_header("x-request-time")
+            .unwrap();
+        test_object.call(tonic::Request::new(())).unwrap();
+        let req = test_object.call(tonic::Request::new(())).unwrap();
+
+        assert_eq!("AQEBAQEBAQEBAQEBAQEBAQ==", header(&req, "x-request-nonce"));
+        assert_eq!("1700000000123", header(&req, "x-request-time"));
+    }
+
+    #[test]
+    fn test_unique_across_clones() {
+        let mut first = AntiReplayInterceptor::new();
+        let mut second = first.clone();
+
+        let nonces: HashSet<String> = (0..50)
+            .flat_map(|_| {
+                [
+                    header(&first.call(tonic::Request::new(())).unwrap(), X_NONCE),
+                    header(&second.call(tonic::Request::new(())).unwrap(), X_NONCE),
+                ]
+            })
+            .collect();
+        assert_eq!(100, nonces.len());
+
+        let mut shared = test_object();
+        let mut clone = shared.clone();
+        let a = header(&shared.call(tonic::Request::new(())).unwrap(), X_NONCE);
+        let b = header(&clone.call(tonic::Request::new(())).unwrap(), X_NONCE);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_signing_extension() {
+        let req = test_object()
+            .with_signing(true)
+            .call(tonic::Request::new(()))
+            .unwrap();
+
+        assert_eq!(
+            Some(&ReplayHeaders {
+                nonce: "00000000000000000000000000000000".to_string(),
+                timestamp: 1_700_000_000_123,
+            }),
+            req.extensions().get::<ReplayHeaders>()
+        );
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn test_signed_by_ed25519() {
+        use crate::grpc::interceptor::ed25519::{
+            verify, verify_with_nonce, Ed25519SigningInterceptor,
+        };
+
+        let mut signer =
+            Ed25519SigningInterceptor::from_bytes("key-1".to_string(), &[7; 32]).unwrap();
+        let public_key = signer.public_key();
+        let req = test_object()
+            .with_signing(true)
+            .call(tonic::Request::new(()))
+            .unwrap();
+        let req = signer.call(req).unwrap();
+
+        assert_eq!(
+            "key-1",
+            verify_with_nonce(req.metadata(), &public_key, X_NONCE).unwrap()
+        );
+        assert!(verify(req.metadata(), &public_key).is_err());
+    }
+}
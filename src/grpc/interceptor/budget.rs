@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::DescribableInterceptor;
+
+/// The size HPACK accounts for every header in addition to its name and
+/// value (RFC 7541, section 4.1)
+pub const ENTRY_OVERHEAD: usize = 32;
+
+/// The number of keys named in the error
+const REPORTED_KEYS: usize = 3;
+
+/// Returns the encoded size of the metadata per key, largest first
+fn sizes_by_key(metadata: &MetadataMap) -> Vec<(&str, usize)> {
+    let mut sizes: HashMap<&str, usize> = HashMap::new();
+    for entry in metadata.iter() {
+        let (key, size) = match entry {
+            KeyAndValueRef::Ascii(key, value) => (key.as_str(), value.len()),
+            // Binary values are stored base64 encoded, like they are sent
+            KeyAndValueRef::Binary(key, value) => (key.as_str(), value.as_encoded_bytes().len()),
+        };
+        *sizes.entry(key).or_default() += key.len() + size + ENTRY_OVERHEAD;
+    }
+    let mut sizes: Vec<_> = sizes.into_iter().collect();
+    sizes.sort_by(|(a_key, a_size), (b_key, b_size)| b_size.cmp(a_size).then(a_key.cmp(b_key)));
+    sizes
+}
+
+/// Returns the approximate size of the metadata in a header block
+///
+/// Every entry counts with its key, its encoded value and
+/// [ENTRY_OVERHEAD]. Compression by HPACK is not taken into account.
+pub fn metadata_size(metadata: &MetadataMap) -> usize {
+    sizes_by_key(metadata).iter().map(|(_, size)| size).sum()
+}
+
+/// An interceptor, that fails calls whose metadata exceeds a size budget
+///
+/// Proxies reject too large header blocks, e.g. with HTTP 431, which
+/// surfaces as a transport error without any hint at the cause. This
+/// interceptor fails such calls before they are sent, with
+/// [Status::invalid_argument] naming the largest keys. Put it last in the
+/// chain, so it sees the metadata of all other interceptors. In warn-only
+/// mode the call is logged and sent anyway.
+#[derive(Clone, Debug)]
+pub struct MetadataBudgetInterceptor {
+    budget: usize,
+    warn_only: bool,
+}
+
+impl MetadataBudgetInterceptor {
+    /// Creates a new interceptor
+    /// # Arguments
+    /// * `budget`: The maximum size of the metadata in bytes, see
+    ///   [metadata_size]
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            warn_only: false,
+        }
+    }
+
+    /// Only logs a warning, when the budget is exceeded
+    pub fn with_warn_only(mut self) -> Self {
+        self.warn_only = true;
+        self
+    }
+}
+
+impl Interceptor for MetadataBudgetInterceptor {
+    fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let sizes = sizes_by_key(req.metadata());
+        let size: usize = sizes.iter().map(|(_, size)| size).sum();
+        if size <= self.budget {
+            return Ok(req);
+        }
+
+        let largest: Vec<_> = sizes
+            .iter()
+            .take(REPORTED_KEYS)
+            .map(|(key, size)| format!("{key} ({size} bytes)"))
+            .collect();
+        let message = format!(
+            "Metadata of {size} bytes exceeds the budget of {} bytes, largest keys: {}",
+            self.budget,
+            largest.join(", ")
+        );
+        if self.warn_only {
+            log::warn!("{message}");
+            return Ok(req);
+        }
+        Err(Status::invalid_argument(message))
+    }
+}
+
+impl DescribableInterceptor for MetadataBudgetInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "budget {} bytes, warn only {}",
+            self.budget, self.warn_only
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::metadata::MetadataValue;
+    use tonic::service::Interceptor;
+    use tonic::Code;
+
+    use crate::grpc::interceptor::budget::{
+        metadata_size, MetadataBudgetInterceptor, ENTRY_OVERHEAD,
+    };
+    use crate::grpc::interceptor::{APIKeyClientInterceptor, CompositeInterceptor};
+    use crate::interceptors;
+
+    fn request() -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("x-small", "value".parse().unwrap());
+        req.metadata_mut()
+            .insert_bin("x-blob-bin", MetadataValue::from_bytes(&[0u8; 30]));
+        req
+    }
+
+    /// The size of the request with an API key of the length
+    fn size_with_key(length: usize) -> usize {
+        metadata_size(request().metadata()) + "x-api-key".len() + length + ENTRY_OVERHEAD
+    }
+
+    fn chain(api_key_length: usize, budget: usize) -> CompositeInterceptor {
+        CompositeInterceptor::new(interceptors!(
+            APIKeyClientInterceptor::try_new(&"k".repeat(api_key_length)).unwrap(),
+            MetadataBudgetInterceptor::new(budget)
+        ))
+    }
+
+    #[test]
+    fn test_size() {
+        // 30 bytes are 40 characters of base64
+        assert_eq!(
+            "x-small".len() + 5 + "x-blob-bin".len() + 40 + 2 * ENTRY_OVERHEAD,
+            metadata_size(request().metadata())
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = size_with_key(1000);
+
+        assert!(chain(1000, budget).call(request()).is_ok());
+
+        let status = chain(1001, budget).call(request()).unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+        assert!(status
+            .message()
+            .contains("largest keys: x-api-key (1042 bytes), x-blob-bin (82 bytes)"));
+    }
+
+    #[test]
+    fn test_warn_only() {
+        let mut test_object = MetadataBudgetInterceptor::new(10).with_warn_only();
+
+        assert!(test_object.call(request()).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+use tonic::metadata::{Ascii, AsciiMetadataValue, MetadataKey};
+use tonic::service::Interceptor;
+
+use crate::grpc::interceptor::OverridePolicy;
+
+/// The default header for the hostname of the client
+pub const X_CLIENT_HOST: &str = "x-client-host";
+/// The default header for the process id of the client
+pub const X_CLIENT_PID: &str = "x-client-pid";
+/// The default header for the application name of the client
+pub const X_CLIENT_APP: &str = "x-client-app";
+
+/// Replaces all characters, that are not allowed in a metadata value
+fn sanitize(value: &str) -> AsciiMetadataValue {
+    let sanitized: String = value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => c,
+            _ => '_',
+        })
+        .collect();
+    AsciiMetadataValue::try_from(sanitized).unwrap_or_else(|_| AsciiMetadataValue::from_static(""))
+}
+
+#[derive(Clone)]
+struct Field {
+    key: MetadataKey<Ascii>,
+    value: AsciiMetadataValue,
+    enabled: bool,
+}
+
+impl Field {
+    fn new(key: &'static str, value: &str) -> Self {
+        Self {
+            key: MetadataKey::from_static(key),
+            value: sanitize(value),
+            enabled: true,
+        }
+    }
+
+    fn rename(&mut self, header_name: &str) {
+        match MetadataKey::<Ascii>::from_bytes(header_name.as_bytes()) {
+            Ok(key) => self.key = key,
+            Err(e) => log::error!("Invalid meta data key: {e}"),
+        }
+    }
+}
+
+/// An interceptor, that identifies the sending process
+///
+/// It adds the hostname, the process id and the application name as
+/// `x-client-host`, `x-client-pid` and `x-client-app`. The hostname is
+/// determined once, when the interceptor is created. Characters, that are
+/// not allowed in metadata, are replaced by `_`, so the values never fail a
+/// call.
+#[derive(Clone)]
+pub struct ClientInfoInterceptor {
+    host: Field,
+    pid: Field,
+    app: Field,
+    override_policy: OverridePolicy,
+}
+
+impl ClientInfoInterceptor {
+    /// Creates a new interceptor sending all fields
+    /// # Arguments
+    /// * `app_name`: The name of the application
+    pub fn new(app_name: &str) -> Self {
+        let host = gethostname::gethostname();
+        Self {
+            host: Field::new(X_CLIENT_HOST, &host.to_string_lossy()),
+            pid: Field::new(X_CLIENT_PID, &std::process::id().to_string()),
+            app: Field::new(X_CLIENT_APP, app_name),
+            override_policy: OverridePolicy::default(),
+        }
+    }
+
+    /// Sets the header name for the hostname
+    pub fn with_host_header(mut self, header_name: &str) -> Self {
+        self.host.rename(header_name);
+        self
+    }
+
+    /// Sets the header name for the process id
+    pub fn with_pid_header(mut self, header_name: &str) -> Self {
+        self.pid.rename(header_name);
+        self
+    }
+
+    /// Sets the header name for the application name
+    pub fn with_app_header(mut self, header_name: &str) -> Self {
+        self.app.rename(header_name);
+        self
+    }
+
+    /// Does not send the hostname
+    pub fn without_host(mut self) -> Self {
+        self.host.enabled = false;
+        self
+    }
+
+    /// Does not send the process id
+    pub fn without_pid(mut self) -> Self {
+        self.pid.enabled = false;
+        self
+    }
+
+    /// Does not send the application name
+    pub fn without_app(mut self) -> Self {
+        self.app.enabled = false;
+        self
+    }
+
+    /// Sets the policy for already present headers
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
+    }
+}
+
+impl Interceptor for ClientInfoInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        for field in [&self.host, &self.pid, &self.app] {
+            if field.enabled {
+                self.override_policy.apply(
+                    req.metadata_mut(),
+                    field.key.clone(),
+                    field.value.clone(),
+                );
+            }
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::service::Interceptor;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::client_info::{sanitize, ClientInfoInterceptor};
+
+    #[test]
+    fn test_all_headers() {
+        let host = gethostname::gethostname();
+        assert_sets_metadata!(ClientInfoInterceptor::new("billing-worker"), {
+            "x-client-host" => host.to_string_lossy().trim(),
+            "x-client-pid" => &std::process::id().to_string(),
+            "x-client-app" => "billing-worker",
+        });
+    }
+
+    #[test]
+    fn test_renamed_and_suppressed() {
+        let req = ClientInfoInterceptor::new("billing-worker")
+            .without_host()
+            .with_app_header("x-service")
+            .call(tonic::Request::new(()))
+            .unwrap();
+
+        assert!(req.metadata().get("x-client-host").is_none());
+        assert!(req.metadata().get("x-client-app").is_none());
+        assert_eq!("billing-worker", req.metadata().get("x-service").unwrap());
+        assert!(req.metadata().get("x-client-pid").is_some());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!("Zu_rich_app", sanitize(" Zu\u{308}rich\napp "));
+        assert_eq!("", sanitize(""));
+    }
+}
@@ -0,0 +1,157 @@
+use std::future::Future;
+
+use tokio::task::futures::TaskLocalFuture;
+use tonic::metadata::MetadataMap;
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::extension::ExtraMetadata;
+use crate::grpc::interceptor::OverridePolicy;
+
+tokio::task_local! {
+    static CONTEXT: MetadataMap;
+}
+
+/// Metadata for all calls made within a scope of a task
+///
+/// Code deep inside a handler can set headers for all downstream calls,
+/// without passing them through every function. The
+/// [ContextMetadataInterceptor] adds them to the outgoing metadata:
+/// ```ignore
+/// MetadataContext::scope([("x-on-behalf-of", "user-123")], async {
+///     client.get_order(request).await
+/// })?
+/// .await
+/// ```
+/// As it is task-local, futures passed to `tokio::spawn` do not inherit
+/// the metadata; wrap them in their own scope.
+pub struct MetadataContext;
+
+impl MetadataContext {
+    /// Runs the future with the metadata added to the current one
+    ///
+    /// In nested scopes the inner value of a key replaces the outer ones.
+    /// Fails with [Status::invalid_argument] for invalid keys or values,
+    /// see [ExtraMetadata::from_pairs].
+    /// # Arguments
+    /// * `pairs`: The keys and values
+    /// * `future`: The future making the calls
+    pub fn scope<'a, F: Future>(
+        pairs: impl IntoIterator<Item = (&'a str, &'a str)>,
+        future: F,
+    ) -> Result<TaskLocalFuture<MetadataMap, F>, Status> {
+        let ExtraMetadata(inner) = ExtraMetadata::from_pairs(pairs)?;
+        let mut metadata = Self::current().unwrap_or_default();
+        OverridePolicy::Overwrite.merge(&mut metadata, &inner);
+        Ok(CONTEXT.scope(metadata, future))
+    }
+
+    /// Returns the metadata of the current scope, if there is one
+    pub fn current() -> Option<MetadataMap> {
+        CONTEXT.try_with(|metadata| metadata.clone()).ok()
+    }
+}
+
+/// An interceptor, that adds the metadata of the current [MetadataContext]
+/// to the outgoing metadata
+///
+/// Calls outside of a scope are not changed.
+#[derive(Clone, Default)]
+pub struct ContextMetadataInterceptor {
+    override_policy: OverridePolicy,
+}
+
+impl ContextMetadataInterceptor {
+    /// Creates a new interceptor, that overwrites already present headers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the policy for already present headers
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
+    }
+}
+
+impl Interceptor for ContextMetadataInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let _ =
+            CONTEXT.try_with(|metadata| self.override_policy.merge(req.metadata_mut(), metadata));
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::service::Interceptor;
+
+    use crate::grpc::interceptor::context::{ContextMetadataInterceptor, MetadataContext};
+    use crate::grpc::interceptor::OverridePolicy;
+
+    fn header(name: &str) -> Option<String> {
+        let req = ContextMetadataInterceptor::new()
+            .call(tonic::Request::new(()))
+            .unwrap();
+        req.metadata()
+            .get(name)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_nested_scopes() {
+        MetadataContext::scope(
+            [("x-on-behalf-of", "user-123"), ("x-tenant", "acme")],
+            async {
+                MetadataContext::scope([("x-on-behalf-of", "user-456")], async {
+                    assert_eq!(Some(String::from("user-456")), header("x-on-behalf-of"));
+                    assert_eq!(Some(String::from("acme")), header("x-tenant"));
+                })
+                .unwrap()
+                .await;
+
+                assert_eq!(Some(String::from("user-123")), header("x-on-behalf-of"));
+            },
+        )
+        .unwrap()
+        .await;
+
+        assert_eq!(None, header("x-on-behalf-of"));
+        assert!(MetadataContext::current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_scopes() {
+        let task = |user: &'static str| {
+            MetadataContext::scope([("x-on-behalf-of", user)], async move {
+                for _ in 0..10 {
+                    tokio::task::yield_now().await;
+                    assert_eq!(Some(String::from(user)), header("x-on-behalf-of"));
+                }
+            })
+            .unwrap()
+        };
+
+        let (a, b) = tokio::join!(tokio::spawn(task("user-a")), tokio::spawn(task("user-b")));
+        a.unwrap();
+        b.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_override_policy() {
+        MetadataContext::scope([("x-on-behalf-of", "user-123")], async {
+            let mut req = tonic::Request::new(());
+            req.metadata_mut()
+                .insert("x-on-behalf-of", "explicit".parse().unwrap());
+
+            let req = ContextMetadataInterceptor::new()
+                .with_override_policy(OverridePolicy::SkipIfPresent)
+                .call(req)
+                .unwrap();
+            assert_eq!("explicit", req.metadata().get("x-on-behalf-of").unwrap());
+        })
+        .unwrap()
+        .await;
+
+        assert!(MetadataContext::scope([("invalid key", "value")], async {}).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::layer::deadline::{parse_grpc_timeout, DeadlineContext, GRPC_TIMEOUT};
+
+/// The default time kept back for the work after the downstream call
+pub const DEFAULT_SAFETY_MARGIN: Duration = Duration::from_millis(10);
+
+/// An interceptor, that propagates the deadline of the current call
+///
+/// The deadline is taken from a [DeadlineContext] in the request extensions,
+/// or else from the current task, as set by the
+/// [DeadlineLayer](crate::grpc::layer::deadline::DeadlineLayer). The remaining
+/// time minus the safety margin is sent as `grpc-timeout`, unless the request
+/// already has a shorter timeout. When no time remains, the call is rejected
+/// with [Status::deadline_exceeded] without being sent. Requests without a
+/// deadline are passed unchanged.
+#[derive(Clone)]
+pub struct DeadlinePropagationInterceptor {
+    safety_margin: Duration,
+}
+
+impl Default for DeadlinePropagationInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadlinePropagationInterceptor {
+    /// Creates a new interceptor with the [DEFAULT_SAFETY_MARGIN]
+    pub fn new() -> Self {
+        Self {
+            safety_margin: DEFAULT_SAFETY_MARGIN,
+        }
+    }
+
+    /// Sets the time, that is subtracted from the remaining time
+    pub fn with_safety_margin(mut self, safety_margin: Duration) -> Self {
+        self.safety_margin = safety_margin;
+        self
+    }
+}
+
+impl Interceptor for DeadlinePropagationInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let Some(context) = req
+            .extensions()
+            .get::<DeadlineContext>()
+            .copied()
+            .or_else(DeadlineContext::current)
+        else {
+            return Ok(req);
+        };
+
+        let remaining = context.remaining().saturating_sub(self.safety_margin);
+        if remaining.is_zero() {
+            return Err(Status::deadline_exceeded(
+                "No time left for the call before the deadline",
+            ));
+        }
+
+        let present = req
+            .metadata()
+            .get(GRPC_TIMEOUT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_grpc_timeout);
+        if present.is_none_or(|present| remaining < present) {
+            req.set_timeout(remaining);
+        }
+        Ok(req)
+    }
+}
+
+/// The point in time, when a queued call must be completed
+///
+/// Insert it into the extensions of the request, when the work is queued;
+/// the [DeadlineFromExtensionInterceptor] turns it into the `grpc-timeout`
+/// when the call is actually sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbsoluteDeadline(pub Instant);
+
+/// A function returning the current instant, used to inject clocks in tests
+pub type InstantClock = Arc<dyn Fn() -> Instant + Send + Sync>;
+
+/// An interceptor, that computes the `grpc-timeout` from an
+/// [AbsoluteDeadline] in the request extensions at send time
+///
+/// The absolute deadline is authoritative, so an existing `grpc-timeout` is
+/// replaced. When the deadline has already passed, the call is rejected
+/// with [Status::deadline_exceeded] without being sent. Requests without
+/// the extension are passed unchanged.
+#[derive(Clone)]
+pub struct DeadlineFromExtensionInterceptor {
+    clock: InstantClock,
+}
+
+impl Default for DeadlineFromExtensionInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadlineFromExtensionInterceptor {
+    /// Creates a new interceptor using the system clock
+    pub fn new() -> Self {
+        Self {
+            clock: Arc::new(Instant::now),
+        }
+    }
+
+    /// Replaces the clock, e.g. in tests
+    pub fn with_clock(mut self, clock: InstantClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Interceptor for DeadlineFromExtensionInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let Some(AbsoluteDeadline(deadline)) = req.extensions().get::<AbsoluteDeadline>().copied()
+        else {
+            return Ok(req);
+        };
+
+        let remaining = deadline.saturating_duration_since((self.clock)());
+        if remaining.is_zero() {
+            return Err(Status::deadline_exceeded(
+                "The deadline passed before the call was sent",
+            ));
+        }
+        req.set_timeout(remaining);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use tonic::codegen::http;
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::service::Interceptor;
+    use tonic::Code;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::interceptor::deadline::{
+        AbsoluteDeadline, DeadlineFromExtensionInterceptor, DeadlinePropagationInterceptor,
+    };
+    use crate::grpc::layer::deadline::{parse_grpc_timeout, DeadlineContext, DeadlineLayer};
+
+    fn timeout(req: &tonic::Request<()>) -> Option<Duration> {
+        parse_grpc_timeout(req.metadata().get("grpc-timeout")?.to_str().ok()?)
+    }
+
+    #[test]
+    fn test_without_deadline() {
+        let req = DeadlinePropagationInterceptor::new()
+            .call(tonic::Request::new(()))
+            .unwrap();
+        assert_eq!(None, timeout(&req));
+    }
+
+    #[test]
+    fn test_extension_and_shorter_timeout() {
+        let mut interceptor =
+            DeadlinePropagationInterceptor::new().with_safety_margin(Duration::from_millis(100));
+
+        let mut req = tonic::Request::new(());
+        req.extensions_mut()
+            .insert(DeadlineContext::from_timeout(Duration::from_secs(10)));
+        let timeout_sent = timeout(&interceptor.call(req).unwrap()).unwrap();
+        assert!(timeout_sent <= Duration::from_millis(9900));
+        assert!(timeout_sent > Duration::from_secs(9));
+
+        // A shorter timeout of the caller is kept
+        let mut req = tonic::Request::new(());
+        req.set_timeout(Duration::from_secs(1));
+        req.extensions_mut()
+            .insert(DeadlineContext::from_timeout(Duration::from_secs(10)));
+        let req = interceptor.call(req).unwrap();
+        assert_eq!(Some(Duration::from_secs(1)), timeout(&req));
+    }
+
+    #[test]
+    fn test_no_budget_left() {
+        let mut req = tonic::Request::new(());
+        req.extensions_mut()
+            .insert(DeadlineContext::from_timeout(Duration::from_millis(5)));
+        let status = DeadlinePropagationInterceptor::new().call(req).unwrap_err();
+        assert_eq!(Code::DeadlineExceeded, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_server_to_server() {
+        let headers = Arc::new(Mutex::new(http::HeaderMap::new()));
+        let captured = headers.clone();
+        let downstream = tower::service_fn(move |req: http::Request<tonic::body::BoxBody>| {
+            *captured.lock().unwrap() = req.headers().clone();
+            async {
+                Ok::<_, Infallible>(
+                    http::Response::builder()
+                        .header("grpc-status", "12")
+                        .body(tonic::body::empty_body())
+                        .unwrap(),
+                )
+            }
+        });
+
+        // The handler of the server calls the downstream service
+        let mut server =
+            DeadlineLayer::new().layer(tower::service_fn(move |_req: http::Request<()>| {
+                let downstream = downstream.clone();
+                async move {
+                    let mut client = tonic::client::Grpc::new(InterceptedService::new(
+                        downstream,
+                        DeadlinePropagationInterceptor::new(),
+                    ));
+                    client.ready().await.unwrap();
+                    let _ = client
+                        .unary(
+                            tonic::Request::new(()),
+                            http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                            tonic::codec::ProstCodec::<(), ()>::default(),
+                        )
+                        .await;
+                    Ok::<_, Infallible>(())
+                }
+            }));
+
+        let req = http::Request::builder()
+            .header("grpc-timeout", "1S")
+            .body(())
+            .unwrap();
+        server.ready().await.unwrap().call(req).await.unwrap();
+
+        let headers = headers.lock().unwrap();
+        let downstream_timeout = headers
+            .get("grpc-timeout")
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_grpc_timeout)
+            .unwrap();
+        assert!(downstream_timeout < Duration::from_secs(1));
+    }
+
+    /// Queues a request with a deadline `deadline_in` from now and sends it
+    /// `sent_after` later
+    fn send_queued(deadline_in: Duration, sent_after: Duration) -> Result<String, Code> {
+        let enqueued = Instant::now();
+        let mut req = tonic::Request::new(());
+        req.set_timeout(Duration::from_secs(1));
+        req.extensions_mut()
+            .insert(AbsoluteDeadline(enqueued + deadline_in));
+
+        DeadlineFromExtensionInterceptor::new()
+            .with_clock(Arc::new(move || enqueued + sent_after))
+            .call(req)
+            .map(|req| {
+                let timeout = req.metadata().get("grpc-timeout").unwrap();
+                timeout.to_str().unwrap().to_string()
+            })
+            .map_err(|status| status.code())
+    }
+
+    #[test]
+    fn test_deadline_from_extension() {
+        let expired = Err(Code::DeadlineExceeded);
+        assert_eq!(
+            expired,
+            send_queued(Duration::from_secs(1), Duration::from_secs(2))
+        );
+        assert_eq!(
+            expired,
+            send_queued(Duration::from_secs(1), Duration::from_secs(1))
+        );
+
+        // The unit is chosen, so that the value has at most 8 digits
+        let nearly_expired = send_queued(Duration::from_millis(1001), Duration::from_secs(1));
+        assert_eq!(Ok(String::from("1000000n")), nearly_expired);
+        let comfortable = send_queued(Duration::from_secs(30), Duration::from_secs(5));
+        assert_eq!(Ok(String::from("25000000u")), comfortable);
+        let long = send_queued(Duration::from_secs(7200), Duration::ZERO);
+        assert_eq!(Ok(String::from("7200000m")), long);
+
+        // Without the extension the timeout is kept
+        let mut req = tonic::Request::new(());
+        req.set_timeout(Duration::from_secs(1));
+        let req = DeadlineFromExtensionInterceptor::new().call(req).unwrap();
+        assert_eq!(Some(Duration::from_secs(1)), timeout(&req));
+    }
+}
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{
+    pkcs8::DecodePrivateKey, Signature, Signer, SigningKey, Verifier, VerifyingKey,
+};
+use tonic::{
+    metadata::{AsciiMetadataValue, MetadataMap},
+    service::Interceptor,
+    Status,
+};
+
+use crate::grpc::interceptor::{anti_replay::ReplayHeaders, unix_millis, Clock};
+
+/// The header containing the id of the signing key
+pub const X_KEY_ID: &str = "x-key-id";
+/// The header containing the unix timestamp in milliseconds
+pub const X_TIMESTAMP: &str = "x-timestamp";
+/// The header containing the base64 encoded signature
+pub const X_SIGNATURE: &str = "x-signature";
+
+/// Builds the canonical string, that is signed for every request
+///
+/// The format is stable: the timestamp (unix epoch milliseconds, as sent in
+/// the [X_TIMESTAMP] header) and the key id, separated by a line feed. When
+/// the nonce of an [AntiReplayInterceptor](super::anti_replay::AntiReplayInterceptor)
+/// is signed as well, it is appended as a third line:
+/// ```text
+/// <timestamp>\n<key id>[\n<nonce>]
+/// ```
+pub fn canonical_string(timestamp: u64, key_id: &str, nonce: Option<&str>) -> String {
+    match nonce {
+        Some(nonce) => format!("{timestamp}\n{key_id}\n{nonce}"),
+        None => format!("{timestamp}\n{key_id}"),
+    }
+}
+
+/// An interceptor, that signs every request with an Ed25519 private key
+///
+/// It inserts the [X_KEY_ID], [X_TIMESTAMP] and [X_SIGNATURE] headers. The
+/// signature is computed over the [canonical_string]. If an earlier
+/// interceptor in the chain added [ReplayHeaders] to the request extensions,
+/// their timestamp and nonce are signed.
+#[derive(Clone)]
+pub struct Ed25519SigningInterceptor {
+    key_id: String,
+    key_id_value: AsciiMetadataValue,
+    signing_key: SigningKey,
+    clock: Clock,
+}
+
+impl Ed25519SigningInterceptor {
+    /// Creates a new interceptor from a PKCS#8 PEM encoded private key
+    /// # Arguments
+    /// * `key_id`: The id of the key, as registered on the server
+    /// * `pem`: The PEM encoded private key
+    pub fn from_pem(key_id: String, pem: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let signing_key = SigningKey::from_pkcs8_pem(pem)?;
+        Self::from_signing_key(key_id, signing_key)
+    }
+
+    /// Creates a new interceptor from the raw 32 bytes of a private key
+    /// # Arguments
+    /// * `key_id`: The id of the key, as registered on the server
+    /// * `bytes`: The raw private key
+    pub fn from_bytes(key_id: String, bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let secret: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| format!("Invalid Ed25519 key length {}, expected 32", bytes.len()))?;
+        Self::from_signing_key(key_id, SigningKey::from_bytes(&secret))
+    }
+
+    fn from_signing_key(
+        key_id: String,
+        signing_key: SigningKey,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let key_id_value = AsciiMetadataValue::try_from(key_id.as_str())?;
+        Ok(Self {
+            key_id,
+            key_id_value,
+            signing_key,
+            clock: Arc::new(SystemTime::now),
+        })
+    }
+
+    /// Replaces the clock used for the timestamps
+    /// # Arguments
+    /// * `clock`: A function returning the current time
+    pub fn with_clock(mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the public key matching the private key of this interceptor
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+}
+
+impl Interceptor for Ed25519SigningInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let (timestamp, nonce) = match req.extensions().get::<ReplayHeaders>() {
+            Some(replay) => (replay.timestamp, Some(replay.nonce.as_str())),
+            None => (unix_millis(&self.clock)?, None),
+        };
+        let signature = self
+            .signing_key
+            .sign(canonical_string(timestamp, &self.key_id, nonce).as_bytes());
+
+        let invalid = |_| Status::invalid_argument("Error while setting signature metadata");
+        let metadata = req.metadata_mut();
+        metadata.insert(X_KEY_ID, self.key_id_value.clone());
+        metadata.insert(X_TIMESTAMP, timestamp.into());
+        metadata.insert(
+            X_SIGNATURE,
+            STANDARD
+                .encode(signature.to_bytes())
+                .parse()
+                .map_err(invalid)?,
+        );
+        Ok(req)
+    }
+}
+
+/// Verifies the signature headers set by an [Ed25519SigningInterceptor]
+///
+/// Returns the key id on success, or [Status::unauthenticated] if a header is
+/// missing or the signature does not match
+/// # Arguments
+/// * `metadata`: The metadata of the received request
+/// * `public_key`: The public key registered for the key id
+pub fn verify(metadata: &MetadataMap, public_key: &[u8; 32]) -> Result<String, Status> {
+    verify_signature(metadata, public_key, None)
+}
+
+/// Verifies the signature headers, including the signed nonce
+///
+/// Use this instead of [verify], when the client signs the nonce of an
+/// [AntiReplayInterceptor](super::anti_replay::AntiReplayInterceptor)
+/// # Arguments
+/// * `metadata`: The metadata of the received request
+/// * `public_key`: The public key registered for the key id
+/// * `nonce_header`: The name of the header containing the nonce
+pub fn verify_with_nonce(
+    metadata: &MetadataMap,
+    public_key: &[u8; 32],
+    nonce_header: &str,
+) -> Result<String, Status> {
+    verify_signature(metadata, public_key, Some(nonce_header))
+}
+
+fn verify_signature(
+    metadata: &MetadataMap,
+    public_key: &[u8; 32],
+    nonce_header: Option<&str>,
+) -> Result<String, Status> {
+    let header = |name: &str| {
+        metadata
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated(format!("Missing header {name}")))
+    };
+    let key_id = header(X_KEY_ID)?;
+    let timestamp: u64 = header(X_TIMESTAMP)?
+        .parse()
+        .map_err(|_| Status::unauthenticated("Invalid timestamp"))?;
+    let signature = STANDARD
+        .decode(header(X_SIGNATURE)?)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or_else(|| Status::unauthenticated("Invalid signature"))?;
+    let nonce = nonce_header.map(header).transpose()?;
+
+    let verifying_key =
+        VerifyingKey::from_bytes(public_key).map_err(|_| Status::internal("Invalid public key"))?;
+    verifying_key
+        .verify(
+            canonical_string(timestamp, key_id, nonce).as_bytes(),
+            &signature,
+        )
+        .map_err(|_| Status::unauthenticated("Signature mismatch"))?;
+    Ok(key_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use ed25519_dalek::{pkcs8::EncodePrivateKey, SigningKey};
+    use tonic::{service::Interceptor, Code};
+
+    use crate::grpc::interceptor::ed25519::{
+        canonical_string, verify, Ed25519SigningInterceptor, X_KEY_ID, X_SIGNATURE, X_TIMESTAMP,
+    };
+
+    const SECRET: [u8; 32] = [7; 32];
+
+    fn test_object() -> Ed25519SigningInterceptor {
+        Ed25519SigningInterceptor::from_bytes("key-1".to_string(), &SECRET)
+            .unwrap()
+            .with_clock(|| UNIX_EPOCH + Duration::from_millis(1_700_000_000_123))
+    }
+
+    #[test]
+    fn test_canonical_string() {
+        assert_eq!(
+            "1700000000123\nkey-1",
+            canonical_string(1_700_000_000_123, "key-1", None)
+        );
+        assert_eq!(
+            "1700000000123\nkey-1\nabc",
+            canonical_string(1_700_000_000_123, "key-1", Some("abc"))
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        let req = test_object().call(tonic::Request::new(())).unwrap();
+
+        assert_eq!("key-1", req.metadata().get(X_KEY_ID).unwrap());
+        assert_eq!("1700000000123", req.metadata().get(X_TIMESTAMP).unwrap());
+        assert!(req.metadata().get(X_SIGNATURE).is_some());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut test_object = test_object();
+        let public_key = test_object.public_key();
+        let req = test_object.call(tonic::Request::new(())).unwrap();
+
+        assert_eq!("key-1", verify(req.metadata(), &public_key).unwrap());
+    }
+
+    #[test]
+    fn test_tampered_timestamp() {
+        let mut test_object = test_object();
+        let public_key = test_object.public_key();
+        let mut req = test_object.call(tonic::Request::new(())).unwrap();
+        req.metadata_mut()
+            .insert(X_TIMESTAMP, "1700000000124".parse().unwrap());
+
+        let status = verify(req.metadata(), &public_key).unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+    }
+
+    #[test]
+    fn test_from_pem() {
+        let pem = SigningKey::from_bytes(&SECRET)
+            .to_pkcs8_pem(Default::default())
+            .unwrap();
+        let test_object = Ed25519SigningInterceptor::from_pem("key-1".to_string(), &pem).unwrap();
+
+        assert_eq!(test_object.public_key(), self::test_object().public_key());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(Ed25519SigningInterceptor::from_bytes("key-1".to_string(), &[1, 2, 3]).is_err());
+        assert!(Ed25519SigningInterceptor::from_pem("key-1".to_string(), "no pem").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use tonic::metadata::{
+    AsciiMetadataKey, AsciiMetadataValue, BinaryMetadataKey, BinaryMetadataValue, MetadataMap,
+};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::OverridePolicy;
+
+/// Call specific metadata, that is added by an [ExtensionMetadataInterceptor]
+///
+/// Insert it into the extensions of the typed request at the call site:
+/// ```
+/// use grpc_utils_rs::grpc::interceptor::extension::ExtraMetadata;
+///
+/// let mut request = tonic::Request::new(());
+/// request
+///     .extensions_mut()
+///     .insert(ExtraMetadata::from_pairs([("x-region", "eu-west-1")]).unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ExtraMetadata(pub MetadataMap);
+
+impl ExtraMetadata {
+    /// Creates the metadata from key value pairs
+    ///
+    /// Keys ending with `-bin` are binary keys, their values are taken as raw
+    /// bytes.
+    /// # Arguments
+    /// * `pairs`: The keys and values
+    pub fn from_pairs<'a>(
+        pairs: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, Status> {
+        let invalid_key = |e| Status::invalid_argument(format!("Invalid meta data key: {e}"));
+        let mut metadata = MetadataMap::new();
+        for (key, value) in pairs {
+            if key.ends_with("-bin") {
+                metadata.append_bin(
+                    BinaryMetadataKey::from_bytes(key.as_bytes()).map_err(invalid_key)?,
+                    BinaryMetadataValue::from_bytes(value.as_bytes()),
+                );
+            } else {
+                metadata.append(
+                    AsciiMetadataKey::from_bytes(key.as_bytes()).map_err(invalid_key)?,
+                    AsciiMetadataValue::try_from(value).map_err(|_| {
+                        Status::invalid_argument(format!("Invalid meta data value for {key}"))
+                    })?,
+                );
+            }
+        }
+        Ok(Self(metadata))
+    }
+}
+
+/// An interceptor, that copies [ExtraMetadata] from the request extensions
+/// into the outgoing metadata
+///
+/// Extensions set on the typed `tonic::Request<T>` at the call site are
+/// carried over to the `tonic::Request<()>` every interceptor sees, so the
+/// caller can decide per request on headers, while the interceptor chain
+/// stays fixed. The extension is removed after it was copied.
+#[derive(Clone, Default)]
+pub struct ExtensionMetadataInterceptor {
+    override_policy: OverridePolicy,
+}
+
+impl ExtensionMetadataInterceptor {
+    /// Creates a new interceptor, that overwrites already present headers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the policy for already present headers
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
+    }
+}
+
+impl Interceptor for ExtensionMetadataInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(ExtraMetadata(extra)) = req.extensions_mut().remove::<ExtraMetadata>() {
+            self.override_policy.merge(req.metadata_mut(), &extra);
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use tonic::codegen::http;
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::service::Interceptor;
+
+    use crate::grpc::interceptor::extension::{ExtensionMetadataInterceptor, ExtraMetadata};
+    use crate::grpc::interceptor::OverridePolicy;
+
+    fn request_with_extra(pairs: &[(&str, &str)]) -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("x-region", "us-east-1".parse().unwrap());
+        req.extensions_mut()
+            .insert(ExtraMetadata::from_pairs(pairs.iter().copied()).unwrap());
+        req
+    }
+
+    #[test]
+    fn test_copies_and_removes_extension() {
+        let req = ExtensionMetadataInterceptor::new()
+            .call(request_with_extra(&[
+                ("x-region", "eu-west-1"),
+                ("x-feature", "a"),
+                ("x-feature", "b"),
+                ("x-trace-bin", "\u{1}\u{2}"),
+            ]))
+            .unwrap();
+
+        assert_eq!("eu-west-1", req.metadata().get("x-region").unwrap());
+        let features: Vec<_> = req.metadata().get_all("x-feature").iter().collect();
+        assert_eq!(vec!["a", "b"], features);
+        assert_eq!(
+            vec![1u8, 2],
+            req.metadata()
+                .get_bin("x-trace-bin")
+                .unwrap()
+                .to_bytes()
+                .unwrap()
+        );
+        assert!(req.extensions().get::<ExtraMetadata>().is_none());
+    }
+
+    #[test]
+    fn test_override_policy() {
+        let req = ExtensionMetadataInterceptor::new()
+            .with_override_policy(OverridePolicy::SkipIfPresent)
+            .call(request_with_extra(&[("x-region", "eu-west-1")]))
+            .unwrap();
+        assert_eq!("us-east-1", req.metadata().get("x-region").unwrap());
+
+        let req = ExtensionMetadataInterceptor::new()
+            .with_override_policy(OverridePolicy::Append)
+            .call(request_with_extra(&[("x-region", "eu-west-1")]))
+            .unwrap();
+        assert_eq!(2, req.metadata().get_all("x-region").iter().count());
+    }
+
+    #[test]
+    fn test_invalid_pairs() {
+        assert!(ExtraMetadata::from_pairs([("invalid key", "value")]).is_err());
+        assert!(ExtraMetadata::from_pairs([("x-key", "line\nbreak")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extension_on_typed_request() {
+        let headers = Arc::new(Mutex::new(http::HeaderMap::new()));
+        let captured = headers.clone();
+        let service = tower::service_fn(move |req: http::Request<tonic::body::BoxBody>| {
+            *captured.lock().unwrap() = req.headers().clone();
+            async {
+                Ok::<_, Infallible>(
+                    http::Response::builder()
+                        .header("grpc-status", "12")
+                        .body(tonic::body::empty_body())
+                        .unwrap(),
+                )
+            }
+        });
+        let mut client = tonic::client::Grpc::new(InterceptedService::new(
+            service,
+            ExtensionMetadataInterceptor::new(),
+        ));
+
+        let mut request = tonic::Request::new(());
+        request
+            .extensions_mut()
+            .insert(ExtraMetadata::from_pairs([("x-feature", "on")]).unwrap());
+        client.ready().await.unwrap();
+        let _ = client
+            .unary(
+                request,
+                http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await;
+
+        assert_eq!("on", headers.lock().unwrap().get("x-feature").unwrap());
+    }
+}
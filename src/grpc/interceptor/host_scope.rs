@@ -0,0 +1,223 @@
+use http::uri::Authority;
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::DescribableInterceptor;
+use crate::grpc::layer::endpoint_identity::EndpointIdentity;
+
+/// An interceptor, that only lets the inner credential interceptor add its
+/// credentials to requests for the expected host
+///
+/// If the chain is reused for channels to several environments, a staging
+/// key is never sent to production. The host of a request is the
+/// [EndpointIdentity] recorded by the
+/// [EndpointIdentityLayer](crate::grpc::layer::endpoint_identity::EndpointIdentityLayer).
+/// Requests for another host, or without an identity, fail with
+/// [Status::failed_precondition], unless it only logs.
+#[derive(Clone)]
+pub struct HostScopedInterceptor<I> {
+    expected: String,
+    host: String,
+    port: Option<u16>,
+    inner: I,
+    log_only: bool,
+}
+
+impl<I> HostScopedInterceptor<I> {
+    /// Creates a new interceptor
+    /// # Arguments
+    /// * `expected`: The host, e.g. `api.example.com` or `::1`, or the
+    ///   authority with a port, e.g. `api.example.com:8443` or `[::1]:8443`,
+    ///   to also check the port
+    /// * `inner`: The interceptor adding the credentials
+    pub fn new(expected: &str, inner: I) -> Self {
+        let expected = expected.to_ascii_lowercase();
+        // A bare IPv6 address is no valid authority, and has no port
+        let (host, port) = match expected.parse::<Authority>() {
+            Ok(authority) => (
+                bare_host(authority.host()).to_string(),
+                authority.port_u16(),
+            ),
+            Err(_) => (bare_host(&expected).to_string(), None),
+        };
+        Self {
+            expected,
+            host,
+            port,
+            inner,
+            log_only: false,
+        }
+    }
+
+    /// Only logs a warning for other hosts and adds the credentials anyway,
+    /// e.g. while rolling out the check
+    pub fn with_log_only(mut self) -> Self {
+        self.log_only = true;
+        self
+    }
+
+    fn matches(&self, identity: &EndpointIdentity) -> bool {
+        bare_host(identity.host()).eq_ignore_ascii_case(&self.host)
+            && self
+                .port
+                .is_none_or(|port| identity.0.port_u16() == Some(port))
+    }
+}
+
+/// Returns the host without the brackets of an IPv6 address
+fn bare_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+impl<I: Interceptor> Interceptor for HostScopedInterceptor<I> {
+    fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let mismatch = match req.extensions().get::<EndpointIdentity>() {
+            Some(identity) if self.matches(identity) => None,
+            Some(identity) => Some(format!(
+                "The credentials for {} must not be sent to {}",
+                self.expected, identity.0
+            )),
+            None => Some(format!(
+                "The credentials for {} must not be sent to an unknown host",
+                self.expected
+            )),
+        };
+        if let Some(message) = mismatch {
+            if !self.log_only {
+                return Err(Status::failed_precondition(message));
+            }
+            log::warn!("{message}");
+        }
+        self.inner.call(req)
+    }
+}
+
+impl<I: DescribableInterceptor> DescribableInterceptor for HostScopedInterceptor<I> {
+    fn describe(&self) -> Option<String> {
+        let inner = self.inner.describe().unwrap_or_else(|| self.inner.name());
+        Some(format!("scoped to {}, {inner}", self.expected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tonic::body::BoxBody;
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::transport::Endpoint;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::interceptor::host_scope::HostScopedInterceptor;
+    use crate::grpc::interceptor::BearerTokenInterceptor;
+    use crate::grpc::layer::endpoint_identity::EndpointIdentityLayer;
+
+    /// Sends a request to the endpoint through the scoped interceptor and
+    /// returns the authorization header the channel would send
+    async fn send(
+        endpoint: &str,
+        interceptor: HostScopedInterceptor<BearerTokenInterceptor>,
+    ) -> Result<Option<String>, tonic::Status> {
+        let channel = tower::service_fn(|req: http::Request<BoxBody>| async move {
+            let mut response = http::Response::new(tonic::body::empty_body());
+            if let Some(authorization) = req.headers().get("authorization") {
+                response
+                    .headers_mut()
+                    .insert("x-seen-authorization", authorization.clone());
+            }
+            Ok::<_, Infallible>(response)
+        });
+        let endpoint = Endpoint::from_shared(endpoint.to_string()).unwrap();
+        let mut service = EndpointIdentityLayer::new(&endpoint)
+            .unwrap()
+            .layer(InterceptedService::new(channel, interceptor));
+
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(http::Request::new(tonic::body::empty_body()))
+            .await
+            .unwrap();
+        match tonic::Status::from_header_map(response.headers()) {
+            Some(status) => Err(status),
+            None => Ok(response
+                .headers()
+                .get("x-seen-authorization")
+                .map(|value| value.to_str().unwrap().to_string())),
+        }
+    }
+
+    fn staging() -> HostScopedInterceptor<BearerTokenInterceptor> {
+        HostScopedInterceptor::new(
+            "api.staging.example.com",
+            BearerTokenInterceptor::try_new("staging").unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_matching_host() {
+        assert_eq!(
+            Some("Bearer staging".to_string()),
+            send("https://API.staging.example.com:8443", staging())
+                .await
+                .unwrap()
+        );
+
+        let with_port = HostScopedInterceptor::new(
+            "api.staging.example.com:8443",
+            BearerTokenInterceptor::try_new("staging").unwrap(),
+        );
+        assert!(send("https://api.staging.example.com:9443", with_port)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_host() {
+        let scoped = |expected: &str| {
+            HostScopedInterceptor::new(expected, BearerTokenInterceptor::try_new("local").unwrap())
+        };
+
+        for expected in ["::1", "[::1]", "[::1]:8443"] {
+            assert_eq!(
+                Some("Bearer local".to_string()),
+                send("https://[::1]:8443", scoped(expected)).await.unwrap(),
+                "{expected}"
+            );
+        }
+        assert!(send("https://[::1]:9443", scoped("[::1]:8443"))
+            .await
+            .is_err());
+        assert!(send("https://[::2]:8443", scoped("::1")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_host() {
+        let status = send("https://api.example.com", staging())
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::FailedPrecondition, status.code());
+        assert_eq!(
+            "The credentials for api.staging.example.com must not be sent to api.example.com",
+            status.message()
+        );
+
+        assert_eq!(
+            Some("Bearer staging".to_string()),
+            send("https://api.example.com", staging().with_log_only())
+                .await
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unknown_host() {
+        use tonic::service::Interceptor;
+
+        let status = staging().call(tonic::Request::new(())).unwrap_err();
+        assert_eq!(tonic::Code::FailedPrecondition, status.code());
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use tonic::{
+    metadata::{AsciiMetadataValue, MetadataKey},
+    service::Interceptor,
+    Status,
+};
+
+use crate::grpc::interceptor::{DescribableInterceptor, OverridePolicy};
+
+/// The path where kubelet mounts the projected service account token
+pub const DEFAULT_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+/// The default maximum age of a cached token before it is re-read
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+struct CachedToken {
+    value: AsciiMetadataValue,
+    modified: Option<SystemTime>,
+    loaded_at: Instant,
+}
+
+/// An interceptor, that authenticates with a Kubernetes service account token
+///
+/// The token is read from a file, cached and injected as a bearer token. The
+/// file is re-read, when its modification time changes (kubelet rotates the
+/// token periodically) or when the cached token is older than the configured
+/// maximum age.
+#[derive(Clone)]
+pub struct KubernetesTokenInterceptor {
+    path: PathBuf,
+    max_age: Duration,
+    cached: Option<CachedToken>,
+    override_policy: OverridePolicy,
+}
+
+impl Default for KubernetesTokenInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KubernetesTokenInterceptor {
+    /// Creates a new interceptor reading the token from [DEFAULT_TOKEN_PATH]
+    pub fn new() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_TOKEN_PATH),
+            max_age: DEFAULT_MAX_AGE,
+            cached: None,
+            override_policy: OverridePolicy::default(),
+        }
+    }
+
+    /// Overrides the path of the token file, e.g. for bound audience tokens
+    /// # Arguments
+    /// * `path`: The path of the token file
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self.cached = None;
+        self
+    }
+
+    /// Sets the maximum age of the cached token
+    /// # Arguments
+    /// * `max_age`: The duration after which the token file is re-read, even
+    ///   if its modification time did not change
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Sets the policy for an already present authorization header
+    pub fn with_override_policy(mut self, override_policy: OverridePolicy) -> Self {
+        self.override_policy = override_policy;
+        self
+    }
+
+    fn token(&mut self) -> Result<AsciiMetadataValue, Status> {
+        let metadata = std::fs::metadata(&self.path).map_err(|e| self.read_error(e))?;
+        let modified = metadata.modified().ok();
+
+        if let Some(cached) = &self.cached
+            && cached.modified == modified
+            && cached.loaded_at.elapsed() < self.max_age
+        {
+            return Ok(cached.value.clone());
+        }
+
+        let content = std::fs::read_to_string(&self.path).map_err(|e| self.read_error(e))?;
+        let value = AsciiMetadataValue::try_from(format!("Bearer {}", content.trim()))
+            .map_err(|_| Status::invalid_argument("Invalid Token"))?;
+
+        log::debug!("Loaded service account token from {}", self.path.display());
+        self.cached = Some(CachedToken {
+            value: value.clone(),
+            modified,
+            loaded_at: Instant::now(),
+        });
+        Ok(value)
+    }
+
+    fn read_error(&self, e: std::io::Error) -> Status {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            Status::failed_precondition(format!(
+                "Service account token file {} not found",
+                self.path.display()
+            ))
+        } else {
+            Status::failed_precondition(format!(
+                "Error while reading service account token file {}: {e}",
+                self.path.display()
+            ))
+        }
+    }
+}
+
+impl Interceptor for KubernetesTokenInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let token = self.token()?;
+        self.override_policy.apply(
+            req.metadata_mut(),
+            MetadataKey::from_static("authorization"),
+            token,
+        );
+        Ok(req)
+    }
+}
+
+impl DescribableInterceptor for KubernetesTokenInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "token file {}, max age {:?}",
+            self.path.display(),
+            self.max_age
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use tonic::{service::Interceptor, Code};
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::kubernetes::{KubernetesTokenInterceptor, DEFAULT_TOKEN_PATH};
+
+    fn token_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("grpc-utils-rs-{}-{name}", std::process::id()))
+    }
+
+    fn write_token(path: &PathBuf, token: &str, modified: SystemTime) {
+        std::fs::write(path, token).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn authorization(interceptor: &mut KubernetesTokenInterceptor) -> String {
+        let req = interceptor.call(tonic::Request::new(())).unwrap();
+        req.metadata()
+            .get("authorization")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_default_path() {
+        let test_object = KubernetesTokenInterceptor::new();
+
+        assert_eq!(PathBuf::from(DEFAULT_TOKEN_PATH), test_object.path);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = token_file("rotation");
+        let now = SystemTime::now();
+        write_token(&path, "first-token\n", now - Duration::from_secs(120));
+
+        let mut test_object = KubernetesTokenInterceptor::new().with_path(&path);
+        assert_eq!("Bearer first-token", authorization(&mut test_object));
+
+        write_token(&path, "second-token\n", now);
+        assert_eq!("Bearer second-token", authorization(&mut test_object));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cached_until_max_age() {
+        let path = token_file("max-age");
+        let modified = SystemTime::now() - Duration::from_secs(120);
+        write_token(&path, "first-token", modified);
+
+        let mut test_object = KubernetesTokenInterceptor::new().with_path(&path);
+        assert_eq!("Bearer first-token", authorization(&mut test_object));
+
+        // Same modification time, so the cached token is still used
+        write_token(&path, "second-token", modified);
+        assert_eq!("Bearer first-token", authorization(&mut test_object));
+
+        let mut test_object = test_object.with_max_age(Duration::ZERO);
+        assert_eq!("Bearer second-token", authorization(&mut test_object));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let path = token_file("missing");
+        let status = assert_sets_metadata!(
+            KubernetesTokenInterceptor::new().with_path(&path),
+            Err(Code::FailedPrecondition)
+        );
+        assert!(status.message().contains(&path.display().to_string()));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tonic::metadata::{Ascii, MetadataKey};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::anti_replay::X_NONCE;
+use crate::grpc::interceptor::metadata_key;
+
+/// The default time window, in which a nonce must not be seen twice
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+/// The default maximum number of remembered nonces
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+#[derive(Default)]
+struct ReplayCache {
+    seen: HashMap<String, Instant>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl ReplayCache {
+    /// Remembers the nonce and returns false, if it was already seen within
+    /// the window
+    fn insert(&mut self, nonce: &str, now: Instant, window: Duration, max_entries: usize) -> bool {
+        while let Some((seen_at, _)) = self.order.front() {
+            if now.duration_since(*seen_at) < window {
+                break;
+            }
+            if let Some((_, expired)) = self.order.pop_front() {
+                self.seen.remove(&expired);
+            }
+        }
+
+        if self.seen.contains_key(nonce) {
+            return false;
+        }
+
+        while self.order.len() >= max_entries {
+            match self.order.pop_front() {
+                Some((_, oldest)) => self.seen.remove(&oldest),
+                None => break,
+            };
+        }
+        self.seen.insert(nonce.to_string(), now);
+        self.order.push_back((now, nonce.to_string()));
+        true
+    }
+}
+
+/// A server interceptor, that rejects requests with an already seen nonce
+///
+/// The nonces (as sent by an
+/// [AntiReplayInterceptor](super::anti_replay::AntiReplayInterceptor)) are
+/// remembered for a time window. The cache is bounded: when it is full, the
+/// oldest nonces are evicted. Clones of the interceptor share the cache.
+#[derive(Clone)]
+pub struct ReplayGuardInterceptor {
+    header_key: MetadataKey<Ascii>,
+    window: Duration,
+    max_entries: usize,
+    cache: Arc<Mutex<ReplayCache>>,
+}
+
+impl Default for ReplayGuardInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayGuardInterceptor {
+    /// Creates a new interceptor with [DEFAULT_WINDOW] and
+    /// [DEFAULT_MAX_ENTRIES]
+    pub fn new() -> Self {
+        Self {
+            header_key: MetadataKey::from_static(X_NONCE),
+            window: DEFAULT_WINDOW,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            cache: Arc::new(Mutex::new(ReplayCache::default())),
+        }
+    }
+
+    /// Overrides the name of the nonce header (default [X_NONCE])
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_header_name(mut self, header_name: &str) -> Result<Self, Status> {
+        self.header_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Sets the time window, in which a nonce is rejected a second time
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the maximum number of remembered nonces
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+}
+
+impl Interceptor for ReplayGuardInterceptor {
+    fn call(&mut self, req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let nonce = req
+            .metadata()
+            .get(&self.header_key)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                Status::unauthenticated(format!("Missing header {}", self.header_key))
+            })?;
+
+        let mut cache = self
+            .cache
+            .lock()
+            .map_err(|e| Status::internal(format!("Failed to lock replay cache: {}", e)))?;
+        if !cache.insert(nonce, Instant::now(), self.window, self.max_entries) {
+            return Err(Status::permission_denied("Replayed nonce"));
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tonic::{service::Interceptor, Code};
+
+    use crate::grpc::interceptor::anti_replay::{AntiReplayInterceptor, X_NONCE};
+    use crate::grpc::interceptor::replay_guard::ReplayGuardInterceptor;
+
+    fn request(nonce: &str) -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        req.metadata_mut().insert(X_NONCE, nonce.parse().unwrap());
+        req
+    }
+
+    #[test]
+    fn test_replay_rejected() {
+        let mut test_object = ReplayGuardInterceptor::new();
+        let mut clone = test_object.clone();
+
+        assert!(test_object.call(request("a")).is_ok());
+        let status = clone.call(request("a")).unwrap_err();
+        assert_eq!(Code::PermissionDenied, status.code());
+        assert!(clone.call(request("b")).is_ok());
+    }
+
+    #[test]
+    fn test_accepted_after_window() {
+        let mut test_object = ReplayGuardInterceptor::new().with_window(Duration::from_millis(20));
+
+        assert!(test_object.call(request("a")).is_ok());
+        assert!(test_object.call(request("a")).is_err());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(test_object.call(request("a")).is_ok());
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut test_object = ReplayGuardInterceptor::new().with_max_entries(2);
+
+        assert!(test_object.call(request("a")).is_ok());
+        assert!(test_object.call(request("b")).is_ok());
+        assert!(test_object.call(request("c")).is_ok());
+        assert_eq!(2, test_object.cache.lock().unwrap().seen.len());
+
+        // "a" was evicted as the oldest entry, "c" is still remembered
+        assert!(test_object.call(request("a")).is_ok());
+        assert!(test_object.call(request("c")).is_err());
+    }
+
+    #[test]
+    fn test_missing_nonce() {
+        let status = ReplayGuardInterceptor::new()
+            .call(tonic::Request::new(()))
+            .unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+    }
+
+    #[test]
+    fn test_with_anti_replay_interceptor() {
+        let mut client = AntiReplayInterceptor::new();
+        let mut test_object = ReplayGuardInterceptor::new();
+
+        let req = client.call(tonic::Request::new(())).unwrap();
+        let replayed = req.metadata().clone();
+        assert!(test_object.call(req).is_ok());
+        assert!(test_object
+            .call(tonic::Request::from_parts(replayed, Default::default(), ()))
+            .is_err());
+    }
+}
@@ -0,0 +1,420 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tonic::{
+    metadata::{AsciiMetadataValue, MetadataKey},
+    service::Interceptor,
+    Status,
+};
+
+use crate::grpc::interceptor::{
+    APIKeyClientInterceptor, BearerTokenInterceptor, BoxedInterceptor, Interceptors,
+};
+
+/// The symlink kubelet swaps atomically, when the secret is updated
+const DATA_LINK: &str = "..data";
+
+/// The time between two checks of the `..data` symlink by default
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The credential a file of a [SecretsDir] contains
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialRole {
+    /// An API key, sent as `x-api-key`
+    ApiKey,
+    /// A token, sent as `authorization: Bearer ...`
+    Bearer,
+    /// The user of basic authentication
+    BasicUser,
+    /// The password of basic authentication
+    BasicPassword,
+}
+
+#[derive(Clone, Debug)]
+struct SecretFile {
+    name: String,
+    role: CredentialRole,
+    required: bool,
+}
+
+/// Loads credentials from a directory of files, like a mounted Kubernetes
+/// Secret
+///
+/// Each file is mapped to a [CredentialRole], its content is trimmed.
+/// [SecretsDir::load] builds the [Interceptors] for the credentials. With
+/// [SecretsDir::with_hot_reload] the files are read again, when kubelet
+/// points the `..data` symlink of the directory to a new version. The
+/// symlink is checked at most once per reload interval, not on every call.
+/// ```ignore
+/// let interceptors = SecretsDir::new("/etc/creds")
+///     .with_file("api-key", CredentialRole::ApiKey)
+///     .with_file("token", CredentialRole::Bearer)
+///     .with_hot_reload()
+///     .load()?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct SecretsDir {
+    path: PathBuf,
+    files: Vec<SecretFile>,
+    hot_reload: bool,
+    reload_interval: Duration,
+}
+
+impl SecretsDir {
+    /// Creates a new loader without files
+    /// # Arguments
+    /// * `path`: The directory of the secret files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            files: Vec::new(),
+            hot_reload: false,
+            reload_interval: RELOAD_INTERVAL,
+        }
+    }
+
+    /// Maps a required file to a credential role
+    /// # Arguments
+    /// * `name`: The file name in the directory
+    /// * `role`: The credential, that the file contains
+    pub fn with_file(mut self, name: &str, role: CredentialRole) -> Self {
+        self.files.push(SecretFile {
+            name: name.to_string(),
+            role,
+            required: true,
+        });
+        self
+    }
+
+    /// Maps a file to a credential role, that is skipped when it is missing
+    pub fn with_optional_file(mut self, name: &str, role: CredentialRole) -> Self {
+        self.files.push(SecretFile {
+            name: name.to_string(),
+            role,
+            required: false,
+        });
+        self
+    }
+
+    /// Reloads the files, when the `..data` symlink of the directory changes
+    pub fn with_hot_reload(mut self) -> Self {
+        self.hot_reload = true;
+        self
+    }
+
+    /// Sets the time between two checks of the `..data` symlink, 1 second
+    /// by default
+    pub fn with_reload_interval(mut self, reload_interval: Duration) -> Self {
+        self.reload_interval = reload_interval;
+        self
+    }
+
+    /// Reads the files and returns the interceptors for the credentials
+    ///
+    /// Fails with [Status::failed_precondition] naming all required files,
+    /// that are missing or unreadable, and with [Status::invalid_argument]
+    /// for an incomplete basic authentication or invalid values.
+    pub fn load(self) -> Result<Interceptors, Status> {
+        let data_target = self.data_target();
+        let chain = self.read_chain()?;
+        let chain = if self.hot_reload {
+            let reloading: BoxedInterceptor = Box::new(ReloadingInterceptor {
+                dir: self,
+                data_target,
+                checked: Instant::now(),
+                chain,
+            });
+            vec![reloading]
+        } else {
+            chain
+        };
+        Ok(Arc::new(Mutex::new(chain)))
+    }
+
+    fn data_target(&self) -> Option<PathBuf> {
+        std::fs::read_link(self.path.join(DATA_LINK)).ok()
+    }
+
+    fn read(&self, name: &str) -> std::io::Result<String> {
+        std::fs::read_to_string(self.path.join(name)).map(|content| content.trim().to_string())
+    }
+
+    fn read_chain(&self) -> Result<Vec<BoxedInterceptor>, Status> {
+        let mut missing = Vec::new();
+        let mut values = Vec::new();
+        for file in &self.files {
+            match self.read(&file.name) {
+                Ok(value) => values.push((file.role, value)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && !file.required => {}
+                Err(e) => missing.push(format!("{} ({e})", file.name)),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Secret files missing in {}: {}",
+                self.path.display(),
+                missing.join(", ")
+            )));
+        }
+
+        let value = |role| {
+            values
+                .iter()
+                .find(|(candidate, _)| *candidate == role)
+                .map(|(_, value)| value.clone())
+        };
+        let invalid = |credential: &str| {
+            Status::invalid_argument(format!(
+                "The {credential} in {} is no valid header value",
+                self.path.display()
+            ))
+        };
+        let mut chain: Vec<BoxedInterceptor> = Vec::new();
+        if let Some(api_key) = value(CredentialRole::ApiKey) {
+            let interceptor =
+                APIKeyClientInterceptor::try_new(&api_key).map_err(|_| invalid("API key"))?;
+            chain.push(Box::new(interceptor));
+        }
+        if let Some(token) = value(CredentialRole::Bearer) {
+            let interceptor =
+                BearerTokenInterceptor::try_new(&token).map_err(|_| invalid("token"))?;
+            chain.push(Box::new(interceptor));
+        }
+        match (
+            value(CredentialRole::BasicUser),
+            value(CredentialRole::BasicPassword),
+        ) {
+            (Some(user), Some(password)) => {
+                let credentials = STANDARD.encode(format!("{user}:{password}"));
+                let header = AsciiMetadataValue::try_from(format!("Basic {credentials}"))
+                    .map_err(|_| invalid("basic credentials"))?;
+                chain.push(Box::new(move |mut req: tonic::Request<()>| {
+                    req.metadata_mut()
+                        .insert(MetadataKey::from_static("authorization"), header.clone());
+                    Ok(req)
+                }));
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Status::invalid_argument(format!(
+                    "Basic authentication in {} needs both the user and the password",
+                    self.path.display()
+                )));
+            }
+        }
+        Ok(chain)
+    }
+}
+
+/// Rebuilds the chain, when the `..data` symlink points to a new version
+struct ReloadingInterceptor {
+    dir: SecretsDir,
+    data_target: Option<PathBuf>,
+    /// The time the symlink was last checked
+    checked: Instant,
+    chain: Vec<BoxedInterceptor>,
+}
+
+impl ReloadingInterceptor {
+    /// Reads the files again, if the reload interval passed and the symlink
+    /// points to a new version
+    fn reload(&mut self) {
+        if self.checked.elapsed() < self.dir.reload_interval {
+            return;
+        }
+        self.checked = Instant::now();
+        let data_target = self.dir.data_target();
+        if data_target != self.data_target {
+            // Keep the previous credentials, if the new version is broken
+            match self.dir.read_chain() {
+                Ok(chain) => {
+                    log::info!("Reloaded the secrets in {}", self.dir.path.display());
+                    self.chain = chain;
+                    self.data_target = data_target;
+                }
+                Err(e) => log::error!("Reloading the secrets failed: {}", e.message()),
+            }
+        }
+    }
+}
+
+impl Interceptor for ReloadingInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        self.reload();
+        for interceptor in self.chain.iter_mut() {
+            req = interceptor.call(req)?;
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::os::unix::fs::symlink;
+    #[cfg(unix)]
+    use std::path::Path;
+    use std::path::PathBuf;
+    #[cfg(unix)]
+    use std::time::Duration;
+
+    use tonic::Code;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::secrets_dir::{CredentialRole, SecretsDir};
+    use crate::grpc::interceptor::CompositeInterceptor;
+
+    fn secrets_dir(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("grpc-utils-rs-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// Writes a new version of the secret and swaps `..data` like kubelet
+    #[cfg(unix)]
+    fn publish(path: &Path, version: &str, files: &[(&str, &str)]) {
+        let version_dir = path.join(version);
+        std::fs::create_dir(&version_dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(version_dir.join(name), content).unwrap();
+            let link = path.join(name);
+            if !link.exists() {
+                symlink(Path::new("..data").join(name), link).unwrap();
+            }
+        }
+        symlink(version, path.join("..data_tmp")).unwrap();
+        std::fs::rename(path.join("..data_tmp"), path.join("..data")).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_load_and_rotation() {
+        let path = secrets_dir("secrets-rotation");
+        publish(
+            &path,
+            "..v1",
+            &[("api-key", "key-1\n"), ("token", " token-1 ")],
+        );
+
+        let dir = SecretsDir::new(&path)
+            .with_file("api-key", CredentialRole::ApiKey)
+            .with_file("token", CredentialRole::Bearer)
+            .with_hot_reload()
+            .with_reload_interval(Duration::ZERO);
+        let interceptors = dir.load().unwrap();
+        assert_sets_metadata!(CompositeInterceptor::new(interceptors.clone()), {
+            "x-api-key" => "key-1",
+            "authorization" => "Bearer token-1",
+        });
+
+        publish(&path, "..v2", &[("api-key", "key-2"), ("token", "token-2")]);
+        assert_sets_metadata!(CompositeInterceptor::new(interceptors), {
+            "x-api-key" => "key-2",
+            "authorization" => "Bearer token-2",
+        });
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reload_interval() {
+        let path = secrets_dir("secrets-interval");
+        publish(&path, "..v1", &[("api-key", "key-1")]);
+
+        let interceptors = SecretsDir::new(&path)
+            .with_file("api-key", CredentialRole::ApiKey)
+            .with_hot_reload()
+            .with_reload_interval(Duration::from_millis(100))
+            .load()
+            .unwrap();
+        publish(&path, "..v2", &[("api-key", "key-2")]);
+        // The symlink is not checked again within the interval
+        assert_sets_metadata!(CompositeInterceptor::new(interceptors.clone()), {
+            "x-api-key" => "key-1",
+        });
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert_sets_metadata!(CompositeInterceptor::new(interceptors), {
+            "x-api-key" => "key-2",
+        });
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let path = secrets_dir("secrets-invalid");
+        std::fs::write(path.join("api-key"), "key\nwith line break").unwrap();
+        std::fs::write(path.join("token"), "token\u{7f}").unwrap();
+
+        for name in ["api-key", "token"] {
+            let role = match name {
+                "api-key" => CredentialRole::ApiKey,
+                _ => CredentialRole::Bearer,
+            };
+            let status = SecretsDir::new(&path)
+                .with_file(name, role)
+                .load()
+                .err()
+                .unwrap();
+            assert_eq!(Code::InvalidArgument, status.code());
+            assert!(status.message().contains("no valid header value"));
+            assert!(!status.message().contains("line break"));
+        }
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_basic_and_optional() {
+        let path = secrets_dir("secrets-basic");
+        std::fs::write(path.join("user"), "alice").unwrap();
+        std::fs::write(path.join("password"), "secret").unwrap();
+
+        let interceptors = SecretsDir::new(&path)
+            .with_file("user", CredentialRole::BasicUser)
+            .with_file("password", CredentialRole::BasicPassword)
+            .with_optional_file("api-key", CredentialRole::ApiKey)
+            .load()
+            .unwrap();
+        let req = assert_sets_metadata!(CompositeInterceptor::new(interceptors), {
+            "authorization" => "Basic YWxpY2U6c2VjcmV0",
+        });
+        assert!(req.metadata().get("x-api-key").is_none());
+
+        let status = SecretsDir::new(&path)
+            .with_file("user", CredentialRole::BasicUser)
+            .load()
+            .err()
+            .unwrap();
+        assert_eq!(Code::InvalidArgument, status.code());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let path = secrets_dir("secrets-missing");
+        std::fs::write(path.join("token"), "token").unwrap();
+
+        let status = SecretsDir::new(&path)
+            .with_file("api-key", CredentialRole::ApiKey)
+            .with_file("token", CredentialRole::Bearer)
+            .with_file("user", CredentialRole::BasicUser)
+            .load()
+            .err()
+            .unwrap();
+
+        assert_eq!(Code::FailedPrecondition, status.code());
+        assert!(status.message().contains("api-key"));
+        assert!(status.message().contains("user"));
+        assert!(!status.message().contains("token"));
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
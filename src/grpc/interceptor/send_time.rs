@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tonic::metadata::{Ascii, MetadataKey};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::{metadata_key, unix_millis, Clock, DescribableInterceptor};
+
+/// The default header containing the send time in unix epoch milliseconds
+pub const X_CLIENT_SEND_TIME_MS: &str = "x-client-send-time-ms";
+
+/// A function receiving the one-way latency of every call, e.g. to record
+/// it in a histogram
+pub type LatencyObserver = Arc<dyn Fn(Duration) + Send + Sync>;
+
+/// Returns the time from sending to receiving a call
+///
+/// A send time after the receive time, caused by clock skew, is clamped to
+/// zero.
+/// # Arguments
+/// * `sent_ms`: The send time of the client in unix epoch milliseconds
+/// * `received_ms`: The receive time of the server in unix epoch milliseconds
+pub fn one_way_latency(sent_ms: u64, received_ms: u64) -> Duration {
+    Duration::from_millis(received_ms.saturating_sub(sent_ms))
+}
+
+/// An interceptor, that adds the time a call is sent
+///
+/// The server compares it to the time it receives the call with a
+/// [SendTimeServerInterceptor], to measure network and queueing latency.
+/// The clocks of client and server should be synchronized, e.g. with NTP.
+#[derive(Clone)]
+pub struct SendTimeInterceptor {
+    header_key: MetadataKey<Ascii>,
+    clock: Clock,
+}
+
+impl Default for SendTimeInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendTimeInterceptor {
+    /// Creates a new interceptor using the system clock
+    pub fn new() -> Self {
+        Self {
+            header_key: MetadataKey::from_static(X_CLIENT_SEND_TIME_MS),
+            clock: Arc::new(SystemTime::now),
+        }
+    }
+
+    /// Overrides the name of the header (default [X_CLIENT_SEND_TIME_MS])
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_header_name(mut self, header_name: &str) -> Result<Self, Status> {
+        self.header_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Replaces the clock used for the send time
+    pub fn with_clock(mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl Interceptor for SendTimeInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        req.metadata_mut()
+            .insert(self.header_key.clone(), unix_millis(&self.clock)?.into());
+        Ok(req)
+    }
+}
+
+impl DescribableInterceptor for SendTimeInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!("header {}", self.header_key))
+    }
+}
+
+/// The one-way latency of a call, inserted into the request extensions by a
+/// [SendTimeServerInterceptor]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OneWayLatency(pub Duration);
+
+/// A server interceptor, that measures the latency from the client sending
+/// a call to the server receiving it
+///
+/// The send time of a [SendTimeInterceptor] is compared to the server clock,
+/// see [one_way_latency]. The latency is inserted as [OneWayLatency] into the
+/// request extensions and passed to the observer. Calls without a valid
+/// header are passed on unchanged.
+#[derive(Clone)]
+pub struct SendTimeServerInterceptor {
+    header_key: MetadataKey<Ascii>,
+    clock: Clock,
+    observer: Option<LatencyObserver>,
+}
+
+impl Default for SendTimeServerInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendTimeServerInterceptor {
+    /// Creates a new interceptor using the system clock
+    pub fn new() -> Self {
+        Self {
+            header_key: MetadataKey::from_static(X_CLIENT_SEND_TIME_MS),
+            clock: Arc::new(SystemTime::now),
+            observer: None,
+        }
+    }
+
+    /// Overrides the name of the header (default [X_CLIENT_SEND_TIME_MS])
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_header_name(mut self, header_name: &str) -> Result<Self, Status> {
+        self.header_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+
+    /// Replaces the clock used for the receive time
+    pub fn with_clock(mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets a function, that receives the latency of every measured call
+    pub fn with_observer(mut self, observer: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+}
+
+impl Interceptor for SendTimeServerInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let sent_ms = req
+            .metadata()
+            .get(&self.header_key)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let Some(sent_ms) = sent_ms else {
+            log::debug!("No valid header {}", self.header_key);
+            return Ok(req);
+        };
+
+        let latency = one_way_latency(sent_ms, unix_millis(&self.clock)?);
+        if let Some(observer) = &self.observer {
+            observer(latency);
+        }
+        req.extensions_mut().insert(OneWayLatency(latency));
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tonic::service::Interceptor;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::send_time::{
+        one_way_latency, OneWayLatency, SendTimeInterceptor, SendTimeServerInterceptor,
+    };
+
+    #[test]
+    fn test_one_way_latency() {
+        assert_eq!(Duration::from_millis(25), one_way_latency(1_000, 1_025));
+        assert_eq!(Duration::ZERO, one_way_latency(1_000, 1_000));
+        // The client clock is ahead
+        assert_eq!(Duration::ZERO, one_way_latency(1_025, 1_000));
+    }
+
+    #[test]
+    fn test_end_to_end() {
+        let sent = assert_sets_metadata!(
+            SendTimeInterceptor::new()
+                .with_header_name("x-sent")
+                .unwrap()
+                .with_clock(|| UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)),
+            { "x-sent" => "1700000000000" }
+        );
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let recorded = observed.clone();
+        let mut server = SendTimeServerInterceptor::new()
+            .with_header_name("x-sent")
+            .unwrap()
+            .with_clock(|| UNIX_EPOCH + Duration::from_millis(1_700_000_000_042))
+            .with_observer(move |latency| recorded.lock().unwrap().push(latency));
+
+        let received = tonic::Request::from_parts(sent.metadata().clone(), Default::default(), ());
+        let req = server.call(received).unwrap();
+        assert_eq!(
+            Some(&OneWayLatency(Duration::from_millis(42))),
+            req.extensions().get::<OneWayLatency>()
+        );
+        assert_eq!(vec![Duration::from_millis(42)], *observed.lock().unwrap());
+
+        let mut missing = tonic::Request::new(());
+        missing
+            .metadata_mut()
+            .insert("x-sent", "yesterday".parse().unwrap());
+        let req = server.call(missing).unwrap();
+        assert_eq!(None, req.extensions().get::<OneWayLatency>());
+        assert_eq!(1, observed.lock().unwrap().len());
+    }
+}
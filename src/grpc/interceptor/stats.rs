@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tonic::Code;
+
+use crate::grpc::interceptor::UNNAMED;
+use crate::grpc::tasks::BackgroundTasks;
+
+/// The version of the JSON schema of [StatsSnapshot::to_json]
+pub const STATS_SCHEMA_VERSION: u32 = 1;
+
+/// A function receiving every flushed snapshot as JSON
+pub type StatsSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The counters of one interceptor of a chain
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterceptorStats {
+    /// The number of calls of the interceptor
+    pub calls: u64,
+    /// The number of calls, that the interceptor failed, by code
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// The counters of a window, see [StatsCollector]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// The start of the window
+    pub since: SystemTime,
+    /// The number of calls through the chain
+    pub calls: u64,
+    /// The number of failed calls by code, e.g. `Unauthenticated`
+    pub errors: BTreeMap<String, u64>,
+    /// The shortest time the chain took
+    pub min_latency: Option<Duration>,
+    /// The longest time the chain took
+    pub max_latency: Option<Duration>,
+    /// The total time the chain took, for the average
+    pub total_latency: Duration,
+    /// The counters by position and name of the interceptors
+    pub interceptors: BTreeMap<(usize, String), InterceptorStats>,
+}
+
+impl StatsSnapshot {
+    /// Returns the average time the chain took
+    pub fn avg_latency(&self) -> Option<Duration> {
+        u32::try_from(self.calls)
+            .ok()
+            .filter(|calls| *calls > 0)
+            .map(|calls| self.total_latency / calls)
+    }
+
+    /// Returns the snapshot as JSON in the schema [STATS_SCHEMA_VERSION]
+    ///
+    /// Latencies are in microseconds and `null` without calls. The window
+    /// start is in milliseconds since the unix epoch.
+    pub fn to_json(&self) -> String {
+        let micros = |latency: Option<Duration>| {
+            latency.map_or("null".to_string(), |latency| {
+                latency.as_micros().to_string()
+            })
+        };
+        let since = self
+            .since
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let mut json = format!(
+            "{{\"version\":{STATS_SCHEMA_VERSION},\"since_ms\":{since},\"calls\":{},\"errors\":{},\"latency_us\":{{\"min\":{},\"max\":{},\"avg\":{}}},\"interceptors\":[",
+            self.calls,
+            counts(&self.errors),
+            micros(self.min_latency),
+            micros(self.max_latency),
+            micros(self.avg_latency()),
+        );
+        for (index, ((position, name), stats)) in self.interceptors.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"position\":{position},\"name\":{},\"calls\":{},\"errors\":{}}}",
+                string(name),
+                stats.calls,
+                counts(&stats.errors)
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Returns the counts as a JSON object
+fn counts(counts: &BTreeMap<String, u64>) -> String {
+    let entries: Vec<_> = counts
+        .iter()
+        .map(|(key, count)| format!("{}:{count}", string(key)))
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+/// Returns the value as a JSON string
+fn string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The counters of the current window
+///
+/// The names of the interceptors are shared with the chain, so recording a
+/// call does not allocate them.
+struct Window {
+    since: SystemTime,
+    calls: u64,
+    errors: BTreeMap<String, u64>,
+    min_latency: Option<Duration>,
+    max_latency: Option<Duration>,
+    total_latency: Duration,
+    interceptors: BTreeMap<(usize, Arc<str>), InterceptorStats>,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            since: SystemTime::now(),
+            calls: 0,
+            errors: BTreeMap::new(),
+            min_latency: None,
+            max_latency: None,
+            total_latency: Duration::ZERO,
+            interceptors: BTreeMap::new(),
+        }
+    }
+
+    fn to_snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            since: self.since,
+            calls: self.calls,
+            errors: self.errors.clone(),
+            min_latency: self.min_latency,
+            max_latency: self.max_latency,
+            total_latency: self.total_latency,
+            interceptors: self
+                .interceptors
+                .iter()
+                .map(|((position, name), stats)| ((*position, name.to_string()), stats.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Collects counters of the calls through a
+/// [CompositeInterceptor](crate::grpc::interceptor::CompositeInterceptor)
+/// in memory and flushes them to a sink, for deployments without a metrics
+/// endpoint
+///
+/// Every flush hands the counters of the window since the last flush as JSON
+/// to the sink and starts a new window. A call and the interceptors it ran
+/// are recorded under one lock, so a snapshot never splits a call between
+/// two windows. Clones share the counters.
+#[derive(Clone)]
+pub struct StatsCollector {
+    window: Arc<Mutex<Window>>,
+    sink: StatsSink,
+    /// The name of interceptors without a description
+    unnamed: Arc<str>,
+}
+
+impl StatsCollector {
+    /// Creates a new collector
+    /// # Arguments
+    /// * `sink`: The function receiving every flushed snapshot as JSON
+    pub fn new(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self {
+            window: Arc::new(Mutex::new(Window::new())),
+            sink: Arc::new(sink),
+            unnamed: Arc::from(UNNAMED),
+        }
+    }
+
+    /// Creates a new collector, that appends every snapshot as a line to the
+    /// file
+    ///
+    /// Failing writes are logged, the snapshot is lost.
+    pub fn to_file(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self::new(move |json| {
+            let written = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| writeln!(file, "{json}"));
+            if let Err(e) = written {
+                log::error!("Writing the stats to {} failed: {e}", path.display());
+            }
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.window.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a call through the chain and the interceptors it called
+    /// # Arguments
+    /// * `latency`: The time the chain took
+    /// * `error`: The code, if the call failed
+    /// * `names`: The names of the interceptors by position
+    /// * `results`: The codes of the failed interceptors by position, for
+    ///   every interceptor called
+    pub(crate) fn record(
+        &self,
+        latency: Duration,
+        error: Option<Code>,
+        names: &[Arc<str>],
+        results: &[Option<Code>],
+    ) {
+        let mut window = self.lock();
+        window.calls += 1;
+        if let Some(code) = error {
+            *window.errors.entry(format!("{code:?}")).or_default() += 1;
+        }
+        window.min_latency = Some(window.min_latency.map_or(latency, |min| min.min(latency)));
+        window.max_latency = Some(window.max_latency.map_or(latency, |max| max.max(latency)));
+        window.total_latency += latency;
+
+        for (position, error) in results.iter().enumerate() {
+            let name = names.get(position).unwrap_or(&self.unnamed).clone();
+            let stats = window.interceptors.entry((position, name)).or_default();
+            stats.calls += 1;
+            if let Some(code) = error {
+                *stats.errors.entry(format!("{code:?}")).or_default() += 1;
+            }
+        }
+    }
+
+    /// Returns the counters of the current window, without resetting them
+    pub fn snapshot(&self) -> StatsSnapshot {
+        self.lock().to_snapshot()
+    }
+
+    /// Hands the counters of the current window to the sink and starts a
+    /// new window, e.g. at shutdown
+    pub fn flush_now(&self) -> StatsSnapshot {
+        let snapshot = std::mem::replace(&mut *self.lock(), Window::new()).to_snapshot();
+        // The sink runs outside of the lock, so calls are not blocked
+        (self.sink)(&snapshot.to_json());
+        snapshot
+    }
+
+    /// Spawns a task in the tasks, that flushes at the interval
+    ///
+    /// When the tasks shut down, the task flushes the last window and exits.
+    pub fn spawn_flusher(&self, interval: Duration, tasks: &BackgroundTasks) {
+        let collector = self.clone();
+        tasks.spawn(|mut signal| async move {
+            let mut ticks = tokio::time::interval(interval);
+            // The first tick completes right away
+            ticks.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {
+                        collector.flush_now();
+                    }
+                    _ = signal.wait() => {
+                        collector.flush_now();
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tonic::service::Interceptor;
+    use tonic::Status;
+
+    use crate::grpc::interceptor::stats::{StatsCollector, STATS_SCHEMA_VERSION};
+    use crate::grpc::interceptor::ChainBuilder;
+    use crate::grpc::tasks::BackgroundTasks;
+
+    fn collector() -> (StatsCollector, Arc<Mutex<Vec<String>>>) {
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let sink = flushed.clone();
+        let collector =
+            StatsCollector::new(move |json| sink.lock().unwrap().push(json.to_string()));
+        (collector, flushed)
+    }
+
+    #[test]
+    fn test_flush() {
+        let (collector, flushed) = collector();
+        let mut composite = ChainBuilder::new()
+            .with_named("pass", |req: tonic::Request<()>| Ok(req))
+            .with_named("deny", |req: tonic::Request<()>| {
+                if req.metadata().contains_key("x-deny") {
+                    Err(Status::permission_denied("denied"))
+                } else {
+                    Ok(req)
+                }
+            })
+            .build()
+            .with_stats(collector.clone());
+
+        composite.call(tonic::Request::new(())).unwrap();
+        composite.call(tonic::Request::new(())).unwrap();
+        let mut denied = tonic::Request::new(());
+        denied.metadata_mut().insert("x-deny", "1".parse().unwrap());
+        composite.call(denied).unwrap_err();
+
+        let snapshot = collector.flush_now();
+        assert_eq!(3, snapshot.calls);
+        assert_eq!(Some(&1), snapshot.errors.get("PermissionDenied"));
+        assert!(snapshot.min_latency <= snapshot.avg_latency());
+        assert!(snapshot.avg_latency() <= snapshot.max_latency);
+        assert_eq!(3, snapshot.interceptors[&(0, "pass".to_string())].calls);
+        assert_eq!(
+            Some(&1),
+            snapshot.interceptors[&(1, "deny".to_string())]
+                .errors
+                .get("PermissionDenied")
+        );
+
+        let json = flushed.lock().unwrap()[0].clone();
+        assert!(json.starts_with(&format!(
+            "{{\"version\":{STATS_SCHEMA_VERSION},\"since_ms\":"
+        )));
+        assert!(json
+            .contains("\"calls\":3,\"errors\":{\"PermissionDenied\":1},\"latency_us\":{\"min\":"));
+        assert!(json.ends_with(
+            "\"interceptors\":[{\"position\":0,\"name\":\"pass\",\"calls\":3,\"errors\":{}},{\"position\":1,\"name\":\"deny\",\"calls\":3,\"errors\":{\"PermissionDenied\":1}}]}"
+        ));
+
+        // The flush started a new window
+        assert_eq!(0, collector.snapshot().calls);
+        collector.flush_now();
+        assert!(flushed.lock().unwrap()[1].contains(
+            "\"calls\":0,\"errors\":{},\"latency_us\":{\"min\":null,\"max\":null,\"avg\":null}"
+        ));
+    }
+
+    #[test]
+    fn test_no_torn_windows() {
+        let (collector, _) = collector();
+        let mut composite = ChainBuilder::new()
+            .with_named("first", |req: tonic::Request<()>| Ok(req))
+            .with_named("second", |req: tonic::Request<()>| Ok(req))
+            .build()
+            .with_stats(collector.clone());
+
+        let caller = std::thread::spawn(move || {
+            for _ in 0..20_000 {
+                composite.call(tonic::Request::new(())).unwrap();
+            }
+        });
+        let mut windows = Vec::new();
+        while !caller.is_finished() {
+            windows.push(collector.flush_now());
+        }
+        caller.join().unwrap();
+        windows.push(collector.flush_now());
+
+        let mut total = 0;
+        for window in windows {
+            // Every call of the window ran both interceptors in the window
+            for name in ["first", "second"] {
+                let calls = window
+                    .interceptors
+                    .get(&(usize::from(name == "second"), name.to_string()))
+                    .map_or(0, |stats| stats.calls);
+                assert_eq!(window.calls, calls);
+            }
+            total += window.calls;
+        }
+        assert_eq!(20_000, total);
+    }
+
+    #[tokio::test]
+    async fn test_flusher() {
+        let (collector, flushed) = collector();
+        collector.record(Duration::from_millis(2), None, &[], &[]);
+        let tasks = BackgroundTasks::new();
+        collector.spawn_flusher(Duration::from_millis(20), &tasks);
+
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        let before = flushed.lock().unwrap().len();
+        collector.record(Duration::from_millis(3), None, &[], &[]);
+        assert_eq!(0, tasks.shutdown().await);
+        let flushed = flushed.lock().unwrap();
+        assert!(flushed.len() >= 2, "{flushed:?}");
+        assert!(flushed[0].contains("\"calls\":1,"));
+        assert!(flushed[0].contains("\"min\":2000,\"max\":2000,\"avg\":2000"));
+        assert!(flushed[1].contains("\"calls\":0,"));
+        // The shutdown flushed the last window
+        assert_eq!(before + 1, flushed.len());
+        assert!(flushed[before].contains("\"min\":3000,"));
+    }
+
+    #[test]
+    fn test_to_file() {
+        let path = std::env::temp_dir().join(format!("stats-{}.jsonl", std::process::id()));
+        let collector = StatsCollector::to_file(&path);
+        collector.flush_now();
+        collector.flush_now();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(2, written.lines().count());
+    }
+}
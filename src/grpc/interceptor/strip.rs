@@ -0,0 +1,161 @@
+use tonic::metadata::{Ascii, Binary, KeyRef, MetadataKey};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::DescribableInterceptor;
+
+/// An interceptor, that removes metadata keys from the outgoing request
+///
+/// It removes internal routing hints or stale credentials set by upstream
+/// layers, before a request leaves a proxy. Keys are matched exactly or by
+/// prefix, ASCII and binary (`-bin`) keys alike. Put it first in the chain,
+/// so later interceptors can add clean values.
+#[derive(Clone, Debug, Default)]
+pub struct StripMetadataInterceptor {
+    keys: Vec<String>,
+    prefixes: Vec<String>,
+}
+
+impl StripMetadataInterceptor {
+    /// Creates a new interceptor
+    /// # Arguments
+    /// * `keys`: The keys to remove, e.g. `x-routing-hint` or `x-trace-bin`
+    pub fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            keys: keys.into_iter().map(str::to_ascii_lowercase).collect(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Also removes all keys starting with the prefix, e.g. `x-internal-`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefixes.push(prefix.to_ascii_lowercase());
+        self
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        self.keys.iter().any(|candidate| candidate == key)
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix.as_str()))
+    }
+}
+
+/// An owned key of either kind, so it can be removed after the iteration
+enum OwnedKey {
+    Ascii(MetadataKey<Ascii>),
+    Binary(MetadataKey<Binary>),
+}
+
+impl Interceptor for StripMetadataInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let metadata = req.metadata_mut();
+        let matched: Vec<_> = metadata
+            .keys()
+            .filter(|key| match key {
+                KeyRef::Ascii(key) => self.matches(key.as_str()),
+                KeyRef::Binary(key) => self.matches(key.as_str()),
+            })
+            .map(|key| match key {
+                KeyRef::Ascii(key) => OwnedKey::Ascii(key.clone()),
+                KeyRef::Binary(key) => OwnedKey::Binary(key.clone()),
+            })
+            .collect();
+
+        for key in matched {
+            let name = match key {
+                OwnedKey::Ascii(key) => {
+                    metadata.remove(&key);
+                    key.to_string()
+                }
+                OwnedKey::Binary(key) => {
+                    metadata.remove_bin(&key);
+                    key.to_string()
+                }
+            };
+            log::debug!("Removed the metadata key {name}");
+        }
+        Ok(req)
+    }
+}
+
+impl DescribableInterceptor for StripMetadataInterceptor {
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "keys {:?}, prefixes {:?}",
+            self.keys, self.prefixes
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::metadata::MetadataValue;
+    use tonic::service::Interceptor;
+
+    use crate::grpc::interceptor::strip::StripMetadataInterceptor;
+
+    fn request() -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        let metadata = req.metadata_mut();
+        metadata.insert("authorization", "Bearer stale".parse().unwrap());
+        metadata.append("authorization", "Bearer other".parse().unwrap());
+        metadata.insert("x-internal-route", "a".parse().unwrap());
+        metadata.insert("x-internal-shard", "b".parse().unwrap());
+        metadata.insert_bin("x-internal-trace-bin", MetadataValue::from_bytes(b"trace"));
+        metadata.insert_bin("x-hint-bin", MetadataValue::from_bytes(b"hint"));
+        metadata.insert("x-keep", "c".parse().unwrap());
+        req
+    }
+
+    fn keys(req: &tonic::Request<()>) -> Vec<String> {
+        let mut keys: Vec<_> = req
+            .metadata()
+            .keys()
+            .map(|key| match key {
+                tonic::metadata::KeyRef::Ascii(key) => key.to_string(),
+                tonic::metadata::KeyRef::Binary(key) => key.to_string(),
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_exact() {
+        let req = StripMetadataInterceptor::new(["Authorization", "x-hint-bin"])
+            .call(request())
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                "x-internal-route",
+                "x-internal-shard",
+                "x-internal-trace-bin",
+                "x-keep"
+            ],
+            keys(&req)
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        let req = StripMetadataInterceptor::new([])
+            .with_prefix("x-internal-")
+            .call(request())
+            .unwrap();
+
+        assert_eq!(vec!["authorization", "x-hint-bin", "x-keep"], keys(&req));
+        assert_eq!(2, req.metadata().get_all("authorization").iter().count());
+    }
+
+    #[test]
+    fn test_no_op() {
+        let req = StripMetadataInterceptor::new(["x-missing"])
+            .with_prefix("x-other-")
+            .call(request())
+            .unwrap();
+
+        assert_eq!(keys(&request()), keys(&req));
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tonic::metadata::{Ascii, MetadataKey};
+use tonic::{service::Interceptor, Status};
+
+use crate::grpc::interceptor::{metadata_key, X_API_KEY};
+
+/// The tenant an API key belongs to
+///
+/// The [TenantInterceptor] inserts it into the request extensions, so
+/// handlers get it with `request.extensions().get::<TenantInfo>()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TenantInfo {
+    /// The id of the tenant
+    pub tenant_id: String,
+    /// The plan of the tenant
+    pub plan: String,
+    /// True, if the tenant must not use the service
+    pub disabled: bool,
+}
+
+/// Resolves API keys to tenants, e.g. from a database
+pub trait TenantResolver: Send + Sync {
+    /// Returns the tenant of the API key
+    ///
+    /// An unknown key should fail with [Status::unauthenticated].
+    fn resolve(&self, api_key: &str) -> Result<TenantInfo, Status>;
+}
+
+/// A [TenantResolver] with a fixed set of API keys
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryTenantResolver {
+    tenants: HashMap<String, TenantInfo>,
+}
+
+impl InMemoryTenantResolver {
+    /// Creates a resolver without API keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an API key of a tenant
+    pub fn with_tenant(mut self, api_key: &str, tenant: TenantInfo) -> Self {
+        self.tenants.insert(api_key.to_string(), tenant);
+        self
+    }
+}
+
+impl TenantResolver for InMemoryTenantResolver {
+    fn resolve(&self, api_key: &str) -> Result<TenantInfo, Status> {
+        self.tenants
+            .get(api_key)
+            .cloned()
+            .ok_or_else(|| Status::unauthenticated("Unknown API key"))
+    }
+}
+
+/// A server interceptor, that resolves the API key of a request to its
+/// tenant
+///
+/// Requests without or with an unknown API key are rejected with
+/// [Status::unauthenticated], requests of disabled tenants with
+/// [Status::permission_denied]. Otherwise the [TenantInfo] is inserted into
+/// the request extensions.
+#[derive(Clone)]
+pub struct TenantInterceptor {
+    header_key: MetadataKey<Ascii>,
+    resolver: Arc<dyn TenantResolver>,
+}
+
+impl TenantInterceptor {
+    /// Creates a new interceptor reading the `x-api-key` header
+    /// # Arguments
+    /// * `resolver`: The store of the API keys
+    pub fn new(resolver: impl TenantResolver + 'static) -> Self {
+        Self {
+            header_key: MetadataKey::from_static(X_API_KEY),
+            resolver: Arc::new(resolver),
+        }
+    }
+
+    /// Sets the header carrying the API key
+    ///
+    /// Fails with [Status::invalid_argument], if the name is no valid key.
+    pub fn with_header_name(mut self, header_name: &str) -> Result<Self, Status> {
+        self.header_key = metadata_key(header_name)?;
+        Ok(self)
+    }
+}
+
+impl Interceptor for TenantInterceptor {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let api_key = req
+            .metadata()
+            .get(&self.header_key)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Missing API key"))?;
+
+        let tenant = self.resolver.resolve(api_key)?;
+        if tenant.disabled {
+            return Err(Status::permission_denied(format!(
+                "Tenant {} is disabled",
+                tenant.tenant_id
+            )));
+        }
+        req.extensions_mut().insert(tenant);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::service::Interceptor;
+    use tonic::Code;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::interceptor::tenant::{InMemoryTenantResolver, TenantInfo, TenantInterceptor};
+
+    fn tenant(tenant_id: &str, disabled: bool) -> TenantInfo {
+        TenantInfo {
+            tenant_id: tenant_id.to_string(),
+            plan: String::from("enterprise"),
+            disabled,
+        }
+    }
+
+    fn interceptor() -> TenantInterceptor {
+        TenantInterceptor::new(
+            InMemoryTenantResolver::new()
+                .with_tenant("key-acme", tenant("acme", false))
+                .with_tenant("key-globex", tenant("globex", true)),
+        )
+    }
+
+    fn request(api_key: &str) -> tonic::Request<()> {
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("x-api-key", api_key.parse().unwrap());
+        req
+    }
+
+    #[test]
+    fn test_known_tenant() {
+        let req = interceptor().call(request("key-acme")).unwrap();
+
+        assert_eq!(
+            Some(&tenant("acme", false)),
+            req.extensions().get::<TenantInfo>()
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let status = interceptor().call(request("key-unknown")).unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+
+        assert_sets_metadata!(interceptor(), Err(Code::Unauthenticated));
+
+        let status = interceptor().call(request("key-globex")).unwrap_err();
+        assert_eq!(Code::PermissionDenied, status.code());
+        assert_eq!("Tenant globex is disabled", status.message());
+    }
+}
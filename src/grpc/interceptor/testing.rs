@@ -0,0 +1,150 @@
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tonic::{Code, Status};
+
+/// The expectation on a metadata value in [assert_sets_metadata!](crate::assert_sets_metadata)
+#[derive(Clone, Debug)]
+pub enum Matcher<'a> {
+    /// The value is equal to the string
+    Exact(&'a str),
+    /// The value starts with the string
+    StartsWith(&'a str),
+}
+
+impl Matcher<'_> {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Exact(expected) => value == *expected,
+            Matcher::StartsWith(prefix) => value.starts_with(prefix),
+        }
+    }
+}
+
+/// Calls the interceptor with an empty request
+pub fn run(mut interceptor: impl Interceptor) -> Result<tonic::Request<()>, Status> {
+    interceptor.call(tonic::Request::new(()))
+}
+
+/// Panics, if no value of the key matches, showing the whole metadata
+pub fn check_metadata(metadata: &MetadataMap, key: &str, matcher: Matcher) {
+    let matched = metadata
+        .get_all(key)
+        .iter()
+        .any(|value| value.to_str().is_ok_and(|value| matcher.matches(value)));
+    assert!(
+        matched,
+        "Expected {key} to match {matcher:?}, but the metadata is {metadata:?}"
+    );
+}
+
+/// Panics, if the interceptor did not fail with the code
+pub fn check_error(result: Result<tonic::Request<()>, Status>, code: Code) -> Status {
+    match result {
+        Ok(req) => panic!(
+            "Expected the interceptor to fail with {code:?}, but it returned the metadata {:?}",
+            req.metadata()
+        ),
+        Err(status) => {
+            assert_eq!(code, status.code(), "Unexpected status {status:?}");
+            status
+        }
+    }
+}
+
+/// Asserts, that an interceptor sets metadata on an empty request
+///
+/// Values are either compared exactly or with `starts_with(...)`. On
+/// success the intercepted request is returned, on failure the whole
+/// metadata is shown. The `Err(code)` variant asserts, that the
+/// interceptor fails with the code, and returns the status.
+/// ```
+/// use grpc_utils_rs::assert_sets_metadata;
+/// use grpc_utils_rs::grpc::interceptor::{APIKeyClientInterceptor, BearerTokenInterceptor};
+///
+/// assert_sets_metadata!(APIKeyClientInterceptor::try_new("key").unwrap(), {
+///     "x-api-key" => "key",
+/// });
+/// assert_sets_metadata!(BearerTokenInterceptor::try_new("token").unwrap(), {
+///     "authorization" => starts_with("Bearer "),
+/// });
+/// let deny = |_: tonic::Request<()>| -> Result<tonic::Request<()>, tonic::Status> {
+///     Err(tonic::Status::permission_denied("Denied"))
+/// };
+/// assert_sets_metadata!(deny, Err(tonic::Code::PermissionDenied));
+/// ```
+#[macro_export]
+macro_rules! assert_sets_metadata {
+    ( $interceptor:expr, Err($code:expr) $(,)? ) => {
+        $crate::grpc::interceptor::testing::check_error(
+            $crate::grpc::interceptor::testing::run($interceptor),
+            $code,
+        )
+    };
+    ( $interceptor:expr, { $($expected:tt)* } $(,)? ) => {{
+        let request = match $crate::grpc::interceptor::testing::run($interceptor) {
+            ::std::result::Result::Ok(request) => request,
+            ::std::result::Result::Err(status) => {
+                ::std::panic!("Expected the interceptor to succeed, but it failed with {status:?}")
+            }
+        };
+        $crate::assert_sets_metadata!(@check request.metadata(), $($expected)*);
+        request
+    }};
+    ( @check $metadata:expr, ) => {};
+    ( @check $metadata:expr, $key:literal => starts_with($prefix:expr) $(, $($rest:tt)*)? ) => {
+        $crate::grpc::interceptor::testing::check_metadata(
+            $metadata,
+            $key,
+            $crate::grpc::interceptor::testing::Matcher::StartsWith($prefix),
+        );
+        $crate::assert_sets_metadata!(@check $metadata, $($($rest)*)?);
+    };
+    ( @check $metadata:expr, $key:literal => $value:expr $(, $($rest:tt)*)? ) => {
+        $crate::grpc::interceptor::testing::check_metadata(
+            $metadata,
+            $key,
+            $crate::grpc::interceptor::testing::Matcher::Exact($value),
+        );
+        $crate::assert_sets_metadata!(@check $metadata, $($($rest)*)?);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use crate::grpc::interceptor::{APIKeyClientInterceptor, BearerTokenInterceptor};
+
+    #[test]
+    fn test_matchers() {
+        let req = assert_sets_metadata!(
+            crate::composite!(
+                APIKeyClientInterceptor::try_new("key").unwrap(),
+                BearerTokenInterceptor::try_new("token").unwrap()
+            ),
+            {
+                "x-api-key" => "key",
+                "authorization" => starts_with("Bearer "),
+                "authorization" => "Bearer token"
+            }
+        );
+        assert_eq!(2, req.metadata().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected x-api-key to match Exact(\"other\")")]
+    fn test_mismatch() {
+        assert_sets_metadata!(APIKeyClientInterceptor::try_new("key").unwrap(), {
+            "x-api-key" => "other",
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "but it returned the metadata")]
+    fn test_unexpected_success() {
+        assert_sets_metadata!(
+            APIKeyClientInterceptor::try_new("key").unwrap(),
+            Err(Code::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// tonic::Status, the error of interceptors and services, is larger than
+// clippy::result_large_err allows
+#![allow(clippy::result_large_err)]
+
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, StreamExt};
+use http_body::{Body, Frame};
+use http_body_util::{BodyStream, StreamBody};
+use pin_project_lite::pin_project;
+use tonic::{body::BoxBody, Status};
+
+/// Client layer caching responses of idempotent unary calls
+pub mod cache;
+/// Client layer keeping per-call guards until the response completes
+pub mod call_scope;
+/// Client layer stopping calls to a failing upstream
+pub mod circuit_breaker;
+/// Server layer making the deadline of incoming calls available
+pub mod deadline;
+/// Client layer recording the endpoint of the channel in every request
+pub mod endpoint_identity;
+/// Server layer answering plain HTTP health checks
+pub mod health;
+/// Client layer sending hedged requests
+pub mod hedge;
+/// Server layer rejecting calls when the server is overloaded
+pub mod load_shed;
+/// Client layer only letting approved methods through
+pub mod method_policy;
+/// Client layers counting messages and bytes and timing calls per method
+#[cfg(feature = "metrics")]
+pub mod metrics;
+/// Client layer refreshing credentials on UNAUTHENTICATED responses
+pub mod reauth;
+/// Server layer making sure every call has a request id
+pub mod request_id;
+/// Server layer limiting the size of request bodies
+pub mod request_size;
+/// Client layer applying interceptor chains per service
+pub mod router;
+/// Client layer letting calls wait for the channel to connect
+pub mod wait_for_ready;
+
+/// A type alias for the boxed errors of bodies and services
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The boxed response future of the layers, that await more than the inner
+/// service
+pub(crate) type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T, BoxError>> + Send>>;
+
+pin_project! {
+    /// The response future of the layers, that reject some calls with a
+    /// status instead of calling the inner service
+    #[project = ResponseFutureProj]
+    pub enum ResponseFuture<F> {
+        Rejected { status: Option<Status> },
+        Inner { #[pin] future: F },
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Rejected { status } => {
+                let status = status.take().expect("polled after completion");
+                Poll::Ready(Ok(status.into_http()))
+            }
+            ResponseFutureProj::Inner { future } => {
+                let response = ready!(future.poll(cx))?;
+                Poll::Ready(Ok(response.map(tonic::body::boxed)))
+            }
+        }
+    }
+}
+
+/// Returns the number of complete gRPC messages in a buffered body, or None
+/// if the body ends within a message
+pub(crate) fn message_count(mut body: &[u8]) -> Option<usize> {
+    let mut count = 0;
+    while !body.is_empty() {
+        let header = body.get(..5)?;
+        let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        body = body.get(5 + length..)?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Reads the frames of the body, that are available without waiting
+///
+/// Returns the data and the rest of the body, if it is not complete yet,
+/// e.g. of a client streaming call.
+pub(crate) async fn buffer<B>(body: B) -> Result<(Bytes, Option<Pin<Box<B>>>), BoxError>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    let mut body = Box::pin(body);
+    let mut data = BytesMut::new();
+    let complete = poll_fn(|cx| loop {
+        match body.as_mut().poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(chunk) = frame.data_ref() {
+                    data.extend_from_slice(chunk);
+                }
+            }
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e.into())),
+            Poll::Ready(None) => return Poll::Ready(Ok(true)),
+            Poll::Pending => return Poll::Ready(Ok(false)),
+        }
+    })
+    .await?;
+    Ok((data.freeze(), (!complete).then_some(body)))
+}
+
+/// Puts the data read by [buffer] in front of the rest of the body again
+pub(crate) fn rejoin<B>(data: Bytes, rest: Pin<Box<B>>) -> BoxBody
+where
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let frames = stream::iter([Ok(Frame::data(data))])
+        .chain(BodyStream::new(rest).map(|frame| frame.map_err(Into::into)));
+    tonic::body::boxed(StreamBody::new(frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grpc::layer::message_count;
+
+    #[test]
+    fn test_message_count() {
+        const MESSAGE: [u8; 6] = [0, 0, 0, 0, 1, 42];
+
+        assert_eq!(Some(0), message_count(&[]));
+        assert_eq!(Some(1), message_count(&MESSAGE));
+        assert_eq!(Some(2), message_count(&[MESSAGE, MESSAGE].concat()));
+        assert_eq!(None, message_count(&MESSAGE[..4]));
+    }
+}
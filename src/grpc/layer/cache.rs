@@ -0,0 +1,755 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use http::{HeaderMap, HeaderName};
+use http_body::{Body, Frame, SizeHint};
+use http_body_util::{Full, StreamBody};
+use pin_project_lite::pin_project;
+use tonic::body::BoxBody;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{buffer, message_count, rejoin, BoxError, BoxFuture};
+
+/// The header added to responses served from the cache
+pub const X_CACHE: &str = "x-cache";
+/// The credential headers, that are part of the cache key by default
+pub const DEFAULT_KEY_HEADERS: [&str; 2] = ["authorization", "x-api-key"];
+
+/// The key of a cached response
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path: String,
+    /// The values of the key headers, in the order of the layer
+    headers: Vec<Option<http::HeaderValue>>,
+    /// The hash of the request message
+    body: u64,
+}
+
+struct Entry {
+    inserted_at: Instant,
+    headers: HeaderMap,
+    body: Bytes,
+    trailers: Option<HeaderMap>,
+}
+
+struct Store {
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<Key, Entry>,
+    /// Hashes the request messages with keys of this cache
+    hasher: RandomState,
+}
+
+impl Store {
+    fn get(&mut self, key: &Key) -> Option<http::Response<BoxBody>> {
+        let entry = self.entries.get(key)?;
+        if entry.inserted_at.elapsed() >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+
+        let mut headers = entry.headers.clone();
+        headers.insert(X_CACHE, http::HeaderValue::from_static("hit"));
+        Some(cached_response(
+            headers,
+            entry.body.clone(),
+            entry.trailers.clone(),
+        ))
+    }
+
+    fn insert(&mut self, key: Key, entry: Entry) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+        while self.entries.len() >= self.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        self.entries.insert(key, entry);
+    }
+}
+
+/// A handle to invalidate the entries of a [CacheLayer]
+#[derive(Clone)]
+pub struct CacheHandle {
+    store: Arc<Mutex<Store>>,
+}
+
+impl CacheHandle {
+    /// Removes all cached responses of a method
+    /// # Arguments
+    /// * `path`: The full method path, e.g. `/package.Service/GetConfig`
+    pub fn invalidate(&self, path: &str) {
+        if let Ok(mut store) = self.store.lock() {
+            store.entries.retain(|key, _| key.path != path);
+        }
+    }
+
+    /// Removes all cached responses
+    pub fn clear(&self) {
+        if let Ok(mut store) = self.store.lock() {
+            store.entries.clear();
+        }
+    }
+}
+
+/// A client layer, that caches the responses of idempotent unary calls
+///
+/// Only methods on the path allowlist are cached. The cache key is the
+/// method path, a hash of the serialized request message and the values of
+/// the key headers, by default the credentials of [DEFAULT_KEY_HEADERS]. So a
+/// response is only served to calls with the same credentials, even when
+/// several users share one client. Successful responses
+/// with exactly one message are kept for the TTL; hits are served without
+/// calling the upstream and carry an `x-cache: hit` header. Streaming
+/// requests and responses and non-OK responses are never cached. Responses
+/// are passed through as they arrive and stored, when they end.
+#[derive(Clone)]
+pub struct CacheLayer {
+    paths: Arc<HashSet<String>>,
+    key_headers: Arc<[HeaderName]>,
+    store: Arc<Mutex<Store>>,
+}
+
+impl CacheLayer {
+    /// Creates a new layer, that caches no method yet
+    /// # Arguments
+    /// * `ttl`: The time a response is served from the cache
+    /// * `max_entries`: The maximum number of cached responses; the oldest
+    ///   response is evicted when it is full
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            paths: Arc::new(HashSet::new()),
+            key_headers: DEFAULT_KEY_HEADERS
+                .into_iter()
+                .map(HeaderName::from_static)
+                .collect(),
+            store: Arc::new(Mutex::new(Store {
+                ttl,
+                max_entries: max_entries.max(1),
+                entries: HashMap::new(),
+                hasher: RandomState::new(),
+            })),
+        }
+    }
+
+    /// Allows caching for an idempotent unary method
+    /// # Arguments
+    /// * `path`: The full method path, e.g. `/package.Service/GetConfig`
+    pub fn allow_path(mut self, path: &str) -> Self {
+        Arc::make_mut(&mut self.paths).insert(path.to_string());
+        self
+    }
+
+    /// Replaces the headers, that are part of the cache key
+    ///
+    /// Responses are shared between calls, that differ in other headers
+    /// only. An empty list shares them between all credentials, which is
+    /// only safe, if the responses do not depend on the caller.
+    /// # Arguments
+    /// * `headers`: The names of the headers, e.g. `x-tenant-id`
+    pub fn with_key_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.key_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Returns a handle to invalidate cached responses
+    pub fn handle(&self) -> CacheHandle {
+        CacheHandle {
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = Cache<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cache {
+            inner,
+            paths: self.paths.clone(),
+            key_headers: self.key_headers.clone(),
+            store: self.store.clone(),
+        }
+    }
+}
+
+/// The service created by the [CacheLayer]
+#[derive(Clone)]
+pub struct Cache<S> {
+    inner: S,
+    paths: Arc<HashSet<String>>,
+    key_headers: Arc<[HeaderName]>,
+    store: Arc<Mutex<Store>>,
+}
+
+fn cached_response(
+    headers: HeaderMap,
+    body: Bytes,
+    trailers: Option<HeaderMap>,
+) -> http::Response<BoxBody> {
+    let mut frames = vec![Ok::<_, BoxError>(Frame::data(body))];
+    frames.extend(trailers.map(|trailers| Ok(Frame::trailers(trailers))));
+    let mut response = http::Response::new(tonic::body::boxed(StreamBody::new(
+        futures_util::stream::iter(frames),
+    )));
+    *response.headers_mut() = headers;
+    response
+}
+
+fn is_ok(headers: &HeaderMap, trailers: Option<&HeaderMap>) -> bool {
+    trailers
+        .and_then(|trailers| trailers.get("grpc-status"))
+        .or_else(|| headers.get("grpc-status"))
+        .is_some_and(|status| status == "0")
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/grpc"))
+}
+
+/// A response on its way to the cache
+struct Pending {
+    key: Key,
+    headers: HeaderMap,
+    store: Arc<Mutex<Store>>,
+}
+
+pin_project! {
+    /// A response body, that is passed through and stored in the cache, if
+    /// it ends with one message and an OK status
+    struct Recording<B> {
+        #[pin]
+        inner: B,
+        // The data so far, or `None`, once the response can not be cached
+        data: Option<BytesMut>,
+        pending: Option<Pending>,
+    }
+}
+
+impl<B> Recording<B> {
+    fn record(data: &mut Option<BytesMut>, chunk: &[u8]) {
+        if let Some(buffer) = data {
+            buffer.extend_from_slice(chunk);
+            // Stop at the second message of a streaming response
+            if starts_second(buffer) {
+                *data = None;
+            }
+        }
+    }
+
+    fn finish(
+        data: &mut Option<BytesMut>,
+        pending: &mut Option<Pending>,
+        trailers: Option<&HeaderMap>,
+    ) {
+        let (Some(data), Some(pending)) = (data.take(), pending.take()) else {
+            return;
+        };
+        let body = data.freeze();
+        if is_ok(&pending.headers, trailers)
+            && message_count(&body) == Some(1)
+            && let Ok(mut store) = pending.store.lock()
+        {
+            store.insert(
+                pending.key,
+                Entry {
+                    inserted_at: Instant::now(),
+                    headers: pending.headers,
+                    body,
+                    trailers: trailers.cloned(),
+                },
+            );
+        }
+    }
+}
+
+/// Returns, whether the data is longer than its first message
+fn starts_second(data: &[u8]) -> bool {
+    data.get(1..5).is_some_and(|length| {
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        data.len() > 5 + length
+    })
+}
+
+impl<B> Body for Recording<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(chunk) = frame.data_ref() {
+                    Self::record(this.data, chunk);
+                } else if let Some(trailers) = frame.trailers_ref() {
+                    Self::finish(this.data, this.pending, Some(trailers));
+                }
+            }
+            Some(Err(_)) => *this.data = None,
+            None => Self::finish(this.data, this.pending, None),
+        }
+        Poll::Ready(frame.map(|frame| frame.map_err(Into::into)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for Cache<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Self::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        // Use the service, that was driven to readiness
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let path = req.uri().path().to_string();
+        if !self.paths.contains(&path) {
+            let future = inner.call(req.map(tonic::body::boxed));
+            return Box::pin(async move {
+                Ok(future.await.map_err(Into::into)?.map(tonic::body::boxed))
+            });
+        }
+
+        let headers = self
+            .key_headers
+            .iter()
+            .map(|name| req.headers().get(name).cloned())
+            .collect();
+        let store = self.store.clone();
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            // A client streaming call may still be sending, so only take the
+            // frames, that are there
+            let (body, rest) = buffer(body).await?;
+            let cacheable = rest.is_none() && message_count(&body) == Some(1);
+            let key = Key {
+                path,
+                headers,
+                body: store
+                    .lock()
+                    .map(|store| store.hasher.hash_one(&body))
+                    .unwrap_or_default(),
+            };
+
+            if cacheable {
+                let hit = store.lock().ok().and_then(|mut store| store.get(&key));
+                if let Some(response) = hit {
+                    log::debug!("Cache hit for {}", key.path);
+                    return Ok(response);
+                }
+            }
+
+            let body = match rest {
+                Some(rest) => rejoin(body, rest),
+                None => tonic::body::boxed(Full::new(body)),
+            };
+            let req = http::Request::from_parts(parts, body);
+            let response = inner.call(req).await.map_err(Into::into)?;
+            if !cacheable || !is_grpc(response.headers()) {
+                return Ok(response.map(tonic::body::boxed));
+            }
+
+            let pending = Pending {
+                key,
+                headers: response.headers().clone(),
+                store,
+            };
+            Ok(response.map(|body| {
+                tonic::body::boxed(Recording {
+                    inner: body,
+                    data: Some(BytesMut::new()),
+                    pending: Some(pending),
+                })
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use http::HeaderMap;
+    use http_body::Frame;
+    use http_body_util::{BodyExt, Full, StreamBody};
+    use tokio::sync::mpsc;
+    use tonic::body::BoxBody;
+    use tonic::Status;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::cache::{cached_response, CacheLayer, X_CACHE};
+
+    const REQUEST: [u8; 6] = [0, 0, 0, 0, 1, 1];
+    const OTHER_REQUEST: [u8; 6] = [0, 0, 0, 0, 1, 2];
+
+    fn grpc_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/grpc".parse().unwrap());
+        headers
+    }
+
+    /// A body sending the frames and staying open
+    fn open_body(frames: Vec<Bytes>) -> (BoxBody, mpsc::Sender<Result<Frame<Bytes>, Status>>) {
+        let (tx, mut rx) = mpsc::channel(4);
+        for frame in frames {
+            tx.try_send(Ok(Frame::data(frame))).unwrap();
+        }
+        let body = StreamBody::new(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)));
+        (tonic::body::boxed(body), tx)
+    }
+
+    /// An upstream answering with the number of the call, failing for the
+    /// other request
+    fn upstream(
+        calls: Arc<AtomicUsize>,
+    ) -> impl Service<
+        http::Request<BoxBody>,
+        Response = http::Response<BoxBody>,
+        Error = Infallible,
+        Future = impl Send,
+    > + Clone
+           + Send
+           + 'static {
+        tower::service_fn(move |req: http::Request<BoxBody>| {
+            let call = calls.fetch_add(1, Ordering::SeqCst) as u8 + 1;
+            async move {
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                let status = match body.as_ref() == OTHER_REQUEST {
+                    true => "5",
+                    false => "0",
+                };
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", status.parse().unwrap());
+                Ok(cached_response(
+                    grpc_headers(),
+                    Bytes::from(vec![0, 0, 0, 0, 1, call]),
+                    Some(trailers),
+                ))
+            }
+        })
+    }
+
+    /// Calls the service, returning the number of the upstream call and
+    /// whether it was a cache hit
+    async fn call<S>(service: &mut S, path: &str, body: &[u8]) -> (u8, bool)
+    where
+        S: Service<http::Request<Full<Bytes>>, Response = http::Response<BoxBody>>,
+        S::Error: std::fmt::Debug,
+    {
+        call_as(service, path, body, None).await
+    }
+
+    /// Calls the service like [call] with the authorization header
+    async fn call_as<S>(
+        service: &mut S,
+        path: &str,
+        body: &[u8],
+        authorization: Option<&str>,
+    ) -> (u8, bool)
+    where
+        S: Service<http::Request<Full<Bytes>>, Response = http::Response<BoxBody>>,
+        S::Error: std::fmt::Debug,
+    {
+        let mut req = http::Request::builder().uri(path);
+        if let Some(authorization) = authorization {
+            req = req.header("authorization", authorization);
+        }
+        let req = req.body(Full::new(Bytes::copy_from_slice(body))).unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        let hit = response.headers().get(X_CACHE).is_some();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (body[5], hit)
+    }
+
+    #[tokio::test]
+    async fn test_hit_and_miss() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream(calls.clone()));
+
+        assert_eq!(
+            (1, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+        assert_eq!(
+            (1, true),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        // A different request message is a different entry
+        let third = [0, 0, 0, 0, 1, 3];
+        assert_eq!(
+            (2, false),
+            call(&mut service, "/test.Service/Get", &third).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ttl() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut service = CacheLayer::new(Duration::from_millis(20), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream(calls.clone()));
+
+        assert_eq!(
+            (1, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(
+            (2, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bypass() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream(calls.clone()));
+
+        // Mutating methods are not on the allowlist
+        assert_eq!(
+            (1, false),
+            call(&mut service, "/test.Service/Update", &REQUEST).await
+        );
+        assert_eq!(
+            (2, false),
+            call(&mut service, "/test.Service/Update", &REQUEST).await
+        );
+
+        // Errors are not cached
+        assert_eq!(
+            (3, false),
+            call(&mut service, "/test.Service/Get", &OTHER_REQUEST).await
+        );
+        assert_eq!(
+            (4, false),
+            call(&mut service, "/test.Service/Get", &OTHER_REQUEST).await
+        );
+
+        // Streaming requests are not cached
+        let two = [REQUEST, REQUEST].concat();
+        assert_eq!(
+            (5, false),
+            call(&mut service, "/test.Service/Get", &two).await
+        );
+        assert_eq!(
+            (6, false),
+            call(&mut service, "/test.Service/Get", &two).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_and_eviction() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let layer = CacheLayer::new(Duration::from_secs(60), 1).allow_path("/test.Service/Get");
+        let handle = layer.handle();
+        let mut service = layer.layer(upstream(calls.clone()));
+
+        assert_eq!(
+            (1, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+        handle.invalidate("/test.Service/Get");
+        assert_eq!(
+            (2, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+        assert_eq!(
+            (2, true),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+
+        // The cache holds only one entry, so the first one is evicted
+        let third = [0, 0, 0, 0, 1, 3];
+        assert_eq!(
+            (3, false),
+            call(&mut service, "/test.Service/Get", &third).await
+        );
+        assert_eq!(
+            (4, false),
+            call(&mut service, "/test.Service/Get", &REQUEST).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_key_headers() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream(calls.clone()));
+
+        let path = "/test.Service/Get";
+        let alice = Some("Bearer alice");
+        let bob = Some("Bearer bob");
+        assert_eq!(
+            (1, false),
+            call_as(&mut service, path, &REQUEST, alice).await
+        );
+        assert_eq!(
+            (1, true),
+            call_as(&mut service, path, &REQUEST, alice).await
+        );
+        // Other credentials never see the response of alice
+        assert_eq!((2, false), call_as(&mut service, path, &REQUEST, bob).await);
+        assert_eq!(
+            (3, false),
+            call_as(&mut service, path, &REQUEST, None).await
+        );
+
+        // Without key headers, all callers share the responses
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path(path)
+            .with_key_headers([])
+            .layer(upstream(calls.clone()));
+        assert_eq!(
+            (4, false),
+            call_as(&mut service, path, &REQUEST, alice).await
+        );
+        assert_eq!((4, true), call_as(&mut service, path, &REQUEST, bob).await);
+    }
+
+    #[tokio::test]
+    async fn test_client_streaming_passes_through() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let upstream = tower::service_fn(move |req: http::Request<BoxBody>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                // Reads the first message, while the client keeps streaming
+                let mut body = req.into_body();
+                let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+                Ok::<_, Infallible>(cached_response(grpc_headers(), first, None))
+            }
+        });
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream);
+
+        for _ in 0..2 {
+            let (body, _sender) = open_body(vec![Bytes::from_static(&REQUEST)]);
+            let req = http::Request::builder()
+                .uri("/test.Service/Get")
+                .body(body)
+                .unwrap();
+            let response = tokio::time::timeout(
+                Duration::from_secs(1),
+                ServiceExt::<http::Request<BoxBody>>::ready(&mut service)
+                    .await
+                    .unwrap()
+                    .call(req),
+            )
+            .await
+            .expect("the call must not wait for the end of the request")
+            .unwrap();
+            assert!(response.headers().get(X_CACHE).is_none());
+        }
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_server_streaming_passes_through() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let (sender, mut senders) = mpsc::unbounded_channel();
+        let upstream = tower::service_fn(move |_: http::Request<BoxBody>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let messages = vec![Bytes::from_static(&REQUEST), Bytes::from_static(&REQUEST)];
+            let (body, tx) = open_body(messages);
+            sender.send(tx).unwrap();
+            let mut response = http::Response::new(body);
+            *response.headers_mut() = grpc_headers();
+            async move { Ok::<_, Infallible>(response) }
+        });
+        let mut service = CacheLayer::new(Duration::from_secs(60), 10)
+            .allow_path("/test.Service/Get")
+            .layer(upstream);
+
+        let response = call_raw(&mut service).await;
+        let mut body = response.into_body();
+        let first = tokio::time::timeout(Duration::from_secs(1), body.frame())
+            .await
+            .expect("the messages must arrive before the end of the response");
+        assert_eq!(
+            REQUEST.as_ref(),
+            first.unwrap().unwrap().data_ref().unwrap()
+        );
+        let tx = senders.recv().await.unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        tx.send(Ok(Frame::trailers(trailers))).await.unwrap();
+        drop(tx);
+        while body.frame().await.is_some() {}
+
+        // Two messages are never served from the cache
+        let response = call_raw(&mut service).await;
+        assert!(response.headers().get(X_CACHE).is_none());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    async fn call_raw<S>(service: &mut S) -> http::Response<BoxBody>
+    where
+        S: Service<http::Request<Full<Bytes>>, Response = http::Response<BoxBody>>,
+        S::Error: std::fmt::Debug,
+    {
+        let req = http::Request::builder()
+            .uri("/test.Service/Get")
+            .body(Full::new(Bytes::from_static(&REQUEST)))
+            .unwrap();
+        service.ready().await.unwrap().call(req).await.unwrap()
+    }
+}
@@ -0,0 +1,269 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use tonic::body::BoxBody;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+/// The guards of a call, released when the call completes
+///
+/// Interceptors attach guards, like the permit of a rate limiter, with
+/// [CallScope::attach]. With the [CallScopeLayer] below the interceptors,
+/// the guards are dropped when the response body ends, fails or is dropped.
+/// Without the layer they are dropped with the request, right after it is
+/// sent.
+///
+/// For streaming calls the response body lives until the last message is
+/// received, so the guards are held for the whole stream, even though the
+/// request was sent long before.
+#[derive(Clone, Default)]
+pub struct CallScope {
+    guards: Arc<Mutex<Vec<Box<dyn Send>>>>,
+}
+
+impl CallScope {
+    /// Attaches a guard to the call of the request
+    /// # Arguments
+    /// * `req`: The request, e.g. in an interceptor
+    /// * `guard`: The value dropped at the end of the call
+    pub fn attach<T>(req: &mut tonic::Request<T>, guard: impl Send + 'static) {
+        let extensions = req.extensions_mut();
+        if extensions.get::<CallScope>().is_none() {
+            extensions.insert(CallScope::default());
+        }
+        if let Some(scope) = extensions.get::<CallScope>() {
+            scope.push(Box::new(guard));
+        }
+    }
+
+    fn push(&self, guard: Box<dyn Send>) {
+        self.guards
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(guard);
+    }
+
+    /// Returns the number of attached guards
+    pub fn len(&self) -> usize {
+        self.guards.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true, if no guards are attached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A client layer, that keeps the guards of the [CallScope] of a request
+/// until the response completes
+///
+/// Add it below the interceptors, so it sees the extensions they set, e.g.
+/// `InterceptedService::new(CallScopeLayer::new().layer(channel), interceptor)`.
+#[derive(Clone, Debug, Default)]
+pub struct CallScopeLayer;
+
+impl CallScopeLayer {
+    /// Creates a new layer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for CallScopeLayer {
+    type Service = CallScopeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CallScopeService { inner }
+    }
+}
+
+/// The service created by the [CallScopeLayer]
+#[derive(Clone, Debug)]
+pub struct CallScopeService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for CallScopeService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let scope = req.extensions_mut().remove::<CallScope>();
+        ResponseFuture {
+            future: self.inner.call(req),
+            scope,
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [CallScopeService]
+    pub struct ResponseFuture<F> {
+        #[pin]
+        future: F,
+        scope: Option<CallScope>,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        // A failed call drops the scope with the future
+        let response = ready!(this.future.poll(cx))?;
+        let scope = this.scope.take();
+        Poll::Ready(Ok(
+            response.map(|inner| tonic::body::boxed(ScopedBody { inner, scope }))
+        ))
+    }
+}
+
+pin_project! {
+    /// A body, that drops the scope when it ends
+    struct ScopedBody<B> {
+        #[pin]
+        inner: B,
+        scope: Option<CallScope>,
+    }
+}
+
+impl<B> Body for ScopedBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx)).map(|frame| frame.map_err(Into::into));
+        let ended = match &frame {
+            Some(Ok(frame)) => frame.is_trailers(),
+            Some(Err(_)) | None => true,
+        };
+        if ended {
+            this.scope.take();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use http_body_util::BodyExt;
+    use tonic::body::BoxBody;
+    use tonic::service::interceptor::InterceptedService;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::call_scope::{CallScope, CallScopeLayer};
+
+    /// A rate limit permit, counting how many are held
+    struct Permit(Arc<AtomicUsize>);
+
+    impl Permit {
+        fn acquire(held: &Arc<AtomicUsize>) -> Self {
+            held.fetch_add(1, Ordering::SeqCst);
+            Self(held.clone())
+        }
+    }
+
+    impl Drop for Permit {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_release_after_response() {
+        let held = Arc::new(AtomicUsize::new(0));
+        let acquired = held.clone();
+        let seen = held.clone();
+        let service =
+            CallScopeLayer::new().layer(tower::service_fn(move |req: http::Request<BoxBody>| {
+                let seen = seen.clone();
+                async move {
+                    assert_eq!(
+                        None,
+                        req.extensions().get::<CallScope>().map(CallScope::len)
+                    );
+                    assert_eq!(1, seen.load(Ordering::SeqCst));
+                    Ok::<_, Infallible>(tonic::Status::ok("").into_http())
+                }
+            }));
+        let mut service = InterceptedService::new(service, move |mut req: tonic::Request<()>| {
+            CallScope::attach(&mut req, Permit::acquire(&acquired));
+            Ok(req)
+        });
+
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(http::Request::new(tonic::body::empty_body()))
+            .await
+            .unwrap();
+        // The headers arrived, the body is still open
+        assert_eq!(1, held.load(Ordering::SeqCst));
+        response.into_body().collect().await.unwrap();
+        assert_eq!(0, held.load(Ordering::SeqCst));
+
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(http::Request::new(tonic::body::empty_body()))
+            .await
+            .unwrap();
+        assert_eq!(1, held.load(Ordering::SeqCst));
+        drop(response);
+        assert_eq!(0, held.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut req = tonic::Request::new(());
+        CallScope::attach(&mut req, 1);
+        CallScope::attach(&mut req, "second");
+        assert_eq!(2, req.extensions().get::<CallScope>().unwrap().len());
+    }
+}
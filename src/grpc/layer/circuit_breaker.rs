@@ -0,0 +1,545 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+/// The state of a circuit breaker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls are passed through and their outcome is recorded
+    Closed,
+    /// Calls are rejected without reaching the upstream
+    Open,
+    /// A limited number of probe calls are let through
+    HalfOpen,
+}
+
+/// A function called with the new state on every state transition
+pub type StateListener = Arc<dyn Fn(CircuitState) + Send + Sync>;
+
+#[derive(Clone, Debug)]
+struct BreakerConfig {
+    consecutive_failures: usize,
+    failure_rate: f64,
+    window_size: usize,
+    min_calls: usize,
+    open_duration: Duration,
+    half_open_probes: usize,
+    failure_codes: Vec<Code>,
+}
+
+struct Breaker {
+    state: CircuitState,
+    opened_at: Instant,
+    window: VecDeque<bool>,
+    consecutive_failures: usize,
+    probes_in_flight: usize,
+    probe_successes: usize,
+}
+
+impl Breaker {
+    fn transition(&mut self, state: CircuitState) -> Option<CircuitState> {
+        if self.state == state {
+            return None;
+        }
+        log::info!(
+            "Circuit breaker changed from {:?} to {:?}",
+            self.state,
+            state
+        );
+        self.state = state;
+        self.window.clear();
+        self.consecutive_failures = 0;
+        self.probe_successes = 0;
+        if state == CircuitState::Open {
+            self.opened_at = Instant::now();
+        }
+        Some(state)
+    }
+
+    /// Returns whether the call is a probe, or None if it is rejected
+    fn acquire(&mut self, config: &BreakerConfig) -> (Option<bool>, Option<CircuitState>) {
+        let mut changed = None;
+        if self.state == CircuitState::Open {
+            if self.opened_at.elapsed() < config.open_duration {
+                return (None, None);
+            }
+            changed = self.transition(CircuitState::HalfOpen);
+        }
+
+        match self.state {
+            CircuitState::Closed => (Some(false), changed),
+            _ if self.probes_in_flight < config.half_open_probes => {
+                self.probes_in_flight += 1;
+                (Some(true), changed)
+            }
+            _ => (None, changed),
+        }
+    }
+
+    fn record(
+        &mut self,
+        config: &BreakerConfig,
+        probe: bool,
+        success: bool,
+    ) -> Option<CircuitState> {
+        if probe {
+            self.probes_in_flight -= 1;
+            if self.state != CircuitState::HalfOpen {
+                return None;
+            }
+            if !success {
+                return self.transition(CircuitState::Open);
+            }
+            self.probe_successes += 1;
+            if self.probe_successes >= config.half_open_probes {
+                return self.transition(CircuitState::Closed);
+            }
+            return None;
+        }
+
+        // Outcomes of calls started before the circuit opened are ignored
+        if self.state != CircuitState::Closed {
+            return None;
+        }
+        self.consecutive_failures = if success {
+            0
+        } else {
+            self.consecutive_failures + 1
+        };
+        self.window.push_back(success);
+        if self.window.len() > config.window_size {
+            self.window.pop_front();
+        }
+
+        let failures = self.window.iter().filter(|success| !**success).count();
+        let rate_exceeded = self.window.len() >= config.min_calls
+            && failures as f64 / self.window.len() as f64 >= config.failure_rate;
+        if self.consecutive_failures >= config.consecutive_failures || rate_exceeded {
+            return self.transition(CircuitState::Open);
+        }
+        None
+    }
+}
+
+#[derive(Clone)]
+struct Shared {
+    config: Arc<BreakerConfig>,
+    breaker: Arc<Mutex<Breaker>>,
+    listener: Option<StateListener>,
+}
+
+impl Shared {
+    fn with_breaker<T>(
+        &self,
+        f: impl FnOnce(&mut Breaker, &BreakerConfig) -> (T, Option<CircuitState>),
+    ) -> T {
+        let (result, changed) = match self.breaker.lock() {
+            Ok(mut breaker) => f(&mut breaker, &self.config),
+            Err(mut poisoned) => f(poisoned.get_mut(), &self.config),
+        };
+        if let (Some(state), Some(listener)) = (changed, &self.listener) {
+            listener(state);
+        }
+        result
+    }
+}
+
+/// A client layer, that stops calling a failing upstream
+///
+/// The breaker is closed at first. It opens after a number of consecutive
+/// failures, or when the failure rate over the last calls exceeds a
+/// threshold. While it is open, calls fail immediately with
+/// `Status::unavailable("circuit open")`. After the open duration, it lets
+/// probe calls through (half-open) and closes again when they succeed.
+///
+/// Transport errors and responses with one of the failure codes count as
+/// failures. The code is taken from the response headers, so it covers
+/// errors of unary calls and all calls failing before the first message.
+/// A status in the trailers of a streamed response is not inspected.
+/// All services created by one layer share the breaker state.
+#[derive(Clone)]
+pub struct CircuitBreakerLayer {
+    config: BreakerConfig,
+    breaker: Arc<Mutex<Breaker>>,
+    listener: Option<StateListener>,
+}
+
+impl Default for CircuitBreakerLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreakerLayer {
+    /// Creates a new layer
+    ///
+    /// It opens after 5 consecutive failures or a failure rate of 50% over
+    /// the last 20 calls (with at least 10 calls), stays open for 30 seconds
+    /// and closes after one successful probe. Only `UNAVAILABLE` responses
+    /// and transport errors count as failures.
+    pub fn new() -> Self {
+        Self::with_config(BreakerConfig {
+            consecutive_failures: 5,
+            failure_rate: 0.5,
+            window_size: 20,
+            min_calls: 10,
+            open_duration: Duration::from_secs(30),
+            half_open_probes: 1,
+            failure_codes: vec![Code::Unavailable],
+        })
+    }
+
+    fn with_config(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            breaker: Arc::new(Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                opened_at: Instant::now(),
+                window: VecDeque::new(),
+                consecutive_failures: 0,
+                probes_in_flight: 0,
+                probe_successes: 0,
+            })),
+            listener: None,
+        }
+    }
+
+    fn configure(mut self, f: impl FnOnce(&mut BreakerConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// Sets the number of consecutive failures, that open the breaker
+    pub fn with_consecutive_failures(self, failures: usize) -> Self {
+        self.configure(|config| config.consecutive_failures = failures.max(1))
+    }
+
+    /// Sets the failure rate, that opens the breaker
+    /// # Arguments
+    /// * `failure_rate`: The ratio of failed calls between 0.0 and 1.0
+    /// * `window_size`: The number of recent calls the rate is computed over
+    /// * `min_calls`: The minimum number of calls before the rate is applied
+    pub fn with_failure_rate(
+        self,
+        failure_rate: f64,
+        window_size: usize,
+        min_calls: usize,
+    ) -> Self {
+        self.configure(|config| {
+            config.failure_rate = failure_rate;
+            config.window_size = window_size.max(1);
+            config.min_calls = min_calls.clamp(1, config.window_size);
+        })
+    }
+
+    /// Sets the duration the breaker stays open before probing
+    pub fn with_open_duration(self, open_duration: Duration) -> Self {
+        self.configure(|config| config.open_duration = open_duration)
+    }
+
+    /// Sets the number of successful probes needed to close the breaker
+    pub fn with_half_open_probes(self, probes: usize) -> Self {
+        self.configure(|config| config.half_open_probes = probes.max(1))
+    }
+
+    /// Sets the gRPC status codes, that count as failures
+    pub fn with_failure_codes(self, codes: Vec<Code>) -> Self {
+        self.configure(|config| config.failure_codes = codes)
+    }
+
+    /// Sets a function, that is called on every state transition
+    pub fn on_state_change(
+        mut self,
+        listener: impl Fn(CircuitState) + Send + Sync + 'static,
+    ) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Returns the current state of the breaker
+    pub fn state(&self) -> CircuitState {
+        match self.breaker.lock() {
+            Ok(breaker) => breaker.state,
+            Err(poisoned) => poisoned.get_ref().state,
+        }
+    }
+}
+
+impl<S> Layer<S> for CircuitBreakerLayer {
+    type Service = CircuitBreaker<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreaker {
+            inner,
+            shared: Shared {
+                config: Arc::new(self.config.clone()),
+                breaker: self.breaker.clone(),
+                listener: self.listener.clone(),
+            },
+        }
+    }
+}
+
+/// The service created by the [CircuitBreakerLayer]
+#[derive(Clone)]
+pub struct CircuitBreaker<S> {
+    inner: S,
+    shared: Shared,
+}
+
+/// Records the outcome of a call, and releases the probe if it is dropped
+struct Permit {
+    shared: Shared,
+    probe: bool,
+    recorded: bool,
+}
+
+impl Permit {
+    fn record(mut self, success: bool) {
+        self.recorded = true;
+        let probe = self.probe;
+        self.shared
+            .with_breaker(|breaker, config| ((), breaker.record(config, probe, success)));
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if !self.recorded && self.probe {
+            self.shared.with_breaker(|breaker, _| {
+                breaker.probes_in_flight -= 1;
+                ((), None)
+            });
+        }
+    }
+}
+
+/// Returns the code of the response headers, which is `OK` for a response
+/// with a body, as its status follows in the trailers
+fn response_code<B>(response: &http::Response<B>) -> Code {
+    response
+        .headers()
+        .get("grpc-status")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i32>().ok())
+        .map(Code::from)
+        .unwrap_or(Code::Ok)
+}
+
+impl<S, Request, ResBody> Service<Request> for CircuitBreaker<S>
+where
+    S: Service<Request, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+{
+    type Response = http::Response<ResBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let Some(probe) = self
+            .shared
+            .with_breaker(|breaker, config| breaker.acquire(config))
+        else {
+            return Box::pin(async {
+                Err(Box::new(Status::unavailable("circuit open")) as BoxError)
+            });
+        };
+        let permit = Permit {
+            shared: self.shared.clone(),
+            probe,
+            recorded: false,
+        };
+        let config = self.shared.config.clone();
+
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            match future.await {
+                Ok(response) => {
+                    permit.record(!config.failure_codes.contains(&response_code(&response)));
+                    Ok(response)
+                }
+                Err(e) => {
+                    permit.record(false);
+                    Err(e.into())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::circuit_breaker::{CircuitBreakerLayer, CircuitState};
+    use crate::grpc::layer::BoxError;
+
+    /// An upstream answering with the scripted codes, counting its calls
+    #[derive(Clone, Default)]
+    struct Scripted {
+        script: Arc<Mutex<VecDeque<Option<Code>>>>,
+        calls: Arc<Mutex<usize>>,
+    }
+
+    impl Scripted {
+        fn push(&self, outcomes: &[Option<Code>]) {
+            self.script.lock().unwrap().extend(outcomes);
+        }
+
+        fn calls(&self) -> usize {
+            *self.calls.lock().unwrap()
+        }
+    }
+
+    impl Service<()> for Scripted {
+        type Response = http::Response<()>;
+        type Error = BoxError;
+        type Future = std::future::Ready<Result<Self::Response, BoxError>>;
+
+        fn poll_ready(
+            &mut self,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), BoxError>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            *self.calls.lock().unwrap() += 1;
+            let outcome = self
+                .script
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Some(Code::Ok));
+            std::future::ready(match outcome {
+                Some(code) => Ok(http::Response::builder()
+                    .header("grpc-status", code as i32)
+                    .body(())
+                    .unwrap()),
+                None => Err("connection refused".into()),
+            })
+        }
+    }
+
+    async fn call(service: &mut impl Service<(), Error = BoxError>) -> Result<(), BoxError> {
+        service.ready().await?.call(()).await.map(|_| ())
+    }
+
+    fn layer(transitions: Arc<Mutex<Vec<CircuitState>>>) -> CircuitBreakerLayer {
+        CircuitBreakerLayer::new()
+            .with_consecutive_failures(2)
+            .with_open_duration(Duration::from_millis(50))
+            .on_state_change(move |state| transitions.lock().unwrap().push(state))
+    }
+
+    #[tokio::test]
+    async fn test_all_states() {
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let layer = layer(transitions.clone());
+        let upstream = Scripted::default();
+        let mut service = layer.layer(upstream.clone());
+
+        upstream.push(&[Some(Code::Ok), None, Some(Code::Unavailable)]);
+        for _ in 0..3 {
+            let _ = call(&mut service).await;
+        }
+        assert_eq!(CircuitState::Open, layer.state());
+
+        let error = call(&mut service).await.unwrap_err();
+        let status = error.downcast::<Status>().unwrap();
+        assert_eq!(Code::Unavailable, status.code());
+        assert_eq!("circuit open", status.message());
+        assert_eq!(3, upstream.calls());
+
+        // A failed probe opens the breaker again
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        upstream.push(&[Some(Code::Unavailable)]);
+        assert!(call(&mut service).await.is_ok());
+        assert_eq!(CircuitState::Open, layer.state());
+
+        // A successful probe closes it
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(call(&mut service).await.is_ok());
+        assert_eq!(CircuitState::Closed, layer.state());
+        assert_eq!(5, upstream.calls());
+
+        assert_eq!(
+            vec![
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+                CircuitState::Closed,
+            ],
+            *transitions.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failure_rate() {
+        let layer = CircuitBreakerLayer::new().with_failure_rate(0.5, 4, 4);
+        let upstream = Scripted::default();
+        let mut service = layer.layer(upstream.clone());
+
+        upstream.push(&[
+            Some(Code::Unavailable),
+            Some(Code::Ok),
+            Some(Code::NotFound),
+            Some(Code::Unavailable),
+        ]);
+        for _ in 0..3 {
+            let _ = call(&mut service).await;
+            assert_eq!(CircuitState::Closed, layer.state());
+        }
+        let _ = call(&mut service).await;
+        assert_eq!(CircuitState::Open, layer.state());
+    }
+
+    #[tokio::test]
+    async fn test_failure_codes() {
+        let layer = CircuitBreakerLayer::new()
+            .with_consecutive_failures(1)
+            .with_failure_codes(vec![Code::Internal]);
+        let upstream = Scripted::default();
+        let mut service = layer.layer(upstream.clone());
+
+        upstream.push(&[Some(Code::Unavailable), Some(Code::Internal)]);
+        let _ = call(&mut service).await;
+        assert_eq!(CircuitState::Closed, layer.state());
+        let _ = call(&mut service).await;
+        assert_eq!(CircuitState::Open, layer.state());
+    }
+
+    #[tokio::test]
+    async fn test_configure_after_layer() {
+        let layer = CircuitBreakerLayer::new().with_consecutive_failures(1);
+        let upstream = Scripted::default();
+        let mut service = layer.layer(upstream.clone());
+
+        // Configuring a clone leaves the existing services alone
+        let _other = layer.clone().with_consecutive_failures(3);
+        upstream.push(&[Some(Code::Unavailable)]);
+        let _ = call(&mut service).await;
+        assert_eq!(CircuitState::Open, layer.state());
+    }
+}
@@ -0,0 +1,179 @@
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::task::futures::TaskLocalFuture;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The header carrying the timeout of a gRPC call
+pub const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+tokio::task_local! {
+    static DEADLINE: Option<DeadlineContext>;
+}
+
+/// The absolute deadline of the call a server is handling
+///
+/// The [DeadlineLayer] sets it for the task running the handler and inserts
+/// it into the request extensions. The
+/// [DeadlinePropagationInterceptor](crate::grpc::interceptor::deadline::DeadlinePropagationInterceptor)
+/// reads it to set the `grpc-timeout` of downstream calls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineContext {
+    /// The point in time, when the caller gives up
+    pub deadline: Instant,
+}
+
+impl DeadlineContext {
+    /// Creates a context from the remaining time
+    /// # Arguments
+    /// * `timeout`: The time from now until the deadline
+    pub fn from_timeout(timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    /// Returns the context of the current task, if there is one
+    pub fn current() -> Option<Self> {
+        DEADLINE.try_with(|context| *context).ok().flatten()
+    }
+
+    /// Returns the time until the deadline, zero if it has passed
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Runs the future with this context as the current one
+    /// # Arguments
+    /// * `future`: The future, e.g. a spawned part of a handler
+    pub fn scope<F: Future>(self, future: F) -> TaskLocalFuture<Option<Self>, F> {
+        DEADLINE.scope(Some(self), future)
+    }
+}
+
+/// Parses a `grpc-timeout` header value, e.g. `100m` for 100 milliseconds
+pub(crate) fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 || !value.is_ascii() {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+/// A server layer, that makes the deadline of incoming calls available
+///
+/// The `grpc-timeout` header of the request is turned into a
+/// [DeadlineContext], that is inserted into the request extensions and set
+/// as the current context while the handler runs. Tasks spawned by the
+/// handler do not inherit it; wrap them with [DeadlineContext::scope].
+///
+/// Use it with `tonic::transport::Server::builder().layer(...)`.
+#[derive(Clone, Debug, Default)]
+pub struct DeadlineLayer;
+
+impl DeadlineLayer {
+    /// Creates a new layer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for DeadlineLayer {
+    type Service = Deadline<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Deadline { inner }
+    }
+}
+
+/// The service created by the [DeadlineLayer]
+#[derive(Clone, Debug)]
+pub struct Deadline<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for Deadline<S>
+where
+    S: Service<http::Request<ReqBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TaskLocalFuture<Option<DeadlineContext>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let context = req
+            .headers()
+            .get(GRPC_TIMEOUT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_grpc_timeout)
+            .map(DeadlineContext::from_timeout);
+        if let Some(context) = context {
+            req.extensions_mut().insert(context);
+        }
+        DEADLINE.scope(context, self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::deadline::{parse_grpc_timeout, DeadlineContext, DeadlineLayer};
+
+    #[test]
+    fn test_parse_grpc_timeout() {
+        assert_eq!(Some(Duration::from_millis(100)), parse_grpc_timeout("100m"));
+        assert_eq!(Some(Duration::from_secs(7200)), parse_grpc_timeout("2H"));
+        assert_eq!(Some(Duration::from_micros(5)), parse_grpc_timeout("5u"));
+        assert_eq!(None, parse_grpc_timeout("m"));
+        assert_eq!(None, parse_grpc_timeout("123456789m"));
+        assert_eq!(None, parse_grpc_timeout("-1m"));
+        assert_eq!(None, parse_grpc_timeout("10x"));
+    }
+
+    #[tokio::test]
+    async fn test_layer_sets_context() {
+        let mut service =
+            DeadlineLayer::new().layer(tower::service_fn(|req: http::Request<()>| async move {
+                let current = DeadlineContext::current();
+                assert_eq!(current.as_ref(), req.extensions().get::<DeadlineContext>());
+                Ok::<_, Infallible>(current.map(|context| context.remaining()))
+            }));
+
+        let req = http::Request::builder()
+            .header("grpc-timeout", "500m")
+            .body(())
+            .unwrap();
+        let remaining = service.ready().await.unwrap().call(req).await.unwrap();
+        let remaining = remaining.unwrap();
+        assert!(remaining <= Duration::from_millis(500));
+        assert!(remaining > Duration::from_millis(400));
+
+        let req = http::Request::new(());
+        let remaining = service.ready().await.unwrap().call(req).await.unwrap();
+        assert_eq!(None, remaining);
+        assert_eq!(None, DeadlineContext::current());
+    }
+}
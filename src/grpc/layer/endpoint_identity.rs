@@ -0,0 +1,82 @@
+use std::task::{Context, Poll};
+
+use http::uri::Authority;
+use tonic::transport::Endpoint;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The authority of the endpoint a channel is connected to, e.g.
+/// `api.example.com:443`
+///
+/// The [EndpointIdentityLayer] inserts it into the request extensions, so
+/// interceptors like the
+/// [HostScopedInterceptor](crate::grpc::interceptor::host_scope::HostScopedInterceptor)
+/// know where a request goes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointIdentity(pub Authority);
+
+impl EndpointIdentity {
+    /// Returns the host without the port
+    pub fn host(&self) -> &str {
+        self.0.host()
+    }
+}
+
+/// A client layer, that records the [EndpointIdentity] of the channel in
+/// every request
+///
+/// Generated clients send requests without the authority, the channel only
+/// adds it below the interceptors. So the layer is configured with the
+/// endpoint of the channel and wraps the intercepted channel, e.g.
+/// `EndpointIdentityLayer::new(&endpoint)?.layer(InterceptedService::new(channel, chain))`.
+#[derive(Clone, Debug)]
+pub struct EndpointIdentityLayer {
+    identity: EndpointIdentity,
+}
+
+impl EndpointIdentityLayer {
+    /// Creates a new layer for the endpoint
+    ///
+    /// Returns None, if the URI of the endpoint has no authority.
+    pub fn new(endpoint: &Endpoint) -> Option<Self> {
+        endpoint.uri().authority().map(|authority| Self {
+            identity: EndpointIdentity(authority.clone()),
+        })
+    }
+}
+
+impl<S> Layer<S> for EndpointIdentityLayer {
+    type Service = EndpointIdentityService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        EndpointIdentityService {
+            inner,
+            identity: self.identity.clone(),
+        }
+    }
+}
+
+/// The service created by the [EndpointIdentityLayer]
+#[derive(Clone, Debug)]
+pub struct EndpointIdentityService<S> {
+    inner: S,
+    identity: EndpointIdentity,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for EndpointIdentityService<S>
+where
+    S: Service<http::Request<ReqBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        req.extensions_mut().insert(self.identity.clone());
+        self.inner.call(req)
+    }
+}
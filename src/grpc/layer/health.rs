@@ -0,0 +1,282 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use bytes::Bytes;
+use http_body::Body;
+use http_body_util::Full;
+use pin_project_lite::pin_project;
+use tonic::body::BoxBody;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+/// The path answering, whether the process is alive
+pub const HEALTHZ: &str = "/healthz";
+/// The path answering, whether the process accepts calls
+pub const READYZ: &str = "/readyz";
+
+#[derive(Debug)]
+struct States {
+    live: AtomicBool,
+    ready: AtomicBool,
+}
+
+/// The status served by the [HealthEndpointLayer]
+///
+/// It starts live and not ready; set it ready, when the server can handle
+/// calls, e.g. after warming up its own channels. Clones share the status.
+#[derive(Clone, Debug)]
+pub struct HealthStatus {
+    states: Arc<States>,
+}
+
+impl Default for HealthStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthStatus {
+    /// Creates a status, that is live and not ready
+    pub fn new() -> Self {
+        Self {
+            states: Arc::new(States {
+                live: AtomicBool::new(true),
+                ready: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Sets, whether `/healthz` answers with 200
+    pub fn set_live(&self, live: bool) {
+        self.states.live.store(live, Ordering::SeqCst);
+    }
+
+    /// Sets, whether `/readyz` answers with 200
+    pub fn set_ready(&self, ready: bool) {
+        self.states.ready.store(ready, Ordering::SeqCst);
+    }
+
+    fn response(&self, req: &http::Request<impl Body>) -> http::Response<BoxBody> {
+        let state = match req.uri().path() {
+            HEALTHZ => &self.states.live,
+            READYZ => &self.states.ready,
+            _ => return json(http::StatusCode::NOT_FOUND, "not found"),
+        };
+        if req.method() != http::Method::GET {
+            return json(http::StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+        }
+        if state.load(Ordering::SeqCst) {
+            json(http::StatusCode::OK, "ok")
+        } else {
+            json(http::StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+        }
+    }
+}
+
+fn json(status: http::StatusCode, message: &str) -> http::Response<BoxBody> {
+    let body = format!("{{\"status\":\"{message}\"}}");
+    let mut response = http::Response::new(tonic::body::boxed(Full::new(Bytes::from(body))));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn is_grpc<B>(req: &http::Request<B>) -> bool {
+    req.headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"))
+}
+
+/// A server layer, that answers plain HTTP health checks next to gRPC
+///
+/// Load balancers often check health with `GET /healthz` over HTTP/1.1.
+/// Requests without a gRPC content type are answered by this layer:
+/// [HEALTHZ] and [READYZ] with 200 or 503 and a small JSON body, other
+/// paths with 404. All gRPC requests are passed to the service. As the
+/// layer works on requests, it is the same with and without TLS.
+///
+/// HTTP/1.1 must be enabled on the server:
+/// ```ignore
+/// tonic::transport::Server::builder()
+///     .accept_http1(true)
+///     .layer(HealthEndpointLayer::new(status.clone()))
+///     .add_service(service)
+/// ```
+#[derive(Clone, Debug)]
+pub struct HealthEndpointLayer {
+    status: HealthStatus,
+}
+
+impl HealthEndpointLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `status`: The status to serve
+    pub fn new(status: HealthStatus) -> Self {
+        Self { status }
+    }
+}
+
+impl<S> Layer<S> for HealthEndpointLayer {
+    type Service = HealthEndpoint<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HealthEndpoint {
+            inner,
+            status: self.status.clone(),
+        }
+    }
+}
+
+/// The service created by the [HealthEndpointLayer]
+#[derive(Clone, Debug)]
+pub struct HealthEndpoint<S> {
+    inner: S,
+    status: HealthStatus,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for HealthEndpoint<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ReqBody: Body,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if is_grpc(&req) {
+            return ResponseFuture::Inner {
+                future: self.inner.call(req),
+            };
+        }
+        ResponseFuture::Local {
+            response: Some(self.status.response(&req)),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [HealthEndpoint]
+    #[project = ResponseFutureProj]
+    pub enum ResponseFuture<F> {
+        Local { response: Option<http::Response<BoxBody>> },
+        Inner { #[pin] future: F },
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Local { response } => {
+                Poll::Ready(Ok(response.take().expect("polled after completion")))
+            }
+            ResponseFutureProj::Inner { future } => {
+                let response = ready!(future.poll(cx))?;
+                Poll::Ready(Ok(response.map(tonic::body::boxed)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tonic::transport::Endpoint;
+
+    use crate::grpc::layer::health::{HealthEndpointLayer, HealthStatus};
+
+    async fn serve(status: HealthStatus) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .accept_http1(true)
+                .layer(HealthEndpointLayer::new(status))
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+        addr
+    }
+
+    /// Sends a plain HTTP/1.1 request and returns the response
+    async fn http_get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn grpc_call(addr: SocketAddr) -> tonic::Code {
+        let channel = Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        client
+            .unary(
+                tonic::Request::new(()),
+                http::uri::PathAndQuery::from_static("/healthz/Method"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await
+            .unwrap_err()
+            .code()
+    }
+
+    #[tokio::test]
+    async fn test_http_and_grpc_on_one_port() {
+        let status = HealthStatus::new();
+        let addr = serve(status.clone()).await;
+
+        let (healthz, readyz, code) = tokio::join!(
+            http_get(addr, "/healthz"),
+            http_get(addr, "/readyz"),
+            grpc_call(addr)
+        );
+        assert!(healthz.starts_with("HTTP/1.1 200 OK"));
+        assert!(healthz.ends_with("{\"status\":\"ok\"}"));
+        assert!(readyz.starts_with("HTTP/1.1 503"));
+        assert!(readyz.ends_with("{\"status\":\"unavailable\"}"));
+        assert_eq!(tonic::Code::Unimplemented, code);
+
+        status.set_ready(true);
+        assert!(http_get(addr, "/readyz")
+            .await
+            .starts_with("HTTP/1.1 200 OK"));
+        status.set_live(false);
+        assert!(http_get(addr, "/healthz").await.starts_with("HTTP/1.1 503"));
+        assert!(http_get(addr, "/other").await.starts_with("HTTP/1.1 404"));
+    }
+}
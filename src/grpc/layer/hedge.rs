@@ -0,0 +1,524 @@
+use std::collections::HashSet;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use http_body::Body;
+use http_body_util::Full;
+use tonic::body::BoxBody;
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{buffer, message_count, rejoin, BoxError, BoxFuture};
+
+/// The header telling the server how many attempts were sent before
+const PREVIOUS_ATTEMPTS: &str = "grpc-previous-rpc-attempts";
+
+#[derive(Debug)]
+struct HedgeConfig {
+    delay: Duration,
+    max_hedges: usize,
+    paths: HashSet<String>,
+    retryable_codes: Vec<Code>,
+}
+
+/// A client layer, that sends hedged requests for idempotent methods
+///
+/// If no response arrived after the hedging delay, the same request is sent
+/// again, up to the maximum number of hedges. The first successful response
+/// is returned and the other requests are cancelled. Put the layer on top of
+/// a balanced channel, so the hedges can reach different backends.
+///
+/// A response with a retryable status in its headers, like the trailers-only
+/// `UNAVAILABLE` of a backend shutting down, counts as failed: the layer
+/// waits for the other attempts or sends the next hedge at once. It is
+/// returned, if no attempt is left. The status in the trailers of a response
+/// with a body is not checked.
+///
+/// Only the methods on the path allowlist are hedged. Their request bodies
+/// are buffered, so they must be unary methods; a request, whose body is
+/// still open or contains more than one message, is sent only once.
+#[derive(Clone, Debug)]
+pub struct HedgeLayer {
+    delay: Duration,
+    max_hedges: usize,
+    paths: HashSet<String>,
+    retryable_codes: Vec<Code>,
+}
+
+impl HedgeLayer {
+    /// Creates a new layer, that hedges no method yet
+    /// # Arguments
+    /// * `delay`: The time to wait for a response before sending a hedge
+    /// * `max_hedges`: The maximum number of additional requests
+    pub fn new(delay: Duration, max_hedges: usize) -> Self {
+        Self {
+            delay,
+            max_hedges,
+            paths: HashSet::new(),
+            retryable_codes: vec![Code::Unavailable],
+        }
+    }
+
+    /// Replaces the status codes, that let the layer try another attempt
+    /// (default `UNAVAILABLE`)
+    /// # Arguments
+    /// * `codes`: The retryable codes, e.g. `[Code::Unavailable, Code::ResourceExhausted]`
+    pub fn with_retryable_codes(mut self, codes: impl IntoIterator<Item = Code>) -> Self {
+        self.retryable_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Allows hedging for an idempotent unary method
+    /// # Arguments
+    /// * `path`: The full method path, e.g. `/package.Service/Get`
+    pub fn allow_path(mut self, path: &str) -> Self {
+        self.paths.insert(path.to_string());
+        self
+    }
+}
+
+impl<S> Layer<S> for HedgeLayer {
+    type Service = Hedge<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Hedge {
+            inner,
+            config: Arc::new(HedgeConfig {
+                delay: self.delay,
+                max_hedges: self.max_hedges,
+                paths: self.paths.clone(),
+                retryable_codes: self.retryable_codes.clone(),
+            }),
+        }
+    }
+}
+
+/// The service created by the [HedgeLayer]
+#[derive(Clone, Debug)]
+pub struct Hedge<S> {
+    inner: S,
+    config: Arc<HedgeConfig>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for Hedge<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    type Response = http::Response<ResBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Self::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        // Use the service, that was driven to readiness
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if !self.config.paths.contains(req.uri().path()) {
+            let future = inner.call(req.map(tonic::body::boxed));
+            return Box::pin(async move { future.await.map_err(Into::into) });
+        }
+        Box::pin(hedged(inner, req, self.config.clone()))
+    }
+}
+
+/// Why an attempt gave no response to return
+enum Failure<ResBody> {
+    /// The service failed
+    Error(BoxError),
+    /// The response has a retryable status
+    Retryable(Code, http::Response<ResBody>),
+}
+
+impl<ResBody> Failure<ResBody> {
+    /// Returns the failure of the last attempt to the caller
+    fn into_result(self) -> Result<http::Response<ResBody>, BoxError> {
+        match self {
+            Failure::Error(e) => Err(e),
+            Failure::Retryable(_, response) => Ok(response),
+        }
+    }
+}
+
+impl<ResBody> std::fmt::Display for Failure<ResBody> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Error(e) => write!(f, "{e}"),
+            Failure::Retryable(code, _) => write!(f, "{code}"),
+        }
+    }
+}
+
+type AttemptFuture<ResBody> =
+    Pin<Box<dyn Future<Output = Result<http::Response<ResBody>, Failure<ResBody>>> + Send>>;
+
+fn attempt<S, ResBody>(
+    mut service: S,
+    mut req: http::Request<BoxBody>,
+    attempt: usize,
+    config: Arc<HedgeConfig>,
+) -> AttemptFuture<ResBody>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    if attempt > 0 {
+        req.headers_mut()
+            .insert(PREVIOUS_ATTEMPTS, http::HeaderValue::from(attempt));
+    }
+    Box::pin(async move {
+        poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .map_err(|e| Failure::Error(e.into()))?;
+        let response = service
+            .call(req)
+            .await
+            .map_err(|e| Failure::Error(e.into()))?;
+        match Status::from_header_map(response.headers()) {
+            Some(status) if config.retryable_codes.contains(&status.code()) => {
+                Err(Failure::Retryable(status.code(), response))
+            }
+            _ => Ok(response),
+        }
+    })
+}
+
+async fn hedged<S, ReqBody, ResBody>(
+    inner: S,
+    req: http::Request<ReqBody>,
+    config: Arc<HedgeConfig>,
+) -> Result<http::Response<ResBody>, BoxError>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    let (parts, body) = req.into_parts();
+    let (body, rest) = buffer(body).await?;
+    if let Some(rest) = rest {
+        log::debug!("Not hedging the streaming call of {}", parts.uri.path());
+        let req = http::Request::from_parts(parts, rejoin(body, rest));
+        return attempt(inner, req, 0, config)
+            .await
+            .or_else(Failure::into_result);
+    }
+    let request =
+        || http::Request::from_parts(parts.clone(), tonic::body::boxed(Full::new(body.clone())));
+
+    // A complete body with more messages is a client streaming call as well
+    let max_hedges = match message_count(&body) {
+        Some(1) => config.max_hedges,
+        _ => 0,
+    };
+
+    let mut in_flight = FuturesUnordered::new();
+    in_flight.push(attempt(inner.clone(), request(), 0, config.clone()));
+    let mut sent = 1;
+    loop {
+        let can_hedge = sent <= max_hedges;
+        tokio::select! {
+            result = in_flight.next() => match result {
+                Some(Ok(response)) => return Ok(response),
+                Some(Err(failure)) if in_flight.is_empty() && !can_hedge => {
+                    return failure.into_result();
+                }
+                Some(Err(failure)) => {
+                    log::debug!("Attempt of {} failed: {failure}", parts.uri.path());
+                    if in_flight.is_empty() {
+                        in_flight.push(attempt(inner.clone(), request(), sent, config.clone()));
+                        sent += 1;
+                    }
+                }
+                None => unreachable!("at least one attempt is in flight"),
+            },
+            _ = tokio::time::sleep(config.delay), if can_hedge => {
+                log::debug!("Sending hedge {sent} for {}", parts.uri.path());
+                in_flight.push(attempt(inner.clone(), request(), sent, config.clone()));
+                sent += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{ready, Context, Poll};
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use futures_util::stream::{self, StreamExt};
+    use http_body::Frame;
+    use http_body_util::{Full, StreamBody};
+    use tokio::net::TcpListener;
+    use tonic::body::BoxBody;
+    use tonic::server::NamedService;
+    use tonic::transport::{Channel, Endpoint};
+    use tonic::Code;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::hedge::HedgeLayer;
+
+    const MESSAGE: [u8; 6] = [0, 0, 0, 0, 1, 42];
+
+    /// A server answering every call with a trailers-only status after a delay
+    #[derive(Clone)]
+    struct Backend {
+        name: &'static str,
+        delay: Duration,
+        code: Code,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl NamedService for Backend {
+        const NAME: &'static str = "test.Service";
+    }
+
+    impl Service<http::Request<BoxBody>> for Backend {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<BoxBody>) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let (name, delay, code) = (self.name, self.delay, self.code);
+            Box::pin(async move {
+                tokio::time::sleep(delay).await;
+                let mut response = tonic::Status::new(code, "").into_http();
+                response
+                    .headers_mut()
+                    .insert("x-backend", http::HeaderValue::from_static(name));
+                Ok(response)
+            })
+        }
+    }
+
+    async fn serve(backend: Backend) -> Channel {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(backend)
+                .serve_with_incoming(incoming),
+        );
+        Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect_lazy()
+    }
+
+    /// Sends the first attempt to the slow server and the hedges to the fast
+    /// one, like a balancer spreading the attempts over the backends
+    #[derive(Clone)]
+    struct Spread {
+        slow: Channel,
+        fast: Channel,
+    }
+
+    impl Service<http::Request<BoxBody>> for Spread {
+        type Response = <Channel as Service<http::Request<BoxBody>>>::Response;
+        type Error = <Channel as Service<http::Request<BoxBody>>>::Error;
+        type Future = <Channel as Service<http::Request<BoxBody>>>::Future;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            ready!(self.slow.poll_ready(cx))?;
+            self.fast.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+            match req.headers().contains_key("grpc-previous-rpc-attempts") {
+                true => self.fast.call(req),
+                false => self.slow.call(req),
+            }
+        }
+    }
+
+    async fn call(layer: HedgeLayer, path: &str, body: BoxBody) -> (String, usize) {
+        let (backend, _, calls) =
+            call_with(layer, path, body, Duration::from_millis(300), Code::Ok).await;
+        (backend, calls)
+    }
+
+    /// Calls the layer, with the first backend answering with the code after the delay
+    async fn call_with(
+        layer: HedgeLayer,
+        path: &str,
+        body: BoxBody,
+        delay: Duration,
+        code: Code,
+    ) -> (String, Code, usize) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let slow = serve(Backend {
+            name: "slow",
+            delay,
+            code,
+            calls: calls.clone(),
+        })
+        .await;
+        let fast = serve(Backend {
+            name: "fast",
+            delay: Duration::ZERO,
+            code: Code::Ok,
+            calls: calls.clone(),
+        })
+        .await;
+        let mut service = layer.layer(Spread { slow, fast });
+        let req = http::Request::builder().uri(path).body(body).unwrap();
+        let response = ServiceExt::<http::Request<BoxBody>>::ready(&mut service)
+            .await
+            .unwrap()
+            .call(req)
+            .await
+            .unwrap();
+        let backend = response.headers()["x-backend"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let code =
+            tonic::Status::from_header_map(response.headers()).map_or(Code::Ok, |s| s.code());
+        (backend, code, calls.load(Ordering::SeqCst))
+    }
+
+    fn layer() -> HedgeLayer {
+        HedgeLayer::new(Duration::from_millis(20), 1).allow_path("/test.Service/Get")
+    }
+
+    fn unary(body: &'static [u8]) -> BoxBody {
+        tonic::body::boxed(Full::new(Bytes::from_static(body)))
+    }
+
+    #[tokio::test]
+    async fn test_fast_hedge_wins() {
+        let start = Instant::now();
+        let (backend, calls) = call(layer(), "/test.Service/Get", unary(&MESSAGE)).await;
+
+        assert_eq!("fast", backend);
+        assert_eq!(2, calls);
+        assert!(start.elapsed() < Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_not_allowed_path() {
+        let (backend, calls) = call(layer(), "/test.Service/Update", unary(&MESSAGE)).await;
+
+        assert_eq!("slow", backend);
+        assert_eq!(1, calls);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_not_hedged() {
+        const TWO_MESSAGES: [u8; 12] = [0, 0, 0, 0, 1, 42, 0, 0, 0, 0, 1, 43];
+        let (backend, calls) = call(layer(), "/test.Service/Get", unary(&TWO_MESSAGES)).await;
+
+        assert_eq!("slow", backend);
+        assert_eq!(1, calls);
+    }
+
+    #[tokio::test]
+    async fn test_open_stream_not_hedged() {
+        // One message, but the client keeps the stream open
+        let frames = stream::iter([Ok::<_, Infallible>(Frame::data(Bytes::from_static(
+            &MESSAGE,
+        )))])
+        .chain(stream::pending());
+        let body = tonic::body::boxed(StreamBody::new(frames));
+        let (backend, calls) = call(layer(), "/test.Service/Get", body).await;
+
+        assert_eq!("slow", backend);
+        assert_eq!(1, calls);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_hedged_at_once() {
+        // The delay is too long to send the hedge on the timer
+        let layer = HedgeLayer::new(Duration::from_secs(10), 1).allow_path("/test.Service/Get");
+        let start = Instant::now();
+        let (backend, code, calls) = call_with(
+            layer,
+            "/test.Service/Get",
+            unary(&MESSAGE),
+            Duration::ZERO,
+            Code::Unavailable,
+        )
+        .await;
+
+        assert_eq!("fast", backend);
+        assert_eq!(Code::Ok, code);
+        assert_eq!(2, calls);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_returned_without_hedges() {
+        let layer = HedgeLayer::new(Duration::from_millis(20), 0).allow_path("/test.Service/Get");
+        let (backend, code, calls) = call_with(
+            layer,
+            "/test.Service/Get",
+            unary(&MESSAGE),
+            Duration::ZERO,
+            Code::Unavailable,
+        )
+        .await;
+
+        assert_eq!("slow", backend);
+        assert_eq!(Code::Unavailable, code);
+        assert_eq!(1, calls);
+    }
+
+    #[tokio::test]
+    async fn test_retryable_codes() {
+        let layer = || HedgeLayer::new(Duration::from_secs(10), 1).allow_path("/test.Service/Get");
+        let (backend, code, calls) = call_with(
+            layer(),
+            "/test.Service/Get",
+            unary(&MESSAGE),
+            Duration::ZERO,
+            Code::NotFound,
+        )
+        .await;
+        assert_eq!("slow", backend);
+        assert_eq!(Code::NotFound, code);
+        assert_eq!(1, calls);
+
+        let (backend, code, calls) = call_with(
+            layer().with_retryable_codes([Code::NotFound]),
+            "/test.Service/Get",
+            unary(&MESSAGE),
+            Duration::ZERO,
+            Code::NotFound,
+        )
+        .await;
+        assert_eq!("fast", backend);
+        assert_eq!(Code::Ok, code);
+        assert_eq!(2, calls);
+    }
+}
@@ -0,0 +1,361 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use http_body::Body;
+use pin_project_lite::pin_project;
+use tonic::metadata::MetadataMap;
+use tonic::{body::BoxBody, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::health::{HEALTHZ, READYZ};
+use crate::grpc::layer::{BoxError, ResponseFuture};
+
+/// The weight of the latest call in the moving average of the latency
+const LATENCY_WEIGHT: f64 = 0.2;
+
+type ShedHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone)]
+struct Config {
+    max_in_flight: usize,
+    max_latency: Option<Duration>,
+    retry_after: Duration,
+    exempt_paths: Vec<String>,
+    on_shed: Option<ShedHook>,
+}
+
+impl Config {
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt_paths
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
+
+/// The state shared by all services of a [LoadShedLayer]
+#[derive(Default)]
+struct State {
+    in_flight: AtomicUsize,
+    shed: AtomicU64,
+    /// The moving average of the handler latency
+    latency: Mutex<Option<Duration>>,
+}
+
+impl State {
+    fn record_latency(&self, took: Duration) {
+        let mut latency = self.latency.lock().unwrap_or_else(|e| e.into_inner());
+        *latency = Some(match *latency {
+            Some(average) => average.mul_f64(1.0 - LATENCY_WEIGHT) + took.mul_f64(LATENCY_WEIGHT),
+            None => took,
+        });
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        *self.latency.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A server layer, that rejects calls right away when the server is
+/// overloaded, instead of queueing them until they time out
+///
+/// A call is shed with [Status::unavailable] and a `retry-after` header in
+/// seconds, when the limit of calls in flight is reached. Optionally, calls
+/// are also shed while the moving average of the handler latency is above
+/// a limit and other calls are in flight. A call is in flight until its
+/// response headers are sent.
+///
+/// The gRPC health service and the paths of the
+/// [HealthEndpointLayer](crate::grpc::layer::health::HealthEndpointLayer)
+/// are never shed, so probes keep passing.
+#[derive(Clone)]
+pub struct LoadShedLayer {
+    config: Config,
+    state: Arc<State>,
+}
+
+impl LoadShedLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `max_in_flight`: The maximum number of calls handled at once
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            config: Config {
+                max_in_flight,
+                max_latency: None,
+                retry_after: Duration::from_secs(1),
+                exempt_paths: vec![
+                    "/grpc.health.v1.Health/".to_string(),
+                    HEALTHZ.to_string(),
+                    READYZ.to_string(),
+                ],
+                on_shed: None,
+            },
+            state: Arc::new(State::default()),
+        }
+    }
+
+    /// Also sheds calls, while the average handler latency exceeds the limit
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.config.max_latency = Some(max_latency);
+        self
+    }
+
+    /// Sets the `retry-after` hint of shed calls, 1 second by default
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.config.retry_after = retry_after;
+        self
+    }
+
+    /// Never sheds calls to paths starting with the prefix
+    pub fn with_exempt_path(mut self, prefix: &str) -> Self {
+        self.config.exempt_paths.push(prefix.to_string());
+        self
+    }
+
+    /// Sets a function, that is called with the path of every shed call
+    pub fn on_shed(mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.config.on_shed = Some(Arc::new(hook));
+        self
+    }
+
+    /// Returns the number of calls shed so far
+    pub fn shed_count(&self) -> u64 {
+        self.state.shed.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of calls in flight
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl<S> Layer<S> for LoadShedLayer {
+    type Service = LoadShed<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LoadShed {
+            inner,
+            config: Arc::new(self.config.clone()),
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// The service created by the [LoadShedLayer]
+#[derive(Clone)]
+pub struct LoadShed<S> {
+    inner: S,
+    config: Arc<Config>,
+    state: Arc<State>,
+}
+
+impl<S> LoadShed<S> {
+    /// Admits the call, or returns why it is shed
+    fn admit(&self) -> Result<InFlight, String> {
+        let in_flight = self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight {
+            state: self.state.clone(),
+            started: Instant::now(),
+        };
+        if in_flight >= self.config.max_in_flight {
+            return Err(format!("{in_flight} calls in flight"));
+        }
+        if let Some(max_latency) = self.config.max_latency
+            && in_flight > 0
+            && let Some(latency) = self
+                .state
+                .latency()
+                .filter(|latency| *latency > max_latency)
+        {
+            return Err(format!("average latency of {latency:?}"));
+        }
+        Ok(guard)
+    }
+
+    fn shed(&self, path: &str, reason: &str) -> Status {
+        self.state.shed.fetch_add(1, Ordering::SeqCst);
+        log::warn!("Shed call to {path}: {reason}");
+        if let Some(hook) = &self.config.on_shed {
+            hook(path);
+        }
+
+        let mut metadata = MetadataMap::new();
+        let seconds = self.config.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        metadata.insert("retry-after", seconds.into());
+        Status::with_metadata(
+            tonic::Code::Unavailable,
+            "The server is overloaded, retry later",
+            metadata,
+        )
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for LoadShed<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<AdmittedFuture<S::Future>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        if self.config.is_exempt(path) {
+            return ResponseFuture::Inner {
+                future: AdmittedFuture {
+                    future: self.inner.call(req),
+                    in_flight: None,
+                },
+            };
+        }
+
+        match self.admit() {
+            Ok(in_flight) => ResponseFuture::Inner {
+                future: AdmittedFuture {
+                    future: self.inner.call(req),
+                    in_flight: Some(in_flight),
+                },
+            },
+            Err(reason) => ResponseFuture::Rejected {
+                status: Some(self.shed(path, &reason)),
+            },
+        }
+    }
+}
+
+/// Counts a call as in flight, until it is dropped
+struct InFlight {
+    state: Arc<State>,
+    started: Instant,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pin_project! {
+    /// The future of an admitted call, that records its latency
+    pub struct AdmittedFuture<F> {
+        #[pin]
+        future: F,
+        in_flight: Option<InFlight>,
+    }
+}
+
+impl<F: Future> Future for AdmittedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = ready!(this.future.poll(cx));
+        if let Some(in_flight) = this.in_flight.take() {
+            in_flight.state.record_latency(in_flight.started.elapsed());
+        }
+        Poll::Ready(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use tonic::body::BoxBody;
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::load_shed::{LoadShed, LoadShedLayer};
+
+    const SLOW: Duration = Duration::from_millis(200);
+
+    /// Answers every call after a delay
+    async fn slow(_req: http::Request<BoxBody>) -> Result<http::Response<BoxBody>, Infallible> {
+        tokio::time::sleep(SLOW).await;
+        Ok(Status::ok("").into_http())
+    }
+
+    async fn call<S>(mut service: LoadShed<S>, path: &'static str) -> Status
+    where
+        LoadShed<S>:
+            Service<http::Request<BoxBody>, Response = http::Response<BoxBody>, Error = Infallible>,
+    {
+        let req = http::Request::builder()
+            .uri(path)
+            .body(tonic::body::empty_body())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        Status::from_header_map(response.headers()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_limit() {
+        let hooked = Arc::new(AtomicUsize::new(0));
+        let counted = hooked.clone();
+        let layer = LoadShedLayer::new(2).on_shed(move |path| {
+            assert_eq!("/test.Service/Method", path);
+            counted.fetch_add(1, Ordering::SeqCst);
+        });
+        let service = layer.layer(tower::service_fn(slow));
+
+        let first = tokio::spawn(call(service.clone(), "/test.Service/Method"));
+        let second = tokio::spawn(call(service.clone(), "/test.Service/Method"));
+        while layer.in_flight() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        let started = Instant::now();
+        let status = call(service.clone(), "/test.Service/Method").await;
+        assert!(started.elapsed() < SLOW / 2);
+        assert_eq!(Code::Unavailable, status.code());
+        assert_eq!("1", status.metadata().get("retry-after").unwrap());
+
+        let health = call(service.clone(), "/grpc.health.v1.Health/Check").await;
+        assert_eq!(Code::Ok, health.code());
+
+        assert_eq!(Code::Ok, first.await.unwrap().code());
+        assert_eq!(Code::Ok, second.await.unwrap().code());
+        assert_eq!(1, layer.shed_count());
+        assert_eq!(1, hooked.load(Ordering::SeqCst));
+        assert_eq!(0, layer.in_flight());
+        assert_eq!(Code::Ok, call(service, "/test.Service/Method").await.code());
+    }
+
+    #[tokio::test]
+    async fn test_latency_limit() {
+        let layer = LoadShedLayer::new(10).with_max_latency(SLOW / 2);
+        let service = layer.layer(tower::service_fn(slow));
+
+        // The first call sets the average, alone it is never shed
+        assert_eq!(
+            Code::Ok,
+            call(service.clone(), "/test.Service/Method").await.code()
+        );
+
+        let first = tokio::spawn(call(service.clone(), "/test.Service/Method"));
+        while layer.in_flight() < 1 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            Code::Unavailable,
+            call(service.clone(), "/test.Service/Method").await.code()
+        );
+        assert_eq!(Code::Ok, first.await.unwrap().code());
+    }
+}
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http_body::Body;
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{BoxError, ResponseFuture};
+
+/// A pattern of a [MethodPolicyLayer]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MethodPattern {
+    /// A full path, e.g. `/package.Service/Method`
+    Exact(String),
+    /// The path prefix of all methods of a service, e.g. `/package.Service/`
+    Service(String),
+}
+
+impl MethodPattern {
+    fn parse(pattern: &str) -> Result<Self, Status> {
+        let invalid = || {
+            Status::invalid_argument(format!(
+                "Pattern {pattern} is neither /package.Service/Method nor /package.Service/*"
+            ))
+        };
+        let (service, method) = pattern
+            .strip_prefix('/')
+            .and_then(|path| path.split_once('/'))
+            .ok_or_else(invalid)?;
+        if service.is_empty() || method.is_empty() || method.contains('/') {
+            return Err(invalid());
+        }
+        if method == "*" {
+            Ok(Self::Service(format!("/{service}/")))
+        } else if service.contains('*') || method.contains('*') {
+            Err(invalid())
+        } else {
+            Ok(Self::Exact(pattern.to_string()))
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Exact(exact) => path == exact,
+            Self::Service(prefix) => path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|method| !method.is_empty() && !method.contains('/')),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Policy {
+    allow: Vec<MethodPattern>,
+    deny: Vec<MethodPattern>,
+}
+
+impl Policy {
+    fn permits(&self, path: &str) -> bool {
+        if self.deny.iter().any(|pattern| pattern.matches(path)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|pattern| pattern.matches(path))
+    }
+}
+
+/// A client layer, that only lets approved methods through
+///
+/// Calls are checked against the deny patterns first, then against the
+/// allow patterns. A denied call, or one that matches no allow pattern, is
+/// answered with [Status::permission_denied] without being sent. Without
+/// allow patterns every method, that is not denied, is allowed, so an empty
+/// policy lets everything through.
+///
+/// Patterns are full paths, e.g. `/package.Service/Method`, or all methods
+/// of a service, e.g. `/package.Service/*`.
+#[derive(Clone, Debug, Default)]
+pub struct MethodPolicyLayer {
+    policy: Policy,
+}
+
+impl MethodPolicyLayer {
+    /// Creates a new layer, that allows all methods
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the methods matching the pattern
+    ///
+    /// Fails with [Status::invalid_argument], if the pattern is invalid.
+    /// # Arguments
+    /// * `pattern`: The method, e.g. `/package.Service/Method`, or the
+    ///   service, e.g. `/package.Service/*`
+    pub fn allow(mut self, pattern: &str) -> Result<Self, Status> {
+        self.policy.allow.push(MethodPattern::parse(pattern)?);
+        Ok(self)
+    }
+
+    /// Denies the methods matching the pattern, even if they are allowed
+    ///
+    /// Fails with [Status::invalid_argument], if the pattern is invalid.
+    /// # Arguments
+    /// * `pattern`: The method, e.g. `/package.Service/Method`, or the
+    ///   service, e.g. `/package.Service/*`
+    pub fn deny(mut self, pattern: &str) -> Result<Self, Status> {
+        self.policy.deny.push(MethodPattern::parse(pattern)?);
+        Ok(self)
+    }
+}
+
+impl<S> Layer<S> for MethodPolicyLayer {
+    type Service = MethodPolicy<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MethodPolicy {
+            inner,
+            policy: Arc::new(self.policy.clone()),
+        }
+    }
+}
+
+/// The service created by the [MethodPolicyLayer]
+#[derive(Clone, Debug)]
+pub struct MethodPolicy<S> {
+    inner: S,
+    policy: Arc<Policy>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for MethodPolicy<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        if self.policy.permits(path) {
+            ResponseFuture::Inner {
+                future: self.inner.call(req),
+            }
+        } else {
+            log::warn!("Call of {path} denied by the method policy");
+            ResponseFuture::Rejected {
+                status: Some(Status::permission_denied(format!(
+                    "Method {path} is not allowed"
+                ))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::method_policy::MethodPolicyLayer;
+
+    /// Calls the methods through the layer and returns the codes and the
+    /// requests, that were sent
+    async fn call(layer: MethodPolicyLayer, paths: &[&str]) -> (Vec<Code>, Vec<http::Request<()>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let captured = sent.clone();
+        let mut service = layer.layer(tower::service_fn(move |req: http::Request<()>| {
+            captured.lock().unwrap().push(req);
+            async { Ok::<_, Infallible>(Status::ok("").into_http()) }
+        }));
+
+        let mut codes = Vec::new();
+        for path in paths {
+            let req = http::Request::builder()
+                .uri(*path)
+                .header("x-api-key", "key")
+                .body(())
+                .unwrap();
+            let response = service.ready().await.unwrap().call(req).await.unwrap();
+            codes.push(Status::from_header_map(response.headers()).unwrap().code());
+        }
+        let sent = std::mem::take(&mut *sent.lock().unwrap());
+        (codes, sent)
+    }
+
+    #[tokio::test]
+    async fn test_wildcards() {
+        let layer = MethodPolicyLayer::new()
+            .allow("/a.ServiceA/*")
+            .unwrap()
+            .allow("/b.ServiceB/Get")
+            .unwrap();
+        let (codes, sent) = call(
+            layer,
+            &[
+                "/a.ServiceA/Get",
+                "/a.ServiceA/Put",
+                "/a.ServiceAB/Get",
+                "/b.ServiceB/Get",
+                "/b.ServiceB/Put",
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            vec![
+                Code::Ok,
+                Code::Ok,
+                Code::PermissionDenied,
+                Code::Ok,
+                Code::PermissionDenied
+            ],
+            codes
+        );
+        // The allowed calls are sent unchanged
+        assert_eq!(3, sent.len());
+        assert_eq!("/a.ServiceA/Get", sent[0].uri().path());
+        assert_eq!("key", sent[0].headers()["x-api-key"]);
+    }
+
+    #[tokio::test]
+    async fn test_deny_precedence() {
+        let layer = MethodPolicyLayer::new()
+            .allow("/a.ServiceA/*")
+            .unwrap()
+            .deny("/a.ServiceA/Delete")
+            .unwrap();
+        let (codes, sent) = call(layer, &["/a.ServiceA/Get", "/a.ServiceA/Delete"]).await;
+        assert_eq!(vec![Code::Ok, Code::PermissionDenied], codes);
+        assert_eq!(1, sent.len());
+
+        // Only denies allow all other methods
+        let layer = MethodPolicyLayer::new().deny("/a.ServiceA/*").unwrap();
+        let (codes, _) = call(layer, &["/a.ServiceA/Get", "/b.ServiceB/Get"]).await;
+        assert_eq!(vec![Code::PermissionDenied, Code::Ok], codes);
+    }
+
+    #[tokio::test]
+    async fn test_empty_policy() {
+        let (codes, sent) = call(MethodPolicyLayer::new(), &["/a.ServiceA/Get"]).await;
+        assert_eq!(vec![Code::Ok], codes);
+        assert_eq!(1, sent.len());
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in [
+            "a.ServiceA/Get",
+            "/a.ServiceA",
+            "/a.ServiceA/",
+            "/a.*/Get",
+            "/a.ServiceA/Get*",
+            "/a.ServiceA/Get/More",
+        ] {
+            assert!(
+                MethodPolicyLayer::new().allow(pattern).is_err(),
+                "{pattern}"
+            );
+        }
+    }
+}
@@ -0,0 +1,854 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, Bytes};
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use tonic::body::BoxBody;
+use tonic::Code;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+/// The counters of one direction of a method
+#[derive(Debug, Default)]
+struct Counter {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// The counters of a method
+#[derive(Debug, Default)]
+struct MethodCounters {
+    request: Counter,
+    response: Counter,
+}
+
+/// The counted traffic of a method
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+    /// The number of request messages
+    pub request_messages: u64,
+    /// The number of request body bytes, including the message headers
+    pub request_bytes: u64,
+    /// The number of response messages
+    pub response_messages: u64,
+    /// The number of response body bytes, including the message headers
+    pub response_bytes: u64,
+}
+
+type Registry = Arc<Mutex<HashMap<String, Arc<MethodCounters>>>>;
+
+/// A handle to read the counters of a [MetricsLayer]
+#[derive(Clone, Debug)]
+pub struct MetricsHandle {
+    registry: Registry,
+}
+
+impl MetricsHandle {
+    /// Returns the counters of all methods, that were called, by path
+    pub fn snapshot(&self) -> HashMap<String, MethodMetrics> {
+        let registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        registry
+            .iter()
+            .map(|(path, counters)| {
+                let metrics = MethodMetrics {
+                    request_messages: counters.request.messages.load(Ordering::Relaxed),
+                    request_bytes: counters.request.bytes.load(Ordering::Relaxed),
+                    response_messages: counters.response.messages.load(Ordering::Relaxed),
+                    response_bytes: counters.response.bytes.load(Ordering::Relaxed),
+                };
+                (path.clone(), metrics)
+            })
+            .collect()
+    }
+
+    /// Returns the counters of the method, e.g. `/package.Service/Method`
+    pub fn method(&self, path: &str) -> Option<MethodMetrics> {
+        self.snapshot().remove(path)
+    }
+}
+
+/// A client layer, that counts the messages and body bytes per method
+///
+/// Every data frame of the request and response bodies is counted, so
+/// streaming calls count each message. The gRPC message headers are
+/// tracked across frames, so messages are counted correctly, even when a
+/// frame holds several or only part of one. The counters are looked up once
+/// per call; a frame costs two atomic additions. Read them with the
+/// [MetricsHandle] of [MetricsLayer::handle].
+#[derive(Clone, Debug, Default)]
+pub struct MetricsLayer {
+    registry: Registry,
+}
+
+impl MetricsLayer {
+    /// Creates a new layer without counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a handle to read the counters
+    pub fn handle(&self) -> MetricsHandle {
+        MetricsHandle {
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+/// The service created by the [MetricsLayer]
+#[derive(Clone, Debug)]
+pub struct Metrics<S> {
+    inner: S,
+    registry: Registry,
+}
+
+impl<S> Metrics<S> {
+    fn counters(&self, path: &str) -> Arc<MethodCounters> {
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        match registry.get(path) {
+            Some(counters) => counters.clone(),
+            None => registry.entry(path.to_string()).or_default().clone(),
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for Metrics<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let counters = self.counters(req.uri().path());
+        let req = req.map(|inner| {
+            tonic::body::boxed(CountingBody {
+                inner,
+                counters: counters.clone(),
+                direction: Direction::Request,
+                framing: Framing::default(),
+            })
+        });
+        ResponseFuture {
+            future: self.inner.call(req),
+            counters: Some(counters),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [Metrics]
+    pub struct ResponseFuture<F> {
+        #[pin]
+        future: F,
+        counters: Option<Arc<MethodCounters>>,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = ready!(this.future.poll(cx))?;
+        let counters = this.counters.take().expect("polled after completion");
+        Poll::Ready(Ok(response.map(|inner| {
+            tonic::body::boxed(CountingBody {
+                inner,
+                counters,
+                direction: Direction::Response,
+                framing: Framing::default(),
+            })
+        })))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    Request,
+    Response,
+}
+
+/// Tracks the gRPC message headers across data frames
+#[derive(Debug, Default)]
+struct Framing {
+    /// The bytes of the current header, that were already seen
+    header: [u8; 5],
+    header_len: usize,
+    /// The bytes of the current message, that are still to come
+    remaining: usize,
+}
+
+impl Framing {
+    /// Returns the number of messages starting in the data
+    fn feed(&mut self, mut data: impl Buf) -> u64 {
+        let mut messages = 0;
+        while data.has_remaining() {
+            if self.remaining > 0 {
+                let skipped = self.remaining.min(data.remaining());
+                data.advance(skipped);
+                self.remaining -= skipped;
+                continue;
+            }
+
+            let copied = (5 - self.header_len).min(data.chunk().len());
+            data.copy_to_slice(&mut self.header[self.header_len..self.header_len + copied]);
+            self.header_len += copied;
+            if self.header_len == 5 {
+                let [_, length @ ..] = self.header;
+                self.remaining = u32::from_be_bytes(length) as usize;
+                self.header_len = 0;
+                messages += 1;
+            }
+        }
+        messages
+    }
+}
+
+pin_project! {
+    /// A body, that counts its data frames
+    struct CountingBody<B> {
+        #[pin]
+        inner: B,
+        counters: Arc<MethodCounters>,
+        direction: Direction,
+        framing: Framing,
+    }
+}
+
+impl<B> Body for CountingBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx)).map(|frame| frame.map_err(Into::into));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            let counter = match this.direction {
+                Direction::Request => &this.counters.request,
+                Direction::Response => &this.counters.response,
+            };
+            let messages = this.framing.feed(data.clone());
+            counter.messages.fetch_add(messages, Ordering::Relaxed);
+            counter
+                .bytes
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// The label of calls to methods, that are not in the allowlist
+pub const OTHER_METHOD: &str = "other";
+/// The name of the histogram, that the [LatencyLayer] records to the
+/// [metrics] recorder, in seconds with the labels `method` and `code`
+pub const LATENCY_HISTOGRAM: &str = "grpc_client_call_duration_seconds";
+
+/// The default upper bounds of the latency buckets
+const DEFAULT_BUCKETS: [Duration; 11] = [
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// The latencies of the calls of a method with a status code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// The upper bounds of the buckets; a last bucket counts all slower
+    /// calls
+    pub bounds: Vec<Duration>,
+    /// The number of calls per bucket, not cumulative
+    pub counts: Vec<u64>,
+    /// The number of calls
+    pub count: u64,
+    /// The sum of the latencies
+    pub sum: Duration,
+}
+
+impl Histogram {
+    fn new(bounds: &[Duration]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let bucket = self.bounds.partition_point(|bound| *bound < latency);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+}
+
+/// The labels of a [Histogram]: the method path and the status code
+pub type LatencyLabels = (String, Code);
+
+#[derive(Debug)]
+struct LatencyConfig {
+    bounds: Vec<Duration>,
+    allowed_methods: Option<HashSet<String>>,
+}
+
+type Histograms = Arc<Mutex<HashMap<LatencyLabels, Histogram>>>;
+
+/// A handle to read the histograms of a [LatencyLayer]
+#[derive(Clone, Debug)]
+pub struct LatencyHandle {
+    histograms: Histograms,
+}
+
+impl LatencyHandle {
+    /// Returns the histograms of all methods and codes, that were recorded
+    pub fn snapshot(&self) -> HashMap<LatencyLabels, Histogram> {
+        self.histograms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// A client layer, that records the latency of calls per method and status
+/// code
+///
+/// A call is timed from the request until the status is received: the end
+/// of the response stream with its trailers, or the response headers for
+/// calls failing right away. Calls, whose response is dropped early, are
+/// recorded as [Code::Cancelled], errors of the channel as
+/// [Code::Unavailable]. The latency is recorded as [LATENCY_HISTOGRAM] with
+/// the [metrics::histogram!] macro, so it reaches the exporter installed in
+/// the application, e.g. for Prometheus. The code label is the name of the
+/// [Code], e.g. `NotFound`. The histograms can also be read with the
+/// [LatencyHandle] of [LatencyLayer::handle], whose buckets are only used
+/// there.
+///
+/// Proxies forwarding any path can create unlimited labels, so the methods
+/// can be limited with [LatencyLayer::with_allowed_methods].
+#[derive(Clone, Debug)]
+pub struct LatencyLayer {
+    config: Arc<LatencyConfig>,
+    histograms: Histograms,
+}
+
+impl Default for LatencyLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyLayer {
+    /// Creates a new layer with buckets from 5 milliseconds to 10 seconds
+    pub fn new() -> Self {
+        Self {
+            config: Arc::new(LatencyConfig {
+                bounds: DEFAULT_BUCKETS.to_vec(),
+                allowed_methods: None,
+            }),
+            histograms: Histograms::default(),
+        }
+    }
+
+    fn configure(mut self, configure: impl FnOnce(&mut LatencyConfig)) -> Self {
+        let mut config = LatencyConfig {
+            bounds: self.config.bounds.clone(),
+            allowed_methods: self.config.allowed_methods.clone(),
+        };
+        configure(&mut config);
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Sets the upper bounds of the buckets
+    pub fn with_buckets(self, mut bounds: Vec<Duration>) -> Self {
+        bounds.sort();
+        bounds.dedup();
+        self.configure(|config| config.bounds = bounds)
+    }
+
+    /// Only records the methods by their path, all others as [OTHER_METHOD]
+    pub fn with_allowed_methods<'a>(self, paths: impl IntoIterator<Item = &'a str>) -> Self {
+        let paths = paths.into_iter().map(str::to_string).collect();
+        self.configure(|config| config.allowed_methods = Some(paths))
+    }
+
+    /// Returns a handle to read the histograms
+    pub fn handle(&self) -> LatencyHandle {
+        LatencyHandle {
+            histograms: self.histograms.clone(),
+        }
+    }
+}
+
+impl<S> Layer<S> for LatencyLayer {
+    type Service = Latency<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Latency {
+            inner,
+            config: self.config.clone(),
+            histograms: self.histograms.clone(),
+        }
+    }
+}
+
+/// The service created by the [LatencyLayer]
+#[derive(Clone, Debug)]
+pub struct Latency<S> {
+    inner: S,
+    config: Arc<LatencyConfig>,
+    histograms: Histograms,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for Latency<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = LatencyFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        let method = match &self.config.allowed_methods {
+            Some(allowed) if !allowed.contains(path) => OTHER_METHOD.to_string(),
+            _ => path.to_string(),
+        };
+        let timer = Timer {
+            method,
+            started: Instant::now(),
+            config: self.config.clone(),
+            histograms: self.histograms.clone(),
+            recorded: false,
+        };
+        LatencyFuture {
+            future: self.inner.call(req),
+            timer: Some(timer),
+        }
+    }
+}
+
+/// Records the latency of a call once, as cancelled if it is dropped
+struct Timer {
+    method: String,
+    started: Instant,
+    config: Arc<LatencyConfig>,
+    histograms: Histograms,
+    recorded: bool,
+}
+
+impl Timer {
+    fn record(&mut self, code: Code) {
+        if self.recorded {
+            return;
+        }
+        self.recorded = true;
+        let latency = self.started.elapsed();
+        metrics::histogram!(
+            LATENCY_HISTOGRAM,
+            "method" => self.method.clone(),
+            "code" => format!("{code:?}"),
+        )
+        .record(latency);
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .entry((std::mem::take(&mut self.method), code))
+            .or_insert_with(|| Histogram::new(&self.config.bounds))
+            .record(latency);
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.record(Code::Cancelled);
+    }
+}
+
+/// Returns the code of the `grpc-status` header, if there is one
+fn status_code(headers: &http::HeaderMap) -> Option<Code> {
+    headers
+        .get("grpc-status")
+        .map(|value| Code::from_bytes(value.as_bytes()))
+}
+
+pin_project! {
+    /// The response future of [Latency]
+    pub struct LatencyFuture<F> {
+        #[pin]
+        future: F,
+        timer: Option<Timer>,
+    }
+}
+
+impl<F, ResBody, E> Future for LatencyFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.future.poll(cx));
+        let mut timer = this.timer.take().expect("polled after completion");
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                timer.record(Code::Unavailable);
+                return Poll::Ready(Err(e));
+            }
+        };
+
+        // A call failing right away has its status in the headers
+        if let Some(code) = status_code(response.headers()) {
+            timer.record(code);
+        }
+        Poll::Ready(Ok(
+            response.map(|inner| tonic::body::boxed(TimedBody { inner, timer }))
+        ))
+    }
+}
+
+pin_project! {
+    /// A body, that records the latency, when the trailers arrive
+    struct TimedBody<B> {
+        #[pin]
+        inner: B,
+        timer: Timer,
+    }
+}
+
+impl<B> Body for TimedBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx)).map(|frame| frame.map_err(Into::into));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(code) = frame.trailers_ref().and_then(status_code) {
+                    this.timer.record(code);
+                }
+            }
+            Some(Err(e)) => {
+                let code = e.downcast_ref::<tonic::Status>().map(tonic::Status::code);
+                this.timer.record(code.unwrap_or(Code::Unavailable));
+            }
+            // The stream ended without a status
+            None => this.timer.record(Code::Unknown),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use http_body_util::BodyExt;
+    use tonic::body::BoxBody;
+    use tonic::codec::ProstCodec;
+    use tower::ServiceBuilder;
+
+    use crate::grpc::layer::metrics::{
+        Framing, LatencyLayer, MethodMetrics, MetricsLayer, LATENCY_HISTOGRAM, OTHER_METHOD,
+    };
+
+    /// A message with a 3 byte payload
+    const MESSAGE: [u8; 8] = [0, 0, 0, 0, 3, 1, 2, 3];
+
+    #[test]
+    fn test_framing() {
+        let mut framing = Framing::default();
+        assert_eq!(2, framing.feed(&[MESSAGE, MESSAGE].concat()[..]));
+
+        // A message is counted, when its header is complete
+        assert_eq!(0, framing.feed(&MESSAGE[..3]));
+        assert_eq!(1, framing.feed(&MESSAGE[3..6]));
+        assert_eq!(1, framing.feed(&[&MESSAGE[6..], &MESSAGE[..]].concat()[..]));
+        assert_eq!(0, framing.header_len);
+        assert_eq!(0, framing.remaining);
+    }
+
+    #[tokio::test]
+    async fn test_unary_and_streaming_calls() {
+        let layer = MetricsLayer::new();
+        let handle = layer.handle();
+
+        // The server answers with one message per request message
+        let channel = ServiceBuilder::new()
+            .layer(layer)
+            .service(tower::service_fn(|req: http::Request<BoxBody>| async {
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                let response = http::Response::builder()
+                    .header("grpc-status", "0")
+                    .body(tonic::body::boxed(http_body_util::Full::new(body)))
+                    .unwrap();
+                Ok::<_, Infallible>(response)
+            }));
+        let mut client = tonic::client::Grpc::new(channel);
+        let codec = ProstCodec::<Bytes, Bytes>::default;
+
+        for _ in 0..2 {
+            client.ready().await.unwrap();
+            client
+                .unary(
+                    tonic::Request::new(Bytes::from_static(&[1, 2, 3])),
+                    http::uri::PathAndQuery::from_static("/test.Service/Unary"),
+                    codec(),
+                )
+                .await
+                .unwrap();
+        }
+
+        client.ready().await.unwrap();
+        let requests = futures_util::stream::iter(
+            [[1].as_slice(), &[1, 2], &[1, 2, 3]].map(Bytes::from_static),
+        );
+        let mut responses = client
+            .streaming(
+                tonic::Request::new(requests),
+                http::uri::PathAndQuery::from_static("/test.Service/Stream"),
+                codec(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        let mut received = 0;
+        while responses.message().await.unwrap().is_some() {
+            received += 1;
+        }
+        assert_eq!(3, received);
+
+        let unary = MethodMetrics {
+            request_messages: 2,
+            request_bytes: 2 * 10,
+            response_messages: 2,
+            response_bytes: 2 * 10,
+        };
+        assert_eq!(Some(unary), handle.method("/test.Service/Unary"));
+        let streaming = MethodMetrics {
+            request_messages: 3,
+            request_bytes: 8 + 9 + 10,
+            response_messages: 3,
+            response_bytes: 8 + 9 + 10,
+        };
+        assert_eq!(Some(streaming), handle.method("/test.Service/Stream"));
+        assert_eq!(2, handle.snapshot().len());
+    }
+
+    /// Echoes the request after a delay, with the status in the trailers
+    async fn echo(req: http::Request<BoxBody>) -> Result<http::Response<BoxBody>, Infallible> {
+        if req.uri().path() == "/test.Service/Missing" {
+            return Ok(tonic::Status::not_found("missing").into_http());
+        }
+        if req.uri().path() == "/test.Service/Slow" {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+        }
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        let frames = futures_util::stream::iter([
+            Ok::<_, Infallible>(http_body::Frame::data(body)),
+            Ok(http_body::Frame::trailers(trailers)),
+        ]);
+        let body = tonic::body::boxed(http_body_util::StreamBody::new(frames));
+        Ok(http::Response::new(body))
+    }
+
+    #[tokio::test]
+    async fn test_latency() {
+        let layer = LatencyLayer::new()
+            .with_buckets(vec![Duration::from_millis(20), Duration::from_secs(1)])
+            .with_allowed_methods(["/test.Service/Fast", "/test.Service/Slow"]);
+        let handle = layer.handle();
+        let channel = ServiceBuilder::new()
+            .layer(layer)
+            .service(tower::service_fn(echo));
+        let mut client = tonic::client::Grpc::new(channel);
+
+        for path in [
+            "/test.Service/Fast",
+            "/test.Service/Slow",
+            "/test.Service/Slow",
+            "/test.Service/Missing",
+            "/random/path",
+        ] {
+            client.ready().await.unwrap();
+            let _ = client
+                .unary(
+                    tonic::Request::new(Bytes::from_static(&[1])),
+                    http::uri::PathAndQuery::from_maybe_shared(path).unwrap(),
+                    ProstCodec::<Bytes, Bytes>::default(),
+                )
+                .await;
+        }
+
+        let snapshot = handle.snapshot();
+        let mut labels: Vec<_> = snapshot.keys().cloned().collect();
+        labels.sort_by_key(|(method, code)| (method.clone(), *code as i32));
+        assert_eq!(
+            vec![
+                ("/test.Service/Fast".to_string(), tonic::Code::Ok),
+                ("/test.Service/Slow".to_string(), tonic::Code::Ok),
+                (OTHER_METHOD.to_string(), tonic::Code::Ok),
+                (OTHER_METHOD.to_string(), tonic::Code::NotFound),
+            ],
+            labels
+        );
+
+        let slow = &snapshot[&("/test.Service/Slow".to_string(), tonic::Code::Ok)];
+        assert_eq!(2, slow.count);
+        assert_eq!(vec![0, 2, 0], slow.counts);
+        assert!(slow.sum >= Duration::from_millis(60));
+        assert!(slow.sum < Duration::from_secs(2));
+
+        let fast = &snapshot[&("/test.Service/Fast".to_string(), tonic::Code::Ok)];
+        assert_eq!(vec![1, 0, 0], fast.counts);
+        let missing = &snapshot[&(OTHER_METHOD.to_string(), tonic::Code::NotFound)];
+        assert_eq!(1, missing.count);
+    }
+
+    #[test]
+    fn test_latency_recorder() {
+        let recorder = metrics_util::debugging::DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let channel = ServiceBuilder::new()
+                    .layer(LatencyLayer::new())
+                    .service(tower::service_fn(echo));
+                let mut client = tonic::client::Grpc::new(channel);
+                for path in ["/test.Service/Slow", "/test.Service/Missing"] {
+                    client.ready().await.unwrap();
+                    let _ = client
+                        .unary(
+                            tonic::Request::new(Bytes::from_static(&[1])),
+                            http::uri::PathAndQuery::from_static(path),
+                            ProstCodec::<Bytes, Bytes>::default(),
+                        )
+                        .await;
+                }
+            })
+        });
+
+        let mut recorded: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                let metrics_util::debugging::DebugValue::Histogram(values) = value else {
+                    panic!("Expected a histogram, got {value:?}");
+                };
+                (key.name().to_string(), labels, values)
+            })
+            .collect();
+        recorded.sort_by(|a, b| a.1.cmp(&b.1));
+
+        assert_eq!(2, recorded.len());
+        let (name, labels, values) = &recorded[0];
+        assert_eq!(LATENCY_HISTOGRAM, name);
+        assert_eq!(
+            &vec!["method=/test.Service/Missing", "code=NotFound"],
+            labels
+        );
+        assert_eq!(1, values.len());
+        let (_, labels, values) = &recorded[1];
+        assert_eq!(&vec!["method=/test.Service/Slow", "code=Ok"], labels);
+        assert_eq!(1, values.len());
+        assert!(values[0].0 >= 0.03);
+    }
+}
@@ -0,0 +1,289 @@
+use std::future::{poll_fn, Future};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http_body::Body;
+use http_body_util::Full;
+use tonic::body::BoxBody;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{buffer, rejoin, BoxError, BoxFuture};
+
+type RefreshHook = Arc<dyn Fn() -> BoxFuture<()> + Send + Sync>;
+
+/// The state shared by all clones of a [ReauthLayer]
+struct Refresher {
+    hook: RefreshHook,
+    /// Counts the successful refreshes, guarded by an async lock, so only one
+    /// refresh runs at a time
+    generation: tokio::sync::Mutex<u64>,
+}
+
+impl Refresher {
+    /// Refreshes the credentials, unless another call did since `seen`
+    async fn refresh(&self, seen: u64) -> Result<(), BoxError> {
+        let mut generation = self.generation.lock().await;
+        if *generation != seen {
+            return Ok(());
+        }
+        (self.hook)().await?;
+        *generation += 1;
+        Ok(())
+    }
+}
+
+/// A client layer, that refreshes the credentials and retries once, when a
+/// call is rejected with UNAUTHENTICATED
+///
+/// Put the layer on top of the service adding the credentials, e.g. an
+/// `InterceptedService` with a token interceptor, so the retried request
+/// is intercepted again. The refresh hook must make that interceptor use
+/// new credentials, e.g. by forcing the token provider to refetch.
+///
+/// When several calls fail at the same time, the hook runs only once and
+/// the other calls retry with its result. A call is retried at most once,
+/// a second UNAUTHENTICATED is returned to the caller. Only unary calls are
+/// retried: the request body must be complete without waiting, and the
+/// status must be in the response headers, as servers send it when they
+/// reject a call.
+#[derive(Clone)]
+pub struct ReauthLayer {
+    refresher: Arc<Refresher>,
+}
+
+impl ReauthLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `hook`: The async function refreshing the credentials
+    pub fn new<F, Fut>(hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), BoxError>> + Send + 'static,
+    {
+        Self {
+            refresher: Arc::new(Refresher {
+                hook: Arc::new(move || Box::pin(hook())),
+                generation: tokio::sync::Mutex::new(0),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for ReauthLayer {
+    type Service = Reauth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Reauth {
+            inner,
+            refresher: self.refresher.clone(),
+        }
+    }
+}
+
+/// The service created by the [ReauthLayer]
+#[derive(Clone)]
+pub struct Reauth<S> {
+    inner: S,
+    refresher: Arc<Refresher>,
+}
+
+fn is_unauthenticated<B>(response: &http::Response<B>) -> bool {
+    tonic::Status::from_header_map(response.headers())
+        .is_some_and(|status| status.code() == tonic::Code::Unauthenticated)
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for Reauth<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    type Response = http::Response<ResBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Self::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        // Use the service, that was driven to readiness
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let refresher = self.refresher.clone();
+
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let (data, rest) = buffer(body).await?;
+            if let Some(rest) = rest {
+                // A streaming request can not be sent again
+                let req = http::Request::from_parts(parts, rejoin(data, rest));
+                return inner.call(req).await.map_err(Into::into);
+            }
+
+            let seen = *refresher.generation.lock().await;
+            let request = || {
+                http::Request::from_parts(
+                    parts.clone(),
+                    tonic::body::boxed(Full::new(data.clone())),
+                )
+            };
+            let response = inner.call(request()).await.map_err(Into::into)?;
+            if !is_unauthenticated(&response) {
+                return Ok(response);
+            }
+
+            log::debug!(
+                "Call to {} is unauthenticated, refreshing",
+                parts.uri.path()
+            );
+            if let Err(e) = refresher.refresh(seen).await {
+                log::warn!("Refreshing the credentials failed: {e}");
+                return Ok(response);
+            }
+            poll_fn(|cx| inner.poll_ready(cx))
+                .await
+                .map_err(Into::into)?;
+            inner.call(request()).await.map_err(Into::into)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use tonic::body::BoxBody;
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::Code;
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::reauth::ReauthLayer;
+
+    const MESSAGE: [u8; 6] = [0, 0, 0, 0, 1, 42];
+
+    struct Fixture {
+        token: Arc<Mutex<String>>,
+        upstream_calls: Arc<AtomicUsize>,
+        refreshes: Arc<AtomicUsize>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self {
+                token: Arc::new(Mutex::new(String::from("first"))),
+                upstream_calls: Arc::new(AtomicUsize::new(0)),
+                refreshes: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        /// A server accepting only the second token, behind an interceptor
+        /// sending the current token
+        fn service(
+            &self,
+            new_token: &'static str,
+        ) -> impl Service<
+            http::Request<Full<Bytes>>,
+            Response = http::Response<BoxBody>,
+            Error = crate::grpc::layer::BoxError,
+            Future = impl Send,
+        > + Clone {
+            let upstream_calls = self.upstream_calls.clone();
+            let server = tower::service_fn(move |req: http::Request<BoxBody>| {
+                upstream_calls.fetch_add(1, Ordering::SeqCst);
+                let status = match req.headers()["authorization"] == "Bearer second" {
+                    true => "0",
+                    false => "16",
+                };
+                async move {
+                    // Let concurrent calls reach the server
+                    tokio::task::yield_now().await;
+                    Ok::<_, Infallible>(
+                        http::Response::builder()
+                            .header("grpc-status", status)
+                            .body(tonic::body::empty_body())
+                            .unwrap(),
+                    )
+                }
+            });
+
+            let token = self.token.clone();
+            let interceptor = move |mut req: tonic::Request<()>| {
+                let value = format!("Bearer {}", token.lock().unwrap());
+                req.metadata_mut()
+                    .insert("authorization", value.parse().unwrap());
+                Ok(req)
+            };
+
+            let token = self.token.clone();
+            let refreshes = self.refreshes.clone();
+            ReauthLayer::new(move || {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                *token.lock().unwrap() = String::from(new_token);
+                async { Ok(()) }
+            })
+            .layer(InterceptedService::new(server, interceptor))
+        }
+    }
+
+    async fn call<S>(mut service: S) -> Code
+    where
+        S: Service<http::Request<Full<Bytes>>, Response = http::Response<BoxBody>>,
+        S::Error: std::fmt::Debug,
+    {
+        let req = http::Request::builder()
+            .uri("/test.Service/Method")
+            .body(Full::new(Bytes::from_static(&MESSAGE)))
+            .unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        tonic::Status::from_header_map(response.headers())
+            .unwrap()
+            .code()
+    }
+
+    #[tokio::test]
+    async fn test_transparent_recovery() {
+        let fixture = Fixture::new();
+        let service = fixture.service("second");
+
+        assert_eq!(Code::Ok, call(service.clone()).await);
+        assert_eq!(2, fixture.upstream_calls.load(Ordering::SeqCst));
+        assert_eq!(1, fixture.refreshes.load(Ordering::SeqCst));
+
+        assert_eq!(Code::Ok, call(service).await);
+        assert_eq!(3, fixture.upstream_calls.load(Ordering::SeqCst));
+        assert_eq!(1, fixture.refreshes.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_burst_refreshes_once() {
+        let fixture = Fixture::new();
+        let service = fixture.service("second");
+
+        let codes = futures_util::future::join_all((0..5).map(|_| call(service.clone()))).await;
+
+        assert!(codes.iter().all(|code| *code == Code::Ok));
+        assert_eq!(1, fixture.refreshes.load(Ordering::SeqCst));
+        assert_eq!(10, fixture.upstream_calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_second_unauthenticated_is_returned() {
+        let fixture = Fixture::new();
+        let service = fixture.service("revoked");
+
+        assert_eq!(Code::Unauthenticated, call(service).await);
+        assert_eq!(2, fixture.upstream_calls.load(Ordering::SeqCst));
+        assert_eq!(1, fixture.refreshes.load(Ordering::SeqCst));
+    }
+}
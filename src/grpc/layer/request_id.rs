@@ -0,0 +1,260 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use pin_project_lite::pin_project;
+use tokio::task::futures::TaskLocalFuture;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The header carrying the id of a request
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// The maximum length of an inbound id, longer ones are replaced
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: RequestId;
+}
+
+/// The id of the call a server is handling
+///
+/// The [RequestIdLayer] inserts it into the request extensions and sets it
+/// as the current id while the handler runs, so handlers can add it to
+/// their log records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId {
+    /// The id, as sent in the [X_REQUEST_ID] header
+    pub id: String,
+    /// Whether the id was generated, because the request had none
+    pub generated: bool,
+}
+
+impl RequestId {
+    /// Returns the id of the current task, if there is one
+    pub fn current() -> Option<Self> {
+        REQUEST_ID.try_with(Clone::clone).ok()
+    }
+
+    /// Runs the future with this id as the current one
+    /// # Arguments
+    /// * `future`: The future, e.g. a spawned part of a handler
+    pub fn scope<F: Future>(self, future: F) -> TaskLocalFuture<Self, F> {
+        REQUEST_ID.scope(self, future)
+    }
+}
+
+/// Returns a random UUID (version 4)
+fn generate() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// A server layer, that makes sure every call has an `x-request-id`
+///
+/// The id of the request is used, if it has a valid one, otherwise a random
+/// UUID is generated and added to the request. The id is inserted as
+/// [RequestId] into the request extensions, set as the current id while the
+/// handler runs and sent back in the response headers, which clients see as
+/// initial metadata, or as the metadata of the status of a failed call.
+///
+/// Use it with `tonic::transport::Server::builder().layer(...)`.
+#[derive(Clone, Debug, Default)]
+pub struct RequestIdLayer;
+
+impl RequestIdLayer {
+    /// Creates a new layer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+/// The service created by the [RequestIdLayer]
+#[derive(Clone, Debug)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for RequestIdService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let inbound = req
+            .headers()
+            .get(X_REQUEST_ID)
+            .filter(|value| !value.is_empty() && value.len() <= MAX_LENGTH)
+            .filter(|value| value.to_str().is_ok())
+            .cloned();
+        let (value, generated) = match inbound {
+            Some(value) => (value, false),
+            None => {
+                let value = http::HeaderValue::from_str(&generate())
+                    .expect("a UUID is a valid header value");
+                req.headers_mut().insert(X_REQUEST_ID, value.clone());
+                (value, true)
+            }
+        };
+
+        let request_id = RequestId {
+            id: value.to_str().unwrap_or_default().to_string(),
+            generated,
+        };
+        log::debug!(
+            "Handling {} with request id {}",
+            req.uri().path(),
+            request_id.id
+        );
+        req.extensions_mut().insert(request_id.clone());
+        ResponseFuture {
+            future: REQUEST_ID.scope(request_id, self.inner.call(req)),
+            value: Some(value),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [RequestIdService]
+    pub struct ResponseFuture<F> {
+        #[pin]
+        future: TaskLocalFuture<RequestId, F>,
+        value: Option<http::HeaderValue>,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<ResBody>, E>>,
+{
+    type Output = Result<http::Response<ResBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = ready!(this.future.poll(cx))?;
+        if let Some(value) = this.value.take() {
+            response.headers_mut().insert(X_REQUEST_ID, value);
+        }
+        Poll::Ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tokio::net::TcpListener;
+    use tonic::transport::{ClientTlsConfig, Endpoint};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::request_id::{RequestId, RequestIdLayer, X_REQUEST_ID};
+
+    #[tokio::test]
+    async fn test_handler_sees_id() {
+        let mut service =
+            RequestIdLayer::new().layer(tower::service_fn(|req: http::Request<()>| async move {
+                let current = RequestId::current().unwrap();
+                assert_eq!(Some(&current), req.extensions().get::<RequestId>());
+                assert_eq!(current.id, req.headers()[X_REQUEST_ID]);
+                Ok::<_, Infallible>(http::Response::new(current))
+            }));
+
+        let req = http::Request::builder()
+            .header(X_REQUEST_ID, "inbound")
+            .body(())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        assert_eq!("inbound", response.headers()[X_REQUEST_ID]);
+        assert_eq!(
+            &RequestId {
+                id: "inbound".to_string(),
+                generated: false
+            },
+            response.body()
+        );
+
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(http::Request::new(()))
+            .await
+            .unwrap();
+        let id = response.body();
+        assert!(id.generated);
+        assert_eq!(36, id.id.len());
+        assert_eq!(Some('4'), id.id.chars().nth(14));
+        assert_eq!(id.id, response.headers()[X_REQUEST_ID]);
+        assert_eq!(None, RequestId::current());
+    }
+
+    async fn call(endpoint: Endpoint, request_id: Option<&str>) -> tonic::Status {
+        let channel = crate::grpc::channel(ClientTlsConfig::new(), endpoint)
+            .await
+            .unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        let mut req = tonic::Request::new(());
+        if let Some(request_id) = request_id {
+            req.metadata_mut()
+                .insert(X_REQUEST_ID, request_id.parse().unwrap());
+        }
+        client
+            .unary(
+                req,
+                http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                tonic::codec::ProstCodec::<(), ()>::default(),
+            )
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .layer(RequestIdLayer::new())
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+
+        let status = call(endpoint.clone(), Some("trace-42")).await;
+        assert_eq!(tonic::Code::Unimplemented, status.code());
+        assert_eq!("trace-42", status.metadata().get(X_REQUEST_ID).unwrap());
+
+        let status = call(endpoint, None).await;
+        let id = status.metadata().get(X_REQUEST_ID).unwrap();
+        assert_eq!(36, id.len());
+    }
+}
@@ -0,0 +1,332 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use tonic::{body::BoxBody, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{BoxError, ResponseFuture};
+
+#[derive(Clone, Debug)]
+struct Limits {
+    default_limit: usize,
+    path_limits: Vec<(String, usize)>,
+}
+
+impl Limits {
+    /// Returns the limit of the longest matching path prefix
+    fn for_path(&self, path: &str) -> usize {
+        self.path_limits
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limit)| *limit)
+            .unwrap_or(self.default_limit)
+    }
+}
+
+fn too_large(limit: usize, observed: usize) -> Status {
+    Status::invalid_argument(format!(
+        "Request size {observed} bytes exceeds the limit of {limit} bytes"
+    ))
+}
+
+/// A server layer, that rejects requests with a body larger than a limit
+///
+/// If the request has a `content-length` header, oversized requests are
+/// rejected before the body is read. Otherwise the body frames are counted
+/// while the service reads them, and reading fails with
+/// [Status::invalid_argument] as soon as the limit is exceeded. Both errors
+/// contain the limit and the observed size.
+///
+/// Use it with `tonic::transport::Server::builder().layer(...)`.
+#[derive(Clone, Debug)]
+pub struct RequestSizeLimitLayer {
+    limits: Limits,
+}
+
+impl RequestSizeLimitLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `limit`: The maximum size of a request body in bytes
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limits: Limits {
+                default_limit: limit,
+                path_limits: Vec::new(),
+            },
+        }
+    }
+
+    /// Sets a different limit for all paths starting with the prefix
+    ///
+    /// When several prefixes match, the longest one wins.
+    /// # Arguments
+    /// * `prefix`: The path prefix, e.g. `/upload.UploadService/`
+    /// * `limit`: The maximum size of a request body in bytes
+    pub fn with_path_limit(mut self, prefix: &str, limit: usize) -> Self {
+        self.limits.path_limits.push((prefix.to_string(), limit));
+        self
+    }
+}
+
+impl<S> Layer<S> for RequestSizeLimitLayer {
+    type Service = RequestSizeLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestSizeLimit {
+            inner,
+            limits: Arc::new(self.limits.clone()),
+        }
+    }
+}
+
+/// The service created by the [RequestSizeLimitLayer]
+#[derive(Clone, Debug)]
+pub struct RequestSizeLimit<S> {
+    inner: S,
+    limits: Arc<Limits>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for RequestSizeLimit<S>
+where
+    S: Service<http::Request<LimitedBody<ReqBody>>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let limit = self.limits.for_path(req.uri().path());
+        let content_length = req
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+
+        if let Some(observed) = content_length.filter(|length| *length > limit) {
+            log::debug!(
+                "Rejected request to {} with {observed} bytes",
+                req.uri().path()
+            );
+            return ResponseFuture::Rejected {
+                status: Some(too_large(limit, observed)),
+            };
+        }
+
+        let req = req.map(|inner| LimitedBody {
+            inner,
+            limit,
+            observed: 0,
+        });
+        ResponseFuture::Inner {
+            future: self.inner.call(req),
+        }
+    }
+}
+
+pin_project! {
+    /// A request body, that fails when more than the limit is read
+    pub struct LimitedBody<B> {
+        #[pin]
+        inner: B,
+        limit: usize,
+        observed: usize,
+    }
+}
+
+impl<B> Body for LimitedBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        match ready!(this.inner.poll_frame(cx)) {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    *this.observed += data.len();
+                    if *this.observed > *this.limit {
+                        let status = too_large(*this.limit, *this.observed);
+                        return Poll::Ready(Some(Err(Box::new(status))));
+                    }
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use http_body::{Body, Frame};
+    use http_body_util::{BodyExt, Full};
+    use tonic::body::BoxBody;
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::request_size::{LimitedBody, RequestSizeLimitLayer};
+
+    /// A body sending its chunks without a content length
+    struct Chunks(VecDeque<Bytes>);
+
+    impl Body for Chunks {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Ready(self.0.pop_front().map(|chunk| Ok(Frame::data(chunk))))
+        }
+    }
+
+    /// Reads the whole body like a tonic service would, returning the status
+    async fn read_body<B>(
+        req: http::Request<LimitedBody<B>>,
+    ) -> Result<http::Response<BoxBody>, Infallible>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: Into<crate::grpc::layer::BoxError>,
+    {
+        Ok(match req.into_body().collect().await {
+            Ok(_) => Status::ok("").into_http(),
+            Err(e) => Status::from_error(e).into_http(),
+        })
+    }
+
+    async fn call<B>(
+        layer: &RequestSizeLimitLayer,
+        path: &str,
+        body: B,
+        size: Option<usize>,
+    ) -> Status
+    where
+        B: Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<crate::grpc::layer::BoxError>,
+    {
+        let mut service = layer.layer(tower::service_fn(read_body::<B>));
+        let mut req = http::Request::builder().uri(path);
+        if let Some(size) = size {
+            req = req.header(http::header::CONTENT_LENGTH, size);
+        }
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(body).unwrap())
+            .await
+            .unwrap();
+        Status::from_header_map(response.headers()).unwrap()
+    }
+
+    async fn unary(layer: &RequestSizeLimitLayer, path: &str, size: usize) -> Status {
+        let body = Full::new(Bytes::from(vec![0; size]));
+        call(layer, path, body, Some(size)).await
+    }
+
+    async fn streaming(layer: &RequestSizeLimitLayer, path: &str, size: usize) -> Status {
+        let chunks = (0..size).map(|_| Bytes::from_static(&[0])).collect();
+        call(layer, path, Chunks(chunks), None).await
+    }
+
+    #[tokio::test]
+    async fn test_unary() {
+        let layer = RequestSizeLimitLayer::new(10);
+
+        assert_eq!(
+            Code::Ok,
+            unary(&layer, "/test.Service/Method", 9).await.code()
+        );
+        assert_eq!(
+            Code::Ok,
+            unary(&layer, "/test.Service/Method", 10).await.code()
+        );
+
+        let status = unary(&layer, "/test.Service/Method", 11).await;
+        assert_eq!(Code::InvalidArgument, status.code());
+        assert_eq!(
+            "Request size 11 bytes exceeds the limit of 10 bytes",
+            status.message()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming() {
+        let layer = RequestSizeLimitLayer::new(10);
+
+        assert_eq!(
+            Code::Ok,
+            streaming(&layer, "/test.Service/Method", 9).await.code()
+        );
+        assert_eq!(
+            Code::Ok,
+            streaming(&layer, "/test.Service/Method", 10).await.code()
+        );
+
+        let status = streaming(&layer, "/test.Service/Method", 11).await;
+        assert_eq!(Code::InvalidArgument, status.code());
+        assert_eq!(
+            "Request size 11 bytes exceeds the limit of 10 bytes",
+            status.message()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_limits() {
+        let layer = RequestSizeLimitLayer::new(10)
+            .with_path_limit("/upload.", 100)
+            .with_path_limit("/upload.Service/", 1000);
+
+        assert_eq!(
+            Code::Ok,
+            unary(&layer, "/upload.Service/Put", 1000).await.code()
+        );
+        assert_eq!(
+            Code::Ok,
+            streaming(&layer, "/upload.Other/Put", 100).await.code()
+        );
+        assert_eq!(
+            Code::InvalidArgument,
+            unary(&layer, "/upload.Other/Put", 101).await.code()
+        );
+        assert_eq!(
+            Code::InvalidArgument,
+            unary(&layer, "/test.Service/Method", 11).await.code()
+        );
+    }
+}
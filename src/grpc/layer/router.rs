@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http_body::Body;
+use tonic::body::BoxBody;
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::interceptor::{CompositeInterceptor, Interceptors};
+use crate::grpc::layer::{BoxError, ResponseFuture};
+
+#[derive(Clone)]
+struct Routes {
+    default: Interceptors,
+    routes: Vec<(String, Interceptors)>,
+}
+
+impl Routes {
+    fn chain_for(&self, path: &str) -> Interceptors {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map(|(_, chain)| chain.clone())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+/// A client layer, that applies a different interceptor chain per service
+///
+/// An [Interceptor] does not see the path of the request, so a channel
+/// shared by several services would send the credentials of all of them
+/// to each one. This layer matches the path against the registered
+/// prefixes, e.g. `/package.Service/`, and calls only the chain of the
+/// matching prefix, or the default chain. When a chain fails, the call is
+/// answered with its status without being sent.
+#[derive(Clone)]
+pub struct ServiceRouterLayer {
+    routes: Routes,
+}
+
+impl ServiceRouterLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `default`: The chain for requests, that match no prefix
+    pub fn new(default: Interceptors) -> Self {
+        Self {
+            routes: Routes {
+                default,
+                routes: Vec::new(),
+            },
+        }
+    }
+
+    /// Registers the chain for all paths starting with the prefix
+    ///
+    /// Fails with [Status::invalid_argument], if the prefix overlaps an
+    /// already registered one, as it would be ambiguous which chain applies.
+    /// # Arguments
+    /// * `prefix`: The path prefix, e.g. `/package.Service/`
+    /// * `chain`: The interceptors for the matching requests
+    pub fn route(mut self, prefix: &str, chain: Interceptors) -> Result<Self, Status> {
+        if !prefix.starts_with('/') {
+            return Err(Status::invalid_argument(format!(
+                "Prefix {prefix} does not start with /"
+            )));
+        }
+        if let Some((existing, _)) = self.routes.routes.iter().find(|(existing, _)| {
+            existing.starts_with(prefix) || prefix.starts_with(existing.as_str())
+        }) {
+            return Err(Status::invalid_argument(format!(
+                "Prefix {prefix} overlaps {existing}"
+            )));
+        }
+        self.routes.routes.push((prefix.to_string(), chain));
+        Ok(self)
+    }
+}
+
+impl<S> Layer<S> for ServiceRouterLayer {
+    type Service = ServiceRouter<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ServiceRouter {
+            inner,
+            routes: Arc::new(self.routes.clone()),
+        }
+    }
+}
+
+/// The service created by the [ServiceRouterLayer]
+#[derive(Clone)]
+pub struct ServiceRouter<S> {
+    inner: S,
+    routes: Arc<Routes>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ServiceRouter<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let chain = self.routes.chain_for(req.uri().path());
+        let (mut parts, body) = req.into_parts();
+        let request = tonic::Request::from_parts(
+            MetadataMap::from_headers(std::mem::take(&mut parts.headers)),
+            std::mem::take(&mut parts.extensions),
+            (),
+        );
+
+        match CompositeInterceptor::new(chain).call(request) {
+            Ok(request) => {
+                let (metadata, extensions, ()) = request.into_parts();
+                parts.headers = metadata.into_headers();
+                parts.extensions = extensions;
+                ResponseFuture::Inner {
+                    future: self.inner.call(http::Request::from_parts(parts, body)),
+                }
+            }
+            Err(status) => ResponseFuture::Rejected {
+                status: Some(status),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::interceptor::{APIKeyClientInterceptor, BearerTokenInterceptor};
+    use crate::grpc::layer::router::ServiceRouterLayer;
+    use crate::interceptors;
+
+    type Received = Arc<Mutex<HashMap<String, http::HeaderMap>>>;
+
+    fn layer() -> ServiceRouterLayer {
+        ServiceRouterLayer::new(interceptors!())
+            .route(
+                "/a.ServiceA/",
+                interceptors!(APIKeyClientInterceptor::try_new("key-a").unwrap()),
+            )
+            .unwrap()
+            .route(
+                "/b.ServiceB/",
+                interceptors!(BearerTokenInterceptor::try_new("token-b").unwrap()),
+            )
+            .unwrap()
+            .route(
+                "/c.ServiceC/",
+                interceptors!(|_req: tonic::Request<()>| Err(Status::permission_denied(
+                    "no access"
+                ))),
+            )
+            .unwrap()
+    }
+
+    async fn call(received: &Received, path: &str) -> Code {
+        let captured = received.clone();
+        let mut service = layer().layer(tower::service_fn(move |req: http::Request<()>| {
+            captured
+                .lock()
+                .unwrap()
+                .insert(req.uri().path().to_string(), req.headers().clone());
+            async { Ok::<_, Infallible>(Status::ok("").into_http()) }
+        }));
+        let req = http::Request::builder().uri(path).body(()).unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        Status::from_header_map(response.headers()).unwrap().code()
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let received = Received::default();
+        assert_eq!(Code::Ok, call(&received, "/a.ServiceA/Get").await);
+        assert_eq!(Code::Ok, call(&received, "/b.ServiceB/Get").await);
+        assert_eq!(Code::Ok, call(&received, "/d.ServiceD/Get").await);
+        assert_eq!(
+            Code::PermissionDenied,
+            call(&received, "/c.ServiceC/Get").await
+        );
+
+        let received = received.lock().unwrap();
+        let a = &received["/a.ServiceA/Get"];
+        assert_eq!("key-a", a["x-api-key"]);
+        assert!(!a.contains_key("authorization"));
+
+        let b = &received["/b.ServiceB/Get"];
+        assert_eq!("Bearer token-b", b["authorization"]);
+        assert!(!b.contains_key("x-api-key"));
+
+        let d = &received["/d.ServiceD/Get"];
+        assert!(!d.contains_key("x-api-key") && !d.contains_key("authorization"));
+        assert!(!received.contains_key("/c.ServiceC/Get"));
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        let layer = ServiceRouterLayer::new(interceptors!())
+            .route("/a.ServiceA/", interceptors!())
+            .unwrap();
+        assert!(layer
+            .clone()
+            .route("/a.ServiceA/", interceptors!())
+            .is_err());
+        assert!(layer.clone().route("/a.", interceptors!()).is_err());
+        assert!(layer.clone().route("a.ServiceB/", interceptors!()).is_err());
+        assert!(layer.route("/a.ServiceB/", interceptors!()).is_ok());
+    }
+}
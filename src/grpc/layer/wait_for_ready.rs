@@ -0,0 +1,279 @@
+use std::future::poll_fn;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body::Body;
+use http_body_util::Full;
+use tokio::time::Instant;
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::layer::{buffer, rejoin, BoxError, BoxFuture};
+
+/// The time between two attempts to reach a channel, that is not connected
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A client layer, that lets calls wait for the channel to connect, instead
+/// of failing with `UNAVAILABLE` (the `wait_for_ready` of gRPC)
+///
+/// The layer polls the readiness of the inner service before sending the
+/// call. A call failing, because the connection could not be established,
+/// is sent again, until the timeout has passed. Then it fails with
+/// `DEADLINE_EXCEEDED`. The timeout only covers the waiting: a call, that
+/// reached the server, is not cancelled. Put the layer on top of a lazy
+/// channel, e.g. of [Endpoint::connect_lazy](tonic::transport::Endpoint::connect_lazy).
+///
+/// The request body is buffered to send it again, so a request, whose body
+/// is still open, e.g. of a client streaming call, is only sent once.
+#[derive(Clone, Debug)]
+pub struct WaitForReadyLayer {
+    timeout: Duration,
+}
+
+impl WaitForReadyLayer {
+    /// Creates a new layer
+    /// # Arguments
+    /// * `timeout`: The maximum time a call waits for the channel
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for WaitForReadyLayer {
+    type Service = WaitForReady<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WaitForReady {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// The service created by the [WaitForReadyLayer]
+#[derive(Clone, Debug)]
+pub struct WaitForReady<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for WaitForReady<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    type Response = http::Response<ResBody>;
+    type Error = BoxError;
+    type Future = BoxFuture<Self::Response>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // The readiness of the inner service is awaited in the call
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(wait_for_ready(inner, req, Instant::now() + self.timeout))
+    }
+}
+
+/// Returns whether the error tells, that the connection was not established
+fn is_not_connected(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            return matches!(
+                error.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::AddrNotAvailable
+                    | std::io::ErrorKind::HostUnreachable
+                    | std::io::ErrorKind::NetworkUnreachable
+            );
+        }
+        source = error.source();
+    }
+    false
+}
+
+fn deadline_exceeded() -> BoxError {
+    Box::new(Status::deadline_exceeded(
+        "The channel was not ready before the deadline",
+    ))
+}
+
+async fn wait_for_ready<S, ReqBody, ResBody>(
+    mut inner: S,
+    req: http::Request<ReqBody>,
+    deadline: Instant,
+) -> Result<http::Response<ResBody>, BoxError>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body<Data = Bytes> + Send + 'static,
+    ReqBody::Error: Into<BoxError>,
+{
+    let (parts, body) = req.into_parts();
+    let (body, rest) = buffer(body).await?;
+    let streaming = rest.is_some();
+    let mut rest = rest.map(|rest| rejoin(body.clone(), rest));
+    loop {
+        tokio::time::timeout_at(deadline, poll_fn(|cx| inner.poll_ready(cx)))
+            .await
+            .map_err(|_| deadline_exceeded())?
+            .map_err(Into::into)?;
+
+        let body = match rest.take() {
+            Some(rest) => rest,
+            None => tonic::body::boxed(Full::new(body.clone())),
+        };
+        let e = match inner
+            .call(http::Request::from_parts(parts.clone(), body))
+            .await
+        {
+            Ok(response) => return Ok(response),
+            Err(e) => e.into(),
+        };
+        if streaming || !is_not_connected(e.as_ref()) {
+            return Err(e);
+        }
+        log::debug!("Waiting for the channel of {}: {e}", parts.uri.path());
+        if Instant::now() + RETRY_INTERVAL >= deadline {
+            return Err(deadline_exceeded());
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use tokio::net::TcpListener;
+    use tonic::body::BoxBody;
+    use tonic::server::NamedService;
+    use tonic::transport::{Channel, Endpoint};
+    use tonic::{Code, Status};
+    use tower::{Service, ServiceExt};
+    use tower_layer::Layer;
+
+    use crate::grpc::layer::wait_for_ready::WaitForReadyLayer;
+    use crate::grpc::layer::BoxError;
+
+    /// A server answering every call with an OK status
+    #[derive(Clone)]
+    struct Backend;
+
+    impl NamedService for Backend {
+        const NAME: &'static str = "test.Service";
+    }
+
+    impl Service<http::Request<BoxBody>> for Backend {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<BoxBody>) -> Self::Future {
+            Box::pin(async { Ok(Status::ok("").into_http()) })
+        }
+    }
+
+    /// Returns a free address and a lazy channel to it
+    async fn closed_port() -> (SocketAddr, Channel) {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let channel = Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect_lazy();
+        (addr, channel)
+    }
+
+    /// Starts the server on the address after the delay
+    fn serve_later(addr: SocketAddr, delay: Duration) {
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            tonic::transport::Server::builder()
+                .add_service(Backend)
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+    }
+
+    async fn call<S>(service: &mut S) -> Result<http::Response<BoxBody>, BoxError>
+    where
+        S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>,
+        S::Error: Into<BoxError>,
+    {
+        let req = http::Request::builder()
+            .uri("/test.Service/Get")
+            .body(tonic::body::boxed(Full::new(Bytes::from_static(&[
+                0, 0, 0, 0, 1, 42,
+            ]))))
+            .unwrap();
+        ServiceExt::<http::Request<BoxBody>>::ready(service)
+            .await
+            .map_err(Into::into)?
+            .call(req)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_late_server() {
+        let (addr, channel) = closed_port().await;
+        let mut service = WaitForReadyLayer::new(Duration::from_secs(5)).layer(channel);
+        serve_later(addr, Duration::from_secs(1));
+
+        let start = Instant::now();
+        let response = call(&mut service).await.unwrap();
+        assert_eq!(
+            Some(Code::Ok),
+            Status::from_header_map(response.headers()).map(|s| s.code())
+        );
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_fails_fast_without_layer() {
+        let (addr, mut channel) = closed_port().await;
+        serve_later(addr, Duration::from_secs(1));
+
+        let start = Instant::now();
+        assert!(call(&mut channel).await.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_exceeded() {
+        let (_, channel) = closed_port().await;
+        let mut service = WaitForReadyLayer::new(Duration::from_millis(300)).layer(channel);
+
+        let error = call(&mut service).await.unwrap_err();
+        let status = error.downcast::<Status>().unwrap();
+        assert_eq!(Code::DeadlineExceeded, status.code());
+    }
+}
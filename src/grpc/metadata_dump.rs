@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+
+/// The number of trailing characters shown of values, that are not fully
+/// redacted
+const VISIBLE_SUFFIX: usize = 4;
+
+/// Defines, how the values of a [MetadataDump] are redacted
+///
+/// The values of redacted keys are replaced entirely. All other ASCII values
+/// only show their last characters, binary (`-bin`) values only their
+/// length.
+#[derive(Clone, Debug)]
+pub struct RedactionPolicy {
+    redacted_keys: HashSet<String>,
+    visible_suffix: usize,
+}
+
+impl Default for RedactionPolicy {
+    /// Redacts the credential headers of this crate and of HTTP
+    fn default() -> Self {
+        Self::new([
+            "authorization",
+            "x-api-key",
+            "cookie",
+            "proxy-authorization",
+        ])
+    }
+}
+
+impl RedactionPolicy {
+    /// Creates a new policy
+    /// # Arguments
+    /// * `redacted_keys`: The keys, whose values are fully redacted
+    pub fn new<'a>(redacted_keys: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            redacted_keys: redacted_keys
+                .into_iter()
+                .map(str::to_ascii_lowercase)
+                .collect(),
+            visible_suffix: VISIBLE_SUFFIX,
+        }
+    }
+
+    /// Also fully redacts the values of the key
+    pub fn with_redacted_key(mut self, key: &str) -> Self {
+        self.redacted_keys.insert(key.to_ascii_lowercase());
+        self
+    }
+
+    /// Sets the number of trailing characters shown, 4 by default
+    pub fn with_visible_suffix(mut self, visible_suffix: usize) -> Self {
+        self.visible_suffix = visible_suffix;
+        self
+    }
+
+    fn redact_ascii(&self, key: &str, value: &[u8]) -> String {
+        if self.redacted_keys.contains(key) {
+            return "<redacted>".to_string();
+        }
+        let Ok(value) = std::str::from_utf8(value) else {
+            return format!("<{} bytes>", value.len());
+        };
+        let chars: Vec<char> = value.chars().collect();
+        // Short values would be shown completely
+        if chars.len() <= self.visible_suffix {
+            return "***".to_string();
+        }
+        let suffix: String = chars[chars.len() - self.visible_suffix..].iter().collect();
+        format!("***{suffix}")
+    }
+}
+
+/// One value of a [MetadataDump]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DumpEntry {
+    /// The metadata key
+    pub key: String,
+    /// The redacted value
+    pub value: String,
+}
+
+/// The redacted entries of a [MetadataMap], e.g. to attach to a bug report
+///
+/// The entries are sorted by key, values of the same key keep their order,
+/// so the same metadata always gives the same dump. The [Display](fmt::Display)
+/// output has one `key: value` line per entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MetadataDump {
+    /// The entries
+    pub entries: Vec<DumpEntry>,
+}
+
+impl MetadataDump {
+    /// Creates the dump of the metadata
+    /// # Arguments
+    /// * `map`: The metadata, e.g. of a request
+    /// * `policy`: The redaction of the values
+    pub fn new(map: &MetadataMap, policy: &RedactionPolicy) -> Self {
+        let mut entries: Vec<DumpEntry> = map
+            .iter()
+            .map(|entry| match entry {
+                KeyAndValueRef::Ascii(key, value) => DumpEntry {
+                    key: key.to_string(),
+                    value: policy.redact_ascii(key.as_str(), value.as_bytes()),
+                },
+                KeyAndValueRef::Binary(key, value) => DumpEntry {
+                    key: key.to_string(),
+                    value: match value.to_bytes() {
+                        Ok(bytes) => format!("<{} bytes>", bytes.len()),
+                        Err(_) => "<invalid base64>".to_string(),
+                    },
+                },
+            })
+            .collect();
+        // The sort is stable, so values of a key keep their order
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        Self { entries }
+    }
+}
+
+impl fmt::Display for MetadataDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", entry.key, entry.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the redacted metadata with one `key: value` line per entry, see
+/// [MetadataDump]
+/// # Arguments
+/// * `map`: The metadata, e.g. of a request
+/// * `policy`: The redaction of the values
+pub fn metadata_dump(map: &MetadataMap, policy: &RedactionPolicy) -> String {
+    MetadataDump::new(map, policy).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::metadata::{MetadataMap, MetadataValue};
+
+    use crate::grpc::metadata_dump::{metadata_dump, DumpEntry, MetadataDump, RedactionPolicy};
+
+    fn metadata() -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("x-tenant", "tenant-acme".parse().unwrap());
+        metadata.insert("authorization", "Bearer s3cr3t-t0ken".parse().unwrap());
+        metadata.insert("x-api-key", "api-key-9876".parse().unwrap());
+        metadata.append("x-trace", "first-0001".parse().unwrap());
+        metadata.append("x-trace", "second-0002".parse().unwrap());
+        metadata.insert("x-short", "abc".parse().unwrap());
+        metadata.insert_bin("x-signature-bin", MetadataValue::from_bytes(b"signed"));
+        metadata
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            "authorization: <redacted>
+x-api-key: <redacted>
+x-short: ***
+x-signature-bin: <6 bytes>
+x-tenant: ***acme
+x-trace: ***0001
+x-trace: ***0002",
+            metadata_dump(&metadata(), &RedactionPolicy::default())
+        );
+        assert_eq!(
+            "",
+            metadata_dump(&MetadataMap::new(), &RedactionPolicy::default())
+        );
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = RedactionPolicy::new(["X-Tenant"]).with_visible_suffix(2);
+        let dump = MetadataDump::new(&metadata(), &policy);
+
+        assert_eq!(
+            DumpEntry {
+                key: "authorization".to_string(),
+                value: "***en".to_string()
+            },
+            dump.entries[0]
+        );
+        assert!(dump.entries.contains(&DumpEntry {
+            key: "x-tenant".to_string(),
+            value: "<redacted>".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_no_secrets() {
+        let policy = RedactionPolicy::default().with_redacted_key("x-tenant");
+        let dump = metadata_dump(&metadata(), &policy);
+
+        for secret in ["s3cr3t", "t0ken", "9876", "acme", "signed", "c2lnbmVk"] {
+            assert!(!dump.contains(secret), "{secret} in {dump}");
+        }
+    }
+}
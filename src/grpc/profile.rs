@@ -0,0 +1,968 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tonic::transport::{ClientTlsConfig, Endpoint};
+
+use crate::grpc::client::{ClientFactory, FactoryService};
+use crate::grpc::connection::ChannelError;
+use crate::grpc::interceptor::client_info::ClientInfoInterceptor;
+use crate::grpc::interceptor::{
+    APIKeyClientInterceptor, BearerTokenInterceptor, BoxedInterceptor, Interceptors,
+};
+use crate::grpc::tls::tls_from_ca_pem_bytes;
+use crate::grpc::warm_up::WarmUpOptions;
+
+/// The keys of a profile by section
+const KEYS: [(&str, &[&str]); 4] = [
+    ("endpoint", &["uri", "timeout_ms", "connect_timeout_ms"]),
+    ("tls", &["ca_file", "domain"]),
+    ("auth", &["api_key", "api_key_header", "bearer_token"]),
+    ("client_info", &["app_name"]),
+];
+
+/// The errors of loading a [ClientProfile]
+#[derive(Debug)]
+pub enum ProfileError {
+    /// The file can not be read
+    Read(std::io::Error),
+    /// The file is no valid TOML
+    Parse(toml::de::Error),
+    /// A value is missing or invalid
+    Invalid {
+        /// The path of the key, e.g. `endpoint.timeout_ms`
+        key: String,
+        /// Why the value is invalid
+        reason: String,
+    },
+    /// A `${NAME}` reference names an environment variable, that is not set
+    MissingVariable {
+        /// The path of the key containing the reference
+        key: String,
+        /// The name of the variable
+        variable: String,
+    },
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Read(e) => write!(f, "Reading the profile failed: {e}"),
+            ProfileError::Parse(e) => write!(f, "The profile is no valid TOML: {e}"),
+            ProfileError::Invalid { key, reason } => write!(f, "The key {key} {reason}"),
+            ProfileError::MissingVariable { key, variable } => write!(
+                f,
+                "The key {key} references the environment variable {variable}, which is not set"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProfileError::Read(e) => Some(e),
+            ProfileError::Parse(e) => Some(e),
+            ProfileError::Invalid { .. } | ProfileError::MissingVariable { .. } => None,
+        }
+    }
+}
+
+fn invalid(key: &str, reason: impl std::fmt::Display) -> ProfileError {
+    ProfileError::Invalid {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// How bad a [ValidationIssue] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting is ignored or questionable, the profile still loads
+    Warning,
+    /// The profile does not load or can not connect
+    Error,
+}
+
+/// A problem of a profile found by [ClientProfile::validate]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// How bad the problem is
+    pub severity: Severity,
+    /// The path of the key, e.g. `endpoint.timeout_ms`, or empty for the
+    /// whole file
+    pub key: String,
+    /// What is wrong
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(severity: Severity, error: &ProfileError) -> Self {
+        let key = match error {
+            ProfileError::Read(_) | ProfileError::Parse(_) => "",
+            ProfileError::Invalid { key, .. } | ProfileError::MissingVariable { key, .. } => key,
+        };
+        Self {
+            severity,
+            key: key.to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// The problems found while reading a profile, in the order of the checks
+#[derive(Default)]
+struct Issues(Vec<(Severity, ProfileError)>);
+
+impl Issues {
+    /// Records the error of a check and returns its value otherwise
+    fn check<T>(&mut self, result: Result<T, ProfileError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.error(e);
+                None
+            }
+        }
+    }
+
+    fn error(&mut self, error: ProfileError) {
+        self.0.push((Severity::Error, error));
+    }
+
+    fn warn(&mut self, key: &str, reason: &str) {
+        self.0.push((Severity::Warning, invalid(key, reason)));
+    }
+
+    fn has_errors(&self) -> bool {
+        self.0
+            .iter()
+            .any(|(severity, _)| *severity == Severity::Error)
+    }
+
+    fn first_error(self) -> Option<ProfileError> {
+        self.0
+            .into_iter()
+            .find_map(|(severity, e)| (severity == Severity::Error).then_some(e))
+    }
+
+    fn report(&self) -> Vec<ValidationIssue> {
+        self.0
+            .iter()
+            .map(|(severity, e)| ValidationIssue::new(*severity, e))
+            .collect()
+    }
+}
+
+/// The values of a profile file and their overrides from the environment
+struct Source<'a> {
+    table: toml::Table,
+    prefix: &'a str,
+    env: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Source<'_> {
+    /// Reports every section and key, that is not known
+    fn check_keys(&self, issues: &mut Issues) {
+        for (section, value) in &self.table {
+            let Some((_, keys)) = KEYS.iter().find(|(name, _)| name == section) else {
+                issues.error(invalid(section, "is no known section"));
+                continue;
+            };
+            let Some(table) = value.as_table() else {
+                issues.error(invalid(section, "must be a table"));
+                continue;
+            };
+            for key in table.keys().filter(|key| !keys.contains(&key.as_str())) {
+                issues.error(invalid(&format!("{section}.{key}"), "is no known key"));
+            }
+        }
+    }
+
+    /// Returns the name of the environment variable overriding the key
+    fn variable(&self, key: &str) -> String {
+        format!("{}_{}", self.prefix, key.replace('.', "_")).to_ascii_uppercase()
+    }
+
+    fn file(&self, key: &str) -> Option<&toml::Value> {
+        let (section, name) = key.split_once('.')?;
+        self.table.get(section)?.get(name)
+    }
+
+    /// Returns the string value of the key with its references expanded
+    fn string(&self, key: &str) -> Result<Option<String>, ProfileError> {
+        let value = match (self.env)(&self.variable(key)) {
+            Some(value) => value,
+            None => match self.file(key) {
+                None => return Ok(None),
+                Some(toml::Value::String(value)) => value.clone(),
+                Some(_) => return Err(invalid(key, "must be a string")),
+            },
+        };
+        self.expand(key, &value).map(Some)
+    }
+
+    /// Returns the duration of a key in milliseconds
+    fn millis(&self, key: &str) -> Result<Option<Duration>, ProfileError> {
+        let millis = match (self.env)(&self.variable(key)) {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(key, format!("must be milliseconds, got {value:?}")))?,
+            None => match self.file(key) {
+                None => return Ok(None),
+                Some(toml::Value::Integer(millis)) if *millis >= 0 => *millis as u64,
+                Some(_) => return Err(invalid(key, "must be a non-negative integer")),
+            },
+        };
+        Ok(Some(Duration::from_millis(millis)))
+    }
+
+    /// Returns the string value of a credential, that must not be empty
+    fn credential(&self, key: &str) -> Result<Option<String>, ProfileError> {
+        match self.string(key)? {
+            Some(value) if value.is_empty() => Err(invalid(key, "must not be empty")),
+            value => Ok(value),
+        }
+    }
+
+    fn endpoint(&self) -> Result<Endpoint, ProfileError> {
+        let uri = self
+            .string("endpoint.uri")?
+            .ok_or_else(|| invalid("endpoint.uri", "is required"))?;
+        Endpoint::from_shared(uri).map_err(|e| invalid("endpoint.uri", e))
+    }
+
+    /// Returns the TLS configuration trusting `tls.ca_file` or the system
+    /// roots, and the path of the file
+    fn ca(&self, base: Option<&Path>) -> Result<(ClientTlsConfig, Option<PathBuf>), ProfileError> {
+        match self.string("tls.ca_file")? {
+            Some(file) => {
+                let path = base.unwrap_or(Path::new("")).join(file);
+                let pem = std::fs::read(&path)
+                    .map_err(|e| invalid("tls.ca_file", format!("can not be read: {e}")))?;
+                let tls = tls_from_ca_pem_bytes(pem).map_err(|e| invalid("tls.ca_file", e))?;
+                Ok((tls, Some(path)))
+            }
+            None => Ok((ClientTlsConfig::new().with_enabled_roots(), None)),
+        }
+    }
+
+    fn api_key(&self) -> Result<Option<APIKeyClientInterceptor>, ProfileError> {
+        let header = self.string("auth.api_key_header")?;
+        let Some(api_key) = self.credential("auth.api_key")? else {
+            return match header {
+                Some(_) => Err(invalid("auth.api_key_header", "requires auth.api_key")),
+                None => Ok(None),
+            };
+        };
+        let mut interceptor = APIKeyClientInterceptor::try_new(&api_key)
+            .map_err(|_| invalid("auth.api_key", "is no valid header value"))?;
+        if let Some(header) = header {
+            interceptor = interceptor
+                .with_header_name(&header)
+                .map_err(|_| invalid("auth.api_key_header", "is no valid header name"))?;
+        }
+        Ok(Some(interceptor))
+    }
+
+    fn bearer_token(&self) -> Result<Option<BoxedInterceptor>, ProfileError> {
+        let Some(token) = self.credential("auth.bearer_token")? else {
+            return Ok(None);
+        };
+        let interceptor = BearerTokenInterceptor::try_new(&token)
+            .map_err(|_| invalid("auth.bearer_token", "is no valid header value"))?;
+        Ok(Some(Box::new(interceptor)))
+    }
+
+    /// Replaces the `${NAME}` references by the environment variables
+    fn expand(&self, key: &str, value: &str) -> Result<String, ProfileError> {
+        let mut expanded = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid(key, "has an unterminated ${ reference"))?;
+            let variable = &rest[start + 2..start + end];
+            let resolved = (self.env)(variable).ok_or_else(|| ProfileError::MissingVariable {
+                key: key.to_string(),
+                variable: variable.to_string(),
+            })?;
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&resolved);
+            rest = &rest[start + end + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// How a channel of a profile trusts the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TlsMode {
+    /// The endpoint is no `https` endpoint
+    Plaintext,
+    /// TLS trusting the system roots
+    SystemRoots,
+    /// TLS trusting the CA certificates of the file
+    CaFile(PathBuf),
+}
+
+/// The settings a channel of a [ClientProfile] is built with, after the
+/// defaults of the crate are applied
+///
+/// Secrets are left out: for the credentials only the API key header and
+/// whether there is a bearer token are kept. Compare it in tests to notice,
+/// when a default changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EffectiveChannelConfig {
+    /// The URI of the endpoint
+    pub uri: String,
+    /// The timeout of a request
+    pub timeout_ms: Option<u64>,
+    /// The timeout of connecting
+    pub connect_timeout_ms: Option<u64>,
+    /// The TCP keepalive interval
+    pub tcp_keepalive_ms: Option<u64>,
+    /// Whether HTTP/2 keepalive pings are sent without active calls
+    pub keep_alive_while_idle: bool,
+    /// How the server is trusted
+    pub tls: TlsMode,
+    /// The domain name the server certificate is checked against
+    pub domain: Option<String>,
+    /// The application name sent by the client info interceptor
+    pub app_name: Option<String>,
+    /// The header of the API key, if there is one
+    pub api_key_header: Option<String>,
+    /// Whether a bearer token is sent
+    pub bearer_token: bool,
+    /// The timeout of the warm up, if the channel is warmed up
+    pub warm_up_timeout_ms: Option<u64>,
+}
+
+/// Returns the prefix of the override variables of a profile file
+fn prefix(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The endpoint, TLS and interceptor settings of a client in one TOML file
+///
+/// ```toml
+/// [endpoint]
+/// uri = "https://payments.internal:8443"
+/// timeout_ms = 5000
+/// connect_timeout_ms = 1000
+///
+/// [tls]
+/// ca_file = "ca.pem"
+/// domain = "payments.internal"
+///
+/// [auth]
+/// api_key = "${PAYMENTS_API_KEY}"
+/// api_key_header = "x-api-key"
+/// bearer_token = "${PAYMENTS_TOKEN}"
+///
+/// [client_info]
+/// app_name = "billing-worker"
+/// ```
+/// Only `endpoint.uri` is required. The TLS settings apply to `https`
+/// endpoints, which trust the system roots without a `tls.ca_file`. A
+/// relative `ca_file` is resolved against the directory of the profile.
+///
+/// Every key can be overridden by an environment variable named after the
+/// prefix, the section and the key, e.g. `PAYMENTS_ENDPOINT_URI` for
+/// `endpoint.uri` of `payments.toml`; the environment beats the file.
+/// `${NAME}` in a string value is replaced by the environment variable
+/// `NAME`, so secrets need not be in the file. Errors name the key path of
+/// the invalid value, unknown keys are rejected. [ClientProfile::validate]
+/// reports all problems of a file at once.
+#[derive(Clone)]
+pub struct ClientProfile {
+    endpoint: Endpoint,
+    tls: Option<ClientTlsConfig>,
+    interceptors: Interceptors,
+    warm_up: Option<WarmUpOptions>,
+    config: EffectiveChannelConfig,
+}
+
+impl ClientProfile {
+    /// Loads a profile file with overrides from the environment
+    ///
+    /// The prefix of the variables is the file name without extension, e.g.
+    /// `PAYMENTS` for `config/payments.toml`:
+    /// ```ignore
+    /// let channel = ClientProfile::load("config/payments.toml")?.connect().await?;
+    /// let payments = PaymentsClient::new(channel);
+    /// ```
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProfileError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(ProfileError::Read)?;
+        let env = |name: &str| std::env::var(name).ok();
+        Self::parse(&text, &prefix(path), &env, path.parent())
+    }
+
+    /// Parses a profile with explicit overrides, e.g. in tests
+    /// # Arguments
+    /// * `text`: The TOML of the profile
+    /// * `prefix`: The prefix of the override variables, e.g. `PAYMENTS`
+    /// * `env`: Returns the value of an environment variable
+    pub fn from_toml(
+        text: &str,
+        prefix: &str,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ProfileError> {
+        Self::parse(text, prefix, &env, None)
+    }
+
+    fn parse(
+        text: &str,
+        prefix: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+        base: Option<&Path>,
+    ) -> Result<Self, ProfileError> {
+        let mut issues = Issues::default();
+        match Self::build(text, prefix, env, base, &mut issues) {
+            Some(profile) => Ok(profile),
+            None => Err(issues
+                .first_error()
+                .expect("a failed build records an error")),
+        }
+    }
+
+    /// Runs every check of the profile and records the problems
+    ///
+    /// Returns the profile, if there are no errors.
+    fn build(
+        text: &str,
+        prefix: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+        base: Option<&Path>,
+        issues: &mut Issues,
+    ) -> Option<Self> {
+        let table = issues.check(text.parse().map_err(ProfileError::Parse))?;
+        let source = Source { table, prefix, env };
+        source.check_keys(issues);
+
+        let endpoint = issues.check(source.endpoint());
+        let timeout = issues.check(source.millis("endpoint.timeout_ms")).flatten();
+        let connect_timeout = issues
+            .check(source.millis("endpoint.connect_timeout_ms"))
+            .flatten();
+
+        let tls = issues.check(source.ca(base));
+        let domain = issues.check(source.string("tls.domain")).flatten();
+
+        let mut interceptors: Vec<BoxedInterceptor> = Vec::new();
+        let app_name = issues
+            .check(source.string("client_info.app_name"))
+            .flatten();
+        if let Some(app_name) = &app_name {
+            interceptors.push(Box::new(ClientInfoInterceptor::new(app_name)));
+        }
+        let api_key = issues.check(source.api_key()).flatten();
+        let api_key_header = api_key
+            .as_ref()
+            .map(|interceptor| interceptor.header_key().as_str().to_string());
+        interceptors.extend(api_key.map(|interceptor| Box::new(interceptor) as BoxedInterceptor));
+        let bearer_token = issues.check(source.bearer_token()).flatten();
+        let has_bearer_token = bearer_token.is_some();
+        interceptors.extend(bearer_token);
+
+        let mut endpoint = endpoint?;
+        let https = endpoint.uri().scheme_str() == Some("https");
+        let ca_file = tls.as_ref().and_then(|(_, path)| path.clone());
+        if !https && (ca_file.is_some() || domain.is_some()) {
+            issues.warn("tls", "is ignored, the endpoint is no https endpoint");
+        }
+        if issues.has_errors() {
+            return None;
+        }
+
+        if let Some(timeout) = timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(timeout) = connect_timeout {
+            endpoint = endpoint.connect_timeout(timeout);
+        }
+        let (mut tls, _) = tls?;
+        if let Some(domain) = &domain {
+            tls = tls.domain_name(domain);
+        }
+
+        let config = EffectiveChannelConfig {
+            uri: endpoint.uri().to_string(),
+            timeout_ms: timeout.map(|timeout| timeout.as_millis() as u64),
+            connect_timeout_ms: connect_timeout.map(|timeout| timeout.as_millis() as u64),
+            tcp_keepalive_ms: Some(crate::grpc::TCP_KEEPALIVE.as_millis() as u64),
+            keep_alive_while_idle: true,
+            tls: match ca_file {
+                _ if !https => TlsMode::Plaintext,
+                Some(path) => TlsMode::CaFile(path),
+                None => TlsMode::SystemRoots,
+            },
+            domain: domain.filter(|_| https),
+            app_name,
+            api_key_header,
+            bearer_token: has_bearer_token,
+            warm_up_timeout_ms: None,
+        };
+        Some(Self {
+            endpoint,
+            tls: https.then_some(tls),
+            interceptors: std::sync::Arc::new(std::sync::Mutex::new(interceptors)),
+            warm_up: None,
+            config,
+        })
+    }
+
+    /// Checks a profile file and reports every problem instead of the first
+    ///
+    /// Runs the checks of [ClientProfile::load]: the TOML, the keys, the
+    /// endpoint URI, the timeouts, the CA file, the environment variables and
+    /// the credentials. An empty result means the profile loads.
+    /// ```ignore
+    /// let issues = ClientProfile::validate("config/payments.toml");
+    /// for issue in &issues {
+    ///     eprintln!("{issue}");
+    /// }
+    /// ```
+    pub fn validate(path: impl AsRef<Path>) -> Vec<ValidationIssue> {
+        Self::validate_file(path.as_ref()).1.report()
+    }
+
+    /// Checks a profile file like [ClientProfile::validate] and connects to
+    /// the endpoint, if the profile has no errors
+    ///
+    /// `endpoint.connect_timeout_ms` limits how long the probe waits.
+    pub async fn validate_with_probe(path: impl AsRef<Path>) -> Vec<ValidationIssue> {
+        let (profile, issues) = Self::validate_file(path.as_ref());
+        let mut report = issues.report();
+        if let Some(profile) = profile
+            && let Err(e) = ClientFactory::from_profile(&profile).await
+        {
+            report.push(ValidationIssue {
+                severity: Severity::Error,
+                key: "endpoint.uri".to_string(),
+                message: format!("The endpoint can not be reached: {e}"),
+            });
+        }
+        report
+    }
+
+    /// Checks a profile with explicit overrides like [ClientProfile::validate]
+    /// # Arguments
+    /// * `text`: The TOML of the profile
+    /// * `prefix`: The prefix of the override variables, e.g. `PAYMENTS`
+    /// * `env`: Returns the value of an environment variable
+    pub fn validate_toml(
+        text: &str,
+        prefix: &str,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Vec<ValidationIssue> {
+        let mut issues = Issues::default();
+        Self::build(text, prefix, &env, None, &mut issues);
+        issues.report()
+    }
+
+    fn validate_file(path: &Path) -> (Option<Self>, Issues) {
+        let mut issues = Issues::default();
+        let profile = issues
+            .check(std::fs::read_to_string(path).map_err(ProfileError::Read))
+            .and_then(|text| {
+                let env = |name: &str| std::env::var(name).ok();
+                Self::build(&text, &prefix(path), &env, path.parent(), &mut issues)
+            });
+        (profile, issues)
+    }
+
+    /// Returns the endpoint with the timeouts of the profile
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Returns the TLS configuration, if the endpoint is `https`
+    pub fn tls(&self) -> Option<&ClientTlsConfig> {
+        self.tls.as_ref()
+    }
+
+    /// Returns the interceptors of the profile; clones share the chain
+    pub fn interceptors(&self) -> Interceptors {
+        self.interceptors.clone()
+    }
+
+    /// Warms up the channels of the profile, when they are connected
+    ///
+    /// Connecting then runs the probe of the options on the new connection
+    /// and fails with [ChannelError::NotReady], if it fails, or with
+    /// [ChannelError::DeadlineExceeded], if it is not ready within the
+    /// timeout of the options.
+    /// ```ignore
+    /// let options = WarmUpOptions::new(Duration::from_secs(5)).with_probe(health_check);
+    /// let channel = ClientProfile::load("config/payments.toml")?
+    ///     .with_warm_up(options)
+    ///     .connect()
+    ///     .await?;
+    /// ```
+    pub fn with_warm_up(mut self, options: WarmUpOptions) -> Self {
+        self.config.warm_up_timeout_ms = Some(options.timeout().as_millis() as u64);
+        self.warm_up = Some(options);
+        self
+    }
+
+    /// Returns the warm up options, if the channels are warmed up
+    pub fn warm_up(&self) -> Option<&WarmUpOptions> {
+        self.warm_up.as_ref()
+    }
+
+    /// Returns the settings the channels of the profile are built with
+    pub fn effective_config(&self) -> &EffectiveChannelConfig {
+        &self.config
+    }
+
+    /// Connects a channel like [crate::grpc::channel], that calls the
+    /// interceptors of the profile on every request
+    ///
+    /// Use [ClientFactory::from_profile] for several clients of the profile.
+    pub async fn connect(&self) -> Result<FactoryService, ChannelError> {
+        Ok(ClientFactory::from_profile(self).await?.client())
+    }
+
+    /// Connects like [ClientProfile::connect] and returns the settings of the
+    /// channel with it
+    pub async fn connect_with_config(
+        &self,
+    ) -> Result<(FactoryService, EffectiveChannelConfig), ChannelError> {
+        Ok((self.connect().await?, self.config.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tonic::codec::ProstCodec;
+
+    use crate::assert_sets_metadata;
+    use crate::grpc::connection::ChannelError;
+    use crate::grpc::interceptor::CompositeInterceptor;
+    use crate::grpc::profile::{
+        ClientProfile, EffectiveChannelConfig, ProfileError, Severity, TlsMode,
+    };
+    use crate::grpc::warm_up::WarmUpOptions;
+
+    const PROFILE: &str = r#"
+[endpoint]
+uri = "http://payments.internal:8080"
+timeout_ms = 5000
+
+[auth]
+api_key = "file-key"
+bearer_token = "${PAYMENTS_TOKEN}"
+
+[client_info]
+app_name = "billing-worker"
+"#;
+
+    fn env<const N: usize>(variables: [(&str, &str); N]) -> impl Fn(&str) -> Option<String> {
+        let variables: HashMap<String, String> = variables
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| variables.get(name).cloned()
+    }
+
+    fn invalid_key(result: Result<ClientProfile, ProfileError>) -> String {
+        match result {
+            Err(ProfileError::Invalid { key, .. }) => key,
+            Err(e) => panic!("Expected an invalid key, got {e}"),
+            Ok(_) => panic!("Expected an invalid key, got a profile"),
+        }
+    }
+
+    #[test]
+    fn test_file_only() {
+        let dir =
+            std::env::temp_dir().join(format!("grpc-utils-rs-{}-profile", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("grpc_utils_profile_test.toml");
+        std::fs::write(&path, PROFILE.replace("${PAYMENTS_TOKEN}", "file-token")).unwrap();
+
+        let profile = ClientProfile::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("http://payments.internal:8080/", profile.endpoint().uri());
+        assert!(profile.tls().is_none());
+        assert_sets_metadata!(CompositeInterceptor::new(profile.interceptors()), {
+            "x-api-key" => "file-key",
+            "authorization" => "Bearer file-token",
+            "x-client-app" => "billing-worker",
+        });
+        assert!(matches!(
+            ClientProfile::load(dir.join("missing.toml")),
+            Err(ProfileError::Read(_))
+        ));
+    }
+
+    #[test]
+    fn test_env_override() {
+        let profile = ClientProfile::from_toml(
+            PROFILE,
+            "PAYMENTS",
+            env([
+                ("PAYMENTS_TOKEN", "secret-token"),
+                ("PAYMENTS_ENDPOINT_URI", "https://payments.example.com"),
+                ("PAYMENTS_AUTH_API_KEY", "env-key"),
+                ("PAYMENTS_AUTH_API_KEY_HEADER", "x-payments-key"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!("https://payments.example.com/", profile.endpoint().uri());
+        assert!(profile.tls().is_some());
+        assert_sets_metadata!(CompositeInterceptor::new(profile.interceptors()), {
+            "x-payments-key" => "env-key",
+            "authorization" => "Bearer secret-token",
+        });
+    }
+
+    #[test]
+    fn test_missing_secret() {
+        let result = ClientProfile::from_toml(PROFILE, "PAYMENTS", env([]));
+
+        match result {
+            Err(ProfileError::MissingVariable { key, variable }) => {
+                assert_eq!("auth.bearer_token", key);
+                assert_eq!("PAYMENTS_TOKEN", variable);
+            }
+            _ => panic!("Expected a missing variable"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let profile = |text: &str| ClientProfile::from_toml(text, "P", env([]));
+
+        assert_eq!(
+            "endpoint.uri",
+            invalid_key(profile("[endpoint]\ntimeout_ms = 1"))
+        );
+        assert_eq!(
+            "endpoint.timeout",
+            invalid_key(profile("[endpoint]\nuri = \"http://a\"\ntimeout = 1"))
+        );
+        assert_eq!(
+            "endpoint.timeout_ms",
+            invalid_key(profile(
+                "[endpoint]\nuri = \"http://a\"\ntimeout_ms = \"1s\""
+            ))
+        );
+        assert_eq!(
+            "proxy",
+            invalid_key(profile(
+                "[endpoint]\nuri = \"http://a\"\n[proxy]\nuri = \"b\""
+            ))
+        );
+        assert_eq!(
+            "auth.api_key",
+            invalid_key(profile(
+                "[endpoint]\nuri = \"http://a\"\n[auth]\napi_key = \"in\\nvalid\""
+            ))
+        );
+        assert_eq!(
+            "endpoint.connect_timeout_ms",
+            invalid_key(ClientProfile::from_toml(
+                "[endpoint]\nuri = \"http://a\"",
+                "P",
+                env([("P_ENDPOINT_CONNECT_TIMEOUT_MS", "soon")]),
+            ))
+        );
+        assert!(matches!(profile("[endpoint"), Err(ProfileError::Parse(_))));
+    }
+
+    #[test]
+    fn test_validate_reports_all() {
+        let issues = ClientProfile::validate_toml(
+            r#"
+[endpoint]
+uri = "http://payments.internal:8080"
+timeout_ms = "5s"
+retries = 3
+
+[tls]
+domain = "payments.internal"
+
+[auth]
+api_key = ""
+bearer_token = "${PAYMENTS_TOKEN}"
+
+[client_info]
+app_name = 1
+"#,
+            "PAYMENTS",
+            env([("PAYMENTS_ENDPOINT_CONNECT_TIMEOUT_MS", "soon")]),
+        );
+
+        let keys: Vec<(Severity, &str)> = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.key.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Severity::Error, "endpoint.retries"),
+                (Severity::Error, "endpoint.timeout_ms"),
+                (Severity::Error, "endpoint.connect_timeout_ms"),
+                (Severity::Error, "client_info.app_name"),
+                (Severity::Error, "auth.api_key"),
+                (Severity::Error, "auth.bearer_token"),
+                (Severity::Warning, "tls"),
+            ],
+            keys
+        );
+        assert_eq!(
+            "error: The key auth.api_key must not be empty",
+            issues[4].to_string()
+        );
+        assert!(
+            ClientProfile::validate_toml(PROFILE, "PAYMENTS", env([("PAYMENTS_TOKEN", "t")]))
+                .is_empty()
+        );
+        assert_eq!(
+            "",
+            ClientProfile::validate_toml("[endpoint", "P", env([]))[0].key
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_with_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let dir = std::env::temp_dir().join(format!("grpc-utils-rs-{}-probe", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("grpc_utils_probe_test.toml");
+        std::fs::write(&path, format!("[endpoint]\nuri = \"http://{addr}\"")).unwrap();
+
+        let without_probe = ClientProfile::validate(&path);
+        let with_probe = ClientProfile::validate_with_probe(&path).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(without_probe.is_empty());
+        assert_eq!(1, with_probe.len());
+        assert_eq!(Severity::Error, with_probe[0].severity);
+        assert_eq!("endpoint.uri", with_probe[0].key);
+        assert_eq!("", ClientProfile::validate(dir.join("missing.toml"))[0].key);
+    }
+
+    #[test]
+    fn test_effective_config_defaults() {
+        let profile =
+            ClientProfile::from_toml("[endpoint]\nuri = \"http://a:8080\"", "P", env([])).unwrap();
+
+        assert_eq!(
+            &EffectiveChannelConfig {
+                uri: "http://a:8080/".to_string(),
+                timeout_ms: None,
+                connect_timeout_ms: None,
+                tcp_keepalive_ms: Some(60_000),
+                keep_alive_while_idle: true,
+                tls: TlsMode::Plaintext,
+                domain: None,
+                app_name: None,
+                api_key_header: None,
+                bearer_token: false,
+                warm_up_timeout_ms: None,
+            },
+            profile.effective_config()
+        );
+    }
+
+    #[test]
+    fn test_effective_config_without_secrets() {
+        let profile = ClientProfile::from_toml(
+            PROFILE,
+            "PAYMENTS",
+            env([
+                ("PAYMENTS_TOKEN", "secret-token"),
+                ("PAYMENTS_ENDPOINT_URI", "https://payments.example.com"),
+                ("PAYMENTS_TLS_DOMAIN", "payments.internal"),
+            ]),
+        )
+        .unwrap();
+        let config = profile.effective_config();
+
+        assert_eq!(Some(5000), config.timeout_ms);
+        assert_eq!(TlsMode::SystemRoots, config.tls);
+        assert_eq!(Some("payments.internal"), config.domain.as_deref());
+        assert_eq!(Some("billing-worker"), config.app_name.as_deref());
+        assert_eq!(Some("x-api-key"), config.api_key_header.as_deref());
+        assert!(config.bearer_token);
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("file-key"));
+        assert!(!debug.contains("secret-token"));
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+
+        let profile = ClientProfile::from_toml(
+            PROFILE,
+            "PAYMENTS",
+            env([
+                ("PAYMENTS_TOKEN", "secret-token"),
+                ("PAYMENTS_ENDPOINT_URI", &format!("http://{addr}")),
+            ]),
+        )
+        .unwrap();
+        let (service, config) = profile.connect_with_config().await.unwrap();
+        assert_eq!(profile.effective_config(), &config);
+        let mut client = tonic::client::Grpc::new(service);
+        client.ready().await.unwrap();
+        let status = client
+            .unary(
+                tonic::Request::new(()),
+                http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                ProstCodec::<(), ()>::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unimplemented, status.code());
+
+        let failing = WarmUpOptions::new(Duration::from_secs(5))
+            .with_probe(|_channel| async { Err(tonic::Status::unavailable("starting")) });
+        let result = profile.clone().with_warm_up(failing).connect().await;
+        assert!(matches!(result, Err(ChannelError::NotReady(_))));
+
+        let pending = WarmUpOptions::new(Duration::from_millis(50))
+            .with_probe(|_channel| std::future::pending());
+        let profile = profile.with_warm_up(pending);
+        assert_eq!(Some(50), profile.effective_config().warm_up_timeout_ms);
+        let result = profile.connect().await;
+        assert!(matches!(result, Err(ChannelError::DeadlineExceeded { .. })));
+    }
+}
@@ -0,0 +1,61 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Uri;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+use tonic::transport::{Channel, Endpoint};
+use tower_service::Service;
+
+use crate::grpc::connection::{channel_with_connector, ChannelError};
+
+/// Connects with TLS using a rustls configuration of this crate
+#[derive(Clone)]
+struct RustlsConnector {
+    config: Arc<ClientConfig>,
+}
+
+impl Service<Uri> for RustlsConnector {
+    type Response = TlsStream<TcpStream>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = TlsConnector::from(self.config.clone());
+        Box::pin(async move {
+            let host = uri.host().ok_or("The endpoint has no host")?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let port = uri.port_u16().unwrap_or(443);
+            let server_name = ServerName::try_from(host.to_string())?;
+
+            let stream = TcpStream::connect((host, port)).await?;
+            stream.set_nodelay(true)?;
+            crate::grpc::set_tcp_keepalive(&stream)?;
+            Ok(connector.connect(server_name, stream).await?)
+        })
+    }
+}
+
+/// Creates a [tonic::transport::Channel] to an `https` endpoint, that does
+/// the TLS with the rustls configuration
+///
+/// The connection is kept alive like the one of [crate::grpc::channel], with
+/// HTTP/2 pings while idle and TCP keepalives.
+pub(crate) async fn channel(uri: Uri, config: Arc<ClientConfig>) -> Result<Channel, ChannelError> {
+    // The connector does the TLS, so tonic must not see an https URI
+    let authority = uri.authority().map(|authority| authority.as_str());
+    let endpoint = Endpoint::from_shared(format!("http://{}", authority.unwrap_or_default()))
+        .map_err(ChannelError::Connect)?
+        .origin(uri);
+
+    channel_with_connector(None, endpoint, RustlsConnector { config }).await
+}
@@ -0,0 +1,228 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// The time [BackgroundTasks::shutdown] waits for the tasks by default
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The signal, that the [BackgroundTasks] of a task shut down
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Waits until the tasks shut down
+    pub async fn wait(&mut self) {
+        // A dropped sender means, that the tasks are being aborted
+        let _ = self.0.wait_for(|shutdown| *shutdown).await;
+    }
+
+    /// Returns true, if the tasks shut down
+    pub fn is_shutdown(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+struct Tasks {
+    signal: watch::Sender<bool>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Tasks {
+    fn handles(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.handles.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Tasks {
+    /// Aborts the tasks, that were not shut down, so they do not outlive
+    /// their owner
+    fn drop(&mut self) {
+        for handle in self.handles().drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+/// The background tasks of a component, shut down together
+///
+/// The helpers of this crate, that spawn tasks, like
+/// [StatsCollector::spawn_flusher](crate::grpc::interceptor::stats::StatsCollector::spawn_flusher),
+/// register them here. [BackgroundTasks::shutdown] signals all tasks and
+/// waits for them to exit, tasks still running after the timeout are
+/// aborted. Clones share the tasks, when the last clone is dropped without
+/// a shutdown, the tasks are aborted.
+///
+/// Use [BackgroundTasks::global] for the tasks of the whole process, or one
+/// set per component to shut them down separately.
+#[derive(Clone)]
+pub struct BackgroundTasks {
+    tasks: Arc<Tasks>,
+    timeout: Duration,
+}
+
+impl Default for BackgroundTasks {
+    fn default() -> Self {
+        Self {
+            tasks: Arc::new(Tasks {
+                signal: watch::Sender::new(false),
+                handles: Mutex::new(Vec::new()),
+            }),
+            timeout: SHUTDOWN_TIMEOUT,
+        }
+    }
+}
+
+impl BackgroundTasks {
+    /// Creates a new, empty set of tasks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the tasks of the process
+    pub fn global() -> &'static BackgroundTasks {
+        static GLOBAL: OnceLock<BackgroundTasks> = OnceLock::new();
+        GLOBAL.get_or_init(BackgroundTasks::new)
+    }
+
+    /// Sets the time [BackgroundTasks::shutdown] waits for the tasks, 5
+    /// seconds by default
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Spawns a task, that exits when it receives the [ShutdownSignal]
+    ///
+    /// After a shutdown the task receives the signal right away.
+    /// # Arguments
+    /// * `task`: Creates the future of the task from the signal
+    pub fn spawn<F, Fut>(&self, task: F)
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task(ShutdownSignal(self.tasks.signal.subscribe())));
+        let mut handles = self.tasks.handles();
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(handle);
+    }
+
+    /// Returns the number of running tasks
+    pub fn len(&self) -> usize {
+        self.tasks
+            .handles()
+            .iter()
+            .filter(|handle| !handle.is_finished())
+            .count()
+    }
+
+    /// Returns true, if no task is running
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Signals all tasks to exit and waits for them until the timeout
+    ///
+    /// Returns the number of tasks, that did not exit in time and were
+    /// aborted.
+    pub async fn shutdown(&self) -> usize {
+        self.tasks.signal.send_replace(true);
+        let handles = std::mem::take(&mut *self.tasks.handles());
+        let deadline = tokio::time::Instant::now() + self.timeout;
+
+        let mut aborted = 0;
+        for mut handle in handles {
+            if tokio::time::timeout_at(deadline, &mut handle)
+                .await
+                .is_err()
+            {
+                handle.abort();
+                aborted += 1;
+            }
+        }
+        if aborted > 0 {
+            log::warn!("Aborted {aborted} background tasks, that did not shut down in time");
+        }
+        aborted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::grpc::tasks::BackgroundTasks;
+
+    /// Counts, how many tasks completed or were dropped
+    struct Completion(Arc<AtomicUsize>);
+
+    impl Drop for Completion {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn spawn_waiting(tasks: &BackgroundTasks, completed: &Arc<AtomicUsize>) {
+        let completion = Completion(completed.clone());
+        tasks.spawn(|mut signal| async move {
+            signal.wait().await;
+            drop(completion);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let tasks = BackgroundTasks::new();
+        let completed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            spawn_waiting(&tasks, &completed);
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(3, tasks.len());
+        assert_eq!(0, completed.load(Ordering::SeqCst));
+
+        assert_eq!(0, tasks.shutdown().await);
+        assert_eq!(3, completed.load(Ordering::SeqCst));
+        assert!(tasks.is_empty());
+
+        // Tasks spawned after the shutdown exit right away
+        spawn_waiting(&tasks, &completed);
+        assert_eq!(0, tasks.shutdown().await);
+        assert_eq!(4, completed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout() {
+        let tasks = BackgroundTasks::new().with_shutdown_timeout(Duration::from_millis(20));
+        let completed = Arc::new(AtomicUsize::new(0));
+        spawn_waiting(&tasks, &completed);
+        let completion = Completion(completed.clone());
+        tasks.spawn(|_signal| async move {
+            // Ignores the signal
+            std::future::pending::<()>().await;
+            drop(completion);
+        });
+
+        assert_eq!(1, tasks.shutdown().await);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(2, completed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_drop_aborts() {
+        let tasks = BackgroundTasks::new();
+        let completed = Arc::new(AtomicUsize::new(0));
+        spawn_waiting(&tasks, &completed);
+        spawn_waiting(&tasks.clone(), &completed);
+        tokio::task::yield_now().await;
+
+        drop(tasks);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(2, completed.load(Ordering::SeqCst));
+    }
+}
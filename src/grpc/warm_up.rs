@@ -0,0 +1,313 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use http::Uri;
+use tonic::body::BoxBody;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Status;
+use tower_service::Service;
+
+use crate::grpc::layer::BoxError;
+
+type Probe =
+    Arc<dyn Fn(Channel) -> Pin<Box<dyn Future<Output = Result<(), Status>> + Send>> + Send + Sync>;
+
+/// The options of [warm_up]
+#[derive(Clone)]
+pub struct WarmUpOptions {
+    timeout: Duration,
+    min_ready: Option<usize>,
+    probe: Option<Probe>,
+}
+
+impl WarmUpOptions {
+    /// Creates options, that wait for all endpoints
+    /// # Arguments
+    /// * `timeout`: The maximum time to wait for the endpoints
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            min_ready: None,
+            probe: None,
+        }
+    }
+
+    /// Returns the maximum time to wait for the endpoints
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns as soon as this number of endpoints is ready
+    pub fn with_min_ready(mut self, min_ready: usize) -> Self {
+        self.min_ready = Some(min_ready);
+        self
+    }
+
+    /// Sets a readiness probe, e.g. a health check, that is called on every
+    /// connected channel
+    ///
+    /// An endpoint is ready, when the probe returns Ok.
+    pub fn with_probe<F, Fut>(mut self, probe: F) -> Self
+    where
+        F: Fn(Channel) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Status>> + Send + 'static,
+    {
+        self.probe = Some(Arc::new(move |channel| Box::pin(probe(channel))));
+        self
+    }
+}
+
+/// The result of warming up one endpoint
+#[derive(Debug)]
+pub enum EndpointState {
+    /// The endpoint is connected and passed the probe
+    Ready(Channel),
+    /// Connecting or probing failed
+    Failed(BoxError),
+    /// The endpoint was not ready before [warm_up] returned
+    Pending,
+}
+
+/// The results of [warm_up]
+#[derive(Debug)]
+pub struct WarmUpReport {
+    /// The state of every endpoint, in the order they were given
+    pub endpoints: Vec<(Uri, EndpointState)>,
+    /// True, if the timeout elapsed before enough endpoints were ready
+    pub timed_out: bool,
+}
+
+impl WarmUpReport {
+    /// Returns the number of ready endpoints
+    pub fn ready(&self) -> usize {
+        self.endpoints
+            .iter()
+            .filter(|(_, state)| matches!(state, EndpointState::Ready(_)))
+            .count()
+    }
+
+    /// Returns a channel balancing the calls over the ready connections, or
+    /// `None`, if no endpoint is ready
+    ///
+    /// The channel uses the connections of the report, so its first calls
+    /// do not connect again. Endpoints, that failed or were pending, are not
+    /// part of it.
+    pub fn channel(&self) -> Option<WarmChannel> {
+        let channels: Arc<[Channel]> = self
+            .endpoints
+            .iter()
+            .filter_map(|(_, state)| match state {
+                EndpointState::Ready(channel) => Some(channel.clone()),
+                _ => None,
+            })
+            .collect();
+        (!channels.is_empty()).then(|| WarmChannel {
+            channels,
+            next: Arc::new(AtomicUsize::new(0)),
+            selected: None,
+        })
+    }
+}
+
+/// A channel sending the calls round robin over the connections, that
+/// were ready in a [WarmUpReport]
+///
+/// Every connection reconnects on its own like a [Channel]. Use it in place
+/// of a channel, e.g. `GreeterClient::new(report.channel().unwrap())`.
+#[derive(Clone, Debug)]
+pub struct WarmChannel {
+    channels: Arc<[Channel]>,
+    next: Arc<AtomicUsize>,
+    /// The channel of the next call, that was driven to readiness
+    selected: Option<Channel>,
+}
+
+impl Service<http::Request<BoxBody>> for WarmChannel {
+    type Response = <Channel as Service<http::Request<BoxBody>>>::Response;
+    type Error = <Channel as Service<http::Request<BoxBody>>>::Error;
+    type Future = <Channel as Service<http::Request<BoxBody>>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let channels = &self.channels;
+        let next = &self.next;
+        self.selected
+            .get_or_insert_with(|| {
+                channels[next.fetch_add(1, Ordering::Relaxed) % channels.len()].clone()
+            })
+            .poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        self.selected
+            .take()
+            .expect("poll_ready must be called before call")
+            .call(req)
+    }
+}
+
+async fn warm_up_endpoint(endpoint: Endpoint, probe: Option<Probe>) -> EndpointState {
+    let channel = match endpoint.connect().await {
+        Ok(channel) => channel,
+        Err(e) => return EndpointState::Failed(Box::new(e)),
+    };
+    if let Some(probe) = probe
+        && let Err(status) = probe(channel.clone()).await
+    {
+        return EndpointState::Failed(Box::new(status));
+    }
+    EndpointState::Ready(channel)
+}
+
+/// Connects to the endpoints eagerly, so the first calls do not pay for the
+/// connection setup
+///
+/// All endpoints are connected concurrently. The function returns, when the
+/// minimum number of endpoints (by default all) is ready, when every endpoint
+/// either is ready or failed, or when the timeout elapses. The ready channels
+/// are part of the report; [WarmUpReport::channel] balances the calls over
+/// them.
+/// # Arguments
+/// * `endpoints`: The endpoints to connect to, configured like for [crate::grpc::channel]
+/// * `options`: The timeout, the minimum number of ready endpoints and the probe
+pub async fn warm_up(
+    endpoints: impl IntoIterator<Item = Endpoint>,
+    options: WarmUpOptions,
+) -> WarmUpReport {
+    let endpoints: Vec<Endpoint> = endpoints.into_iter().collect();
+    let min_ready = options.min_ready.unwrap_or(endpoints.len());
+    let mut states: Vec<(Uri, EndpointState)> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.uri().clone(), EndpointState::Pending))
+        .collect();
+
+    let mut in_flight: FuturesUnordered<_> = endpoints
+        .into_iter()
+        .enumerate()
+        .map(|(index, endpoint)| {
+            let probe = options.probe.clone();
+            async move { (index, warm_up_endpoint(endpoint, probe).await) }
+        })
+        .collect();
+
+    let timeout = tokio::time::sleep(options.timeout);
+    tokio::pin!(timeout);
+    let mut ready = 0;
+    let mut timed_out = false;
+    while ready < min_ready {
+        tokio::select! {
+            result = in_flight.next() => match result {
+                Some((index, state)) => {
+                    if let EndpointState::Failed(e) = &state {
+                        log::warn!("Warming up {} failed: {e}", states[index].0);
+                    } else {
+                        ready += 1;
+                    }
+                    states[index].1 = state;
+                }
+                None => break,
+            },
+            _ = &mut timeout => {
+                timed_out = true;
+                break;
+            }
+        }
+    }
+
+    WarmUpReport {
+        endpoints: states,
+        timed_out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tonic::codec::ProstCodec;
+    use tonic::transport::Endpoint;
+
+    use crate::grpc::warm_up::{warm_up, EndpointState, WarmUpOptions};
+
+    /// Returns an endpoint accepting connections and one, that is down
+    async fn pool() -> (Vec<Endpoint>, TcpListener) {
+        let up = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let down = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoints = [up.local_addr().unwrap(), down.local_addr().unwrap()]
+            .iter()
+            .map(|addr| Endpoint::from_shared(format!("http://{addr}")).unwrap())
+            .collect();
+        drop(down);
+        (endpoints, up)
+    }
+
+    #[tokio::test]
+    async fn test_partial_success() {
+        let (endpoints, _up) = pool().await;
+
+        let report = warm_up(endpoints, WarmUpOptions::new(Duration::from_secs(5))).await;
+
+        assert!(!report.timed_out);
+        assert_eq!(1, report.ready());
+        assert!(matches!(report.endpoints[0].1, EndpointState::Ready(_)));
+        assert!(matches!(report.endpoints[1].1, EndpointState::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_channel() {
+        let (endpoints, up) = pool().await;
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(up, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(tonic::service::Routes::default())
+                .serve_with_incoming(incoming),
+        );
+
+        let report = warm_up(endpoints, WarmUpOptions::new(Duration::from_secs(5))).await;
+        let mut client = tonic::client::Grpc::new(report.channel().unwrap());
+
+        // Every call goes to the ready endpoint
+        for _ in 0..2 {
+            client.ready().await.unwrap();
+            let status = client
+                .unary(
+                    tonic::Request::new(()),
+                    http::uri::PathAndQuery::from_static("/test.Service/Method"),
+                    ProstCodec::<(), ()>::default(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(tonic::Code::Unimplemented, status.code());
+        }
+
+        let (down, _up) = pool().await;
+        let report = warm_up(
+            down.into_iter().skip(1),
+            WarmUpOptions::new(Duration::from_secs(5)),
+        )
+        .await;
+        assert!(report.channel().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let (endpoints, _up) = pool().await;
+
+        // The probe of the available endpoint never finishes
+        let options = WarmUpOptions::new(Duration::from_millis(100))
+            .with_min_ready(1)
+            .with_probe(|_channel| std::future::pending::<Result<(), tonic::Status>>());
+        let report = warm_up(endpoints, options).await;
+
+        assert!(report.timed_out);
+        assert_eq!(0, report.ready());
+        assert!(matches!(report.endpoints[0].1, EndpointState::Pending));
+        assert!(matches!(report.endpoints[1].1, EndpointState::Failed(_)));
+    }
+}